@@ -1,23 +1,437 @@
+use std::ffi::OsString;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use walkdir::WalkDir;
-use chrono::Utc;
-use std::collections::HashMap;
+use chrono::{Datelike, DateTime, Local, NaiveDateTime, Timelike, Utc};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use filetime::FileTime;
+use flate2::{write::GzEncoder, Compression};
+use driveguard_shared::checksum;
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+const INDEX_FILE: &str = "backup_index.json";
+const DIFF_FILE: &str = "backup_diff.txt";
+const STATE_FILE: &str = ".driveguard_state.json";
+const LOG_FILE: &str = "backup.txt";
+const ERROR_LOG_FILE: &str = "backup_errors.txt";
+const INCOMPLETE_MARKER: &str = ".driveguard_incomplete"; // Left in interrupted backups, flags them for cleanup
+const BACKUP_FOLDER_FORMAT: &str = "%Y-%m-%dT%H-%M-%S";
+
+// Path component rules that hold on NTFS as well as FAT/exFAT USB drives
+const ILLEGAL_CHARS: &str = "<>:\"/\\|?*";
+const MAX_COMPONENT_LEN: usize = 255;
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+// Buffer used for the per-file copy loop unless the schedule overrides it
+pub const DEFAULT_COPY_BUFFER_SIZE: usize = 1024 * 1024;
+
+// Largest file FAT32 can store (4 GB - 1 byte)
+pub const FAT32_MAX_FILE_SIZE: u64 = 0xFFFF_FFFF;
+
+// Limits for a source that is a whole drive (C:\) when the schedule sets none; most likely a config mistake
+pub const VOLUME_ROOT_MAX_FILES: usize = 100_000;
+pub const VOLUME_ROOT_MAX_DEPTH: usize = 32;
+
+// Longest path the classic Windows file API accepts, terminator included
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+// Lines per category in backup.txt and backup_errors.txt; the rest only show up in the counts
+const MAX_LOG_LINES: usize = 1000;
+
+// How often the in-progress state file is rewritten
+const STATE_FLUSH_FILES: usize = 100;
+const STATE_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+// Copy rate smoothing for the ETA: samples shorter than the minimum are merged, and a sample's
+// weight approaches 1 as its length approaches a few smoothing periods
+const THROUGHPUT_MIN_SAMPLE: Duration = Duration::from_millis(500);
+const THROUGHPUT_SMOOTHING: Duration = Duration::from_secs(20);
+
+// A state file touched more recently than this may belong to a backup that is still running
+const ACTIVE_STATE_AGE: Duration = Duration::from_secs(600);
+
+/// How the engine decides which files need to be copied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupMode {
+    /// Copy every file on every run
+    #[default]
+    Full,
+    /// Copy only files with the Windows archive attribute set and clear it afterwards
+    ArchiveBit,
+    /// Copy only files whose size or modification time differ from the latest backup
+    Incremental,
+}
+
+/// What to do when a configured source path doesn't exist
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingSourcePolicy {
+    /// Back up the remaining sources and report the missing ones
+    #[default]
+    SkipMissing,
+    /// Abort before copying anything
+    FailOnMissing,
+}
+
+/// How a backup's files are stored in the destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// A copy of every file in the timestamped backup folder
+    #[default]
+    FolderTree,
+    /// One deflated `<timestamp>.zip` next to the backup folder, which then only holds the logs and index
+    Zip,
+}
+
+/// Which old backups a schedule keeps; the others are deleted after a successful backup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionPolicy {
+    /// The newest N backups
+    KeepLast(usize),
+    /// Backups made in the last N days
+    KeepDays(u32),
+}
+
+/// A schedule's `exclude` globs, matched against paths relative to the source root. Patterns
+/// without a `/` match names at any depth ("*.tmp", "Thumbs.db", "node_modules"); case is
+/// ignored, as Windows does.
+#[derive(Debug, Clone, Default)]
+pub struct ExcludePatterns {
+    entries: GlobSet, // Files and folders the patterns match
+    folders: GlobSet, // Folders a `<folder>/**` pattern empties completely, so the walk skips them
+}
+
+impl ExcludePatterns {
+    pub fn new(patterns: &[String]) -> Result<Self, String> {
+        let mut entries = GlobSetBuilder::new();
+        let mut folders = GlobSetBuilder::new();
+        
+        for pattern in patterns.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let pattern = pattern.replace('\\', "/");
+            let pattern = pattern.trim_end_matches('/');
+            let pattern = if pattern.contains('/') { pattern.to_string() } else { format!("**/{}", pattern) };
+            
+            entries.add(exclude_glob(&pattern)?);
+            if let Some(folder) = pattern.strip_suffix("/**") {
+                folders.add(exclude_glob(folder)?);
+            }
+        }
+        
+        let build = |builder: GlobSetBuilder| builder.build().map_err(|e| format!("Invalid exclude pattern: {}", e));
+        Ok(Self { entries: build(entries)?, folders: build(folders)? })
+    }
+    
+    /// Whether a walked entry under `root` is left out; `root` itself never is
+    fn excludes(&self, root: &Path, entry: &walkdir::DirEntry) -> bool {
+        let relative = match entry.path().strip_prefix(root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => relative,
+            _ => return false,
+        };
+        
+        let excluded = self.entries.is_match(relative) || (entry.file_type().is_dir() && self.folders.is_match(relative));
+        if excluded {
+            log::debug!("Excluding by pattern: {}", entry.path().display());
+        }
+        excluded
+    }
+}
+
+fn exclude_glob(pattern: &str) -> Result<Glob, String> {
+    GlobBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| format!("Invalid exclude pattern: {}", e))
+}
+
+/// Result of a finished backup, handed back to the UI
+#[derive(Debug, Clone, Default)]
+pub struct BackupSummary {
+    pub folder: String,
+    pub total_files: usize,
+    pub copied_files: usize,
+    pub copied_bytes: u64,
+    pub skipped_files: usize,
+    pub failed_files: Vec<(String, String)>, // (path, error)
+    pub oversized_files: usize, // Included in failed_files; too large for the destination file system
+    pub missing_sources: Vec<String>,
+    pub failed_sources: Vec<(String, String)>, // (source, error); the other sources were still backed up
+    pub error_log: Option<String>, // backup_errors.txt written for failed_files, if any
+}
+
+/// Called with each progress snapshot while a backup runs
+pub type ProgressCallback = Box<dyn FnMut(&BackupProgress) + Send>;
+
+/// Progress snapshot reported to `BackupEngine::on_progress` while a backup runs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BackupProgress {
+    pub processed_files: usize, // copied + skipped + failed
+    pub total_files: usize, // files discovered so far
+    pub processed_bytes: u64, // Size of the processed files
+    pub expected_files: usize, // Counted before the run when `prescan` is on, else 0
+    pub expected_bytes: u64, // Counted before the run when `prescan` is on, else 0
+    pub eta: Option<Duration>, // From the smoothed recent copy rate; needs `expected_bytes`
+}
+
+impl BackupProgress {
+    /// Share of the expected files processed, 0-100; None without a prescan
+    pub fn files_percent(&self) -> Option<f64> {
+        (self.expected_files > 0).then(|| percent(self.processed_files as f64, self.expected_files as f64))
+    }
+    
+    /// Share of the expected bytes processed, 0-100; differs a lot from `files_percent`
+    /// when a few large files make up most of the data
+    pub fn bytes_percent(&self) -> Option<f64> {
+        (self.expected_bytes > 0).then(|| percent(self.processed_bytes as f64, self.expected_bytes as f64))
+    }
+}
+
+// Files can grow or appear after the scan; never report past 100%
+fn percent(done: f64, expected: f64) -> f64 {
+    (done / expected * 100.0).clamp(0.0, 100.0)
+}
+
+/// Copy rate as a time-weighted moving average: each sample counts by how long it took, so a
+/// huge file that took minutes outweighs the burst of small files before it, and old samples fade
+#[derive(Debug, Clone, Copy, Default)]
+struct Throughput {
+    bytes_per_second: Option<f64>,
+    last_sample: Option<(Instant, u64)>, // When and at how many processed bytes the rate was last updated
+}
+
+impl Throughput {
+    fn start(now: Instant) -> Self {
+        Self { bytes_per_second: None, last_sample: Some((now, 0)) }
+    }
+    
+    fn update(&mut self, now: Instant, processed_bytes: u64) {
+        let (then, bytes) = match self.last_sample {
+            Some(sample) => sample,
+            None => {
+                self.last_sample = Some((now, processed_bytes));
+                return;
+            }
+        };
+        
+        // Many small files finish within milliseconds; measure them together
+        let seconds = now.saturating_duration_since(then).as_secs_f64();
+        if seconds < THROUGHPUT_MIN_SAMPLE.as_secs_f64() {
+            return;
+        }
+        
+        let rate = processed_bytes.saturating_sub(bytes) as f64 / seconds;
+        let weight = 1.0 - (-seconds / THROUGHPUT_SMOOTHING.as_secs_f64()).exp();
+        self.bytes_per_second = Some(match self.bytes_per_second {
+            Some(average) => average + weight * (rate - average),
+            None => rate,
+        });
+        self.last_sample = Some((now, processed_bytes));
+    }
+    
+    fn eta(&self, remaining_bytes: u64) -> Option<Duration> {
+        let rate = self.bytes_per_second.filter(|rate| *rate > 0.0)?;
+        Duration::try_from_secs_f64(remaining_bytes as f64 / rate).ok()
+    }
+}
+
+/// Size and modification time of a file captured by a backup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub size: u64,
+    pub modified: i64, // Unix seconds
+}
+
+/// Source state captured by a backup, keyed by path relative to the backup folder
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackupIndex {
+    pub files: BTreeMap<String, IndexEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<BackupMode>, // Unless Full, unchanged files are in the backups before; None in older indexes
+}
+
+impl BackupIndex {
+    pub fn load(backup_folder: &Path) -> Option<Self> {
+        let content = fs::read_to_string(backup_folder.join(INDEX_FILE)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+    
+    pub fn save(&self, backup_folder: &Path) -> std::io::Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(backup_folder.join(INDEX_FILE), content)
+    }
+}
+
+/// Files that changed in the sources since a previous backup
+#[derive(Debug, Clone, Default)]
+pub struct BackupDiff {
+    pub previous_backup: Option<String>,
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+impl BackupDiff {
+    pub fn summary(&self) -> String {
+        format!("{} added, {} modified, {} deleted", self.added.len(), self.modified.len(), self.deleted.len())
+    }
+    
+    pub fn save(&self, backup_folder: &str) -> std::io::Result<()> {
+        let mut content = String::from("DriveGuard Backup Diff\n");
+        content.push_str(&format!("Compared against: {}\n", self.previous_backup.as_deref().unwrap_or("(no previous backup)")));
+        content.push_str(&format!("Summary: {}\n\n", self.summary()));
+        
+        for path in &self.added {
+            content.push_str(&format!("+ {}\n", path));
+        }
+        for path in &self.modified {
+            content.push_str(&format!("~ {}\n", path));
+        }
+        for path in &self.deleted {
+            content.push_str(&format!("- {}\n", path));
+        }
+        
+        fs::write(Path::new(backup_folder).join(DIFF_FILE), content)
+    }
+}
+
+/// Snapshot of a running backup, left behind if the app crashes mid-copy
+#[derive(Debug, Serialize)]
+struct BackupState<'a> {
+    app_version: &'a str,
+    started_at: String,
+    updated_at: String,
+    current_path: String,
+    total_files: usize,
+    copied_files: usize,
+    skipped_files: usize,
+    failed_files: usize,
+}
 
 pub struct BackupEngine {
+    pub mode: BackupMode,
+    pub dedup: bool, // Hard-link identical files instead of storing them twice
+    pub copy_buffer_size: usize, // Bytes per read/write; larger helps slow USB flash and network shares
+    pub workers: usize, // Files copied at the same time (1 = one after another); not for zip or dedup runs
+    pub verify: bool, // Read every copy back and compare its SHA-256 with the source (not for zip runs)
+    pub min_file_age: Duration, // Files modified more recently than this may still be written to
+    pub skip_hidden: bool, // Leave out entries with the hidden attribute (whole folders included)
+    pub skip_system: bool, // Leave out system entries like desktop.ini or System Volume Information
+    pub max_file_size: Option<u64>, // Destination file system limit (FAT32); larger files are skipped
+    pub compress_logs: bool, // Write the log files gzipped
+    pub prescan: bool, // Count files and bytes before copying so progress can show a percentage and ETA
+    pub excluded_paths: Vec<PathBuf>, // Left out with everything inside them (DriveGuard's own files)
+    pub exclude: ExcludePatterns, // The schedule's `exclude` globs; matching folders aren't walked
+    pub max_files: Option<usize>, // Abort when the sources hold more files than this
+    pub max_depth: Option<usize>, // Abort when a source has entries nested deeper than this
+    pub newest_only: Vec<String>, // File name patterns ("report_*.csv"): only the newest match per folder is copied
+    pub missing_source_policy: MissingSourcePolicy,
+    pub output_format: OutputFormat,
+    pub min_free_space: u64, // Bytes that must stay free on the destination after a run (0 = no check)
     pub total_files: usize,
     pub copied_files: usize,
+    pub copied_paths: Vec<String>, // The copied (or linked) files, listed in backup.txt
+    pub copied_bytes: u64, // Size of the files actually copied (or linked) by this run
+    pub skipped_files: usize,
+    pub linked_files: usize,
+    pub bytes_saved: u64,
+    pub verified_files: usize, // Copies whose SHA-256 matched the source
+    pub verify_failures: usize, // Copies that differed or couldn't be read back (also in failed_files)
     pub failed_files: Vec<(String, String)>, // (path, error)
+    pub deferred_files: Vec<String>, // Too recently modified, left for the next run
+    pub superseded_files: Vec<String>, // Left out by newest_only, a newer file of the same group was copied
+    pub oversized_files: usize, // Skipped for exceeding max_file_size (also in failed_files)
+    pub missing_sources: Vec<String>,
+    pub failed_sources: Vec<(String, String)>, // (source, error) for sources that couldn't be copied at all
+    pub processed_bytes: u64,
     pub is_running: bool,
+    pub on_progress: Option<ProgressCallback>,
+    pub cancel: Option<Arc<AtomicBool>>, // Set from another thread to stop the run before its next file
+    
+    expected_files: usize,
+    expected_bytes: u64,
+    dedup_index: HashMap<String, PathBuf>, // content hash -> first destination copy
+    newest_in_group: HashMap<(PathBuf, usize), Option<PathBuf>>, // (folder, newest_only index) -> newest match
+    index: BackupIndex, // Source state captured by the current run
+    previous_index: BackupIndex, // Index of the latest backup, used by incremental mode
+    backup_root: PathBuf,
+    archive: Option<ZipWriter<fs::File>>, // Open while a zip backup runs
+    surveyed: Option<(Vec<String>, Survey)>, // Sources counted by check_free_space, so run_backup needn't walk them again
+    started_at: DateTime<Utc>,
+    clock: fn() -> DateTime<Utc>, // Utc::now; tests freeze it
+    last_state_flush: Instant,
+    files_since_flush: usize,
+    throughput: Throughput,
 }
 
 impl BackupEngine {
     pub fn new() -> Self {
         Self {
+            mode: BackupMode::Full,
+            dedup: false,
+            copy_buffer_size: DEFAULT_COPY_BUFFER_SIZE,
+            workers: 1,
+            verify: false,
+            min_file_age: Duration::ZERO,
+            skip_hidden: false,
+            skip_system: false,
+            max_file_size: None,
+            compress_logs: false,
+            prescan: false,
+            excluded_paths: Vec::new(),
+            exclude: ExcludePatterns::default(),
+            max_files: None,
+            max_depth: None,
+            newest_only: Vec::new(),
+            missing_source_policy: MissingSourcePolicy::SkipMissing,
+            output_format: OutputFormat::FolderTree,
+            min_free_space: 0,
             total_files: 0,
             copied_files: 0,
+            copied_paths: Vec::new(),
+            copied_bytes: 0,
+            skipped_files: 0,
+            linked_files: 0,
+            bytes_saved: 0,
+            verified_files: 0,
+            verify_failures: 0,
             failed_files: Vec::new(),
+            deferred_files: Vec::new(),
+            superseded_files: Vec::new(),
+            oversized_files: 0,
+            missing_sources: Vec::new(),
+            failed_sources: Vec::new(),
+            processed_bytes: 0,
             is_running: false,
+            on_progress: None,
+            cancel: None,
+            expected_files: 0,
+            expected_bytes: 0,
+            dedup_index: HashMap::new(),
+            newest_in_group: HashMap::new(),
+            index: BackupIndex::default(),
+            previous_index: BackupIndex::default(),
+            backup_root: PathBuf::new(),
+            archive: None,
+            surveyed: None,
+            started_at: Utc::now(),
+            clock: Utc::now,
+            last_state_flush: Instant::now(),
+            files_since_flush: 0,
+            throughput: Throughput::default(),
         }
     }
     
@@ -29,140 +443,2687 @@ impl BackupEngine {
         self.is_running = true;
         self.total_files = 0;
         self.copied_files = 0;
+        self.copied_paths.clear();
+        self.copied_bytes = 0;
+        self.skipped_files = 0;
+        self.linked_files = 0;
+        self.bytes_saved = 0;
+        self.verified_files = 0;
+        self.verify_failures = 0;
         self.failed_files.clear();
+        self.deferred_files.clear();
+        self.superseded_files.clear();
+        self.oversized_files = 0;
+        self.failed_sources.clear();
+        self.processed_bytes = 0;
+        self.dedup_index.clear();
+        self.newest_in_group.clear();
+        self.index = BackupIndex::default();
+        self.started_at = (self.clock)();
+        self.last_state_flush = Instant::now();
+        self.files_since_flush = 0;
         
-        // Create timestamped backup folder (ISO 8601, NTFS-safe)
-        let timestamp = Utc::now().format("%Y-%m-%dT%H-%M-%S").to_string();
-        let backup_folder = format!("{}\\{}", destination_base, timestamp);
+        let source_paths = &dedup_sources(source_paths);
+        
+        // Check sources up front so a typo can't silently produce a partial backup
+        self.missing_sources = source_paths
+            .iter()
+            .filter(|s| !Path::new(s).exists())
+            .cloned()
+            .collect();
         
-        fs::create_dir_all(&backup_folder)
-            .map_err(|e| format!("Failed to create backup folder: {}", e))?;
+        if !self.missing_sources.is_empty() && self.missing_source_policy == MissingSourcePolicy::FailOnMissing {
+            self.is_running = false;
+            return Err(format!("Source path(s) not found: {}", self.missing_sources.join(", ")));
+        }
+        
+        (self.expected_files, self.expected_bytes) = match self.surveyed.take() {
+            // check_free_space has just walked these same sources
+            Some((surveyed, survey)) if surveyed == *source_paths => (survey.files, survey.bytes),
+            _ if self.prescan => {
+                let survey = self.survey(source_paths, None);
+                (survey.files, survey.bytes)
+            }
+            _ => (0, 0),
+        };
+        self.throughput = Throughput::start(Instant::now());
+        
+        // Look up the latest backup before this run's folder becomes the latest
+        self.previous_index = if self.mode == BackupMode::Incremental {
+            find_latest_backup(Path::new(destination_base))
+                .as_deref()
+                .and_then(BackupIndex::load)
+                .unwrap_or_default()
+        } else {
+            BackupIndex::default()
+        };
+        self.index.mode = Some(match self.mode {
+            BackupMode::Incremental if self.previous_index.files.is_empty() => BackupMode::Full, // Nothing to build on
+            mode => mode,
+        });
         
-        // Track folder names to avoid duplicates
-        let mut folder_counter: HashMap<String, u32> = HashMap::new();
+        // Create timestamped backup folder (ISO 8601, NTFS-safe)
+        let timestamp = self.started_at.format(BACKUP_FOLDER_FORMAT).to_string();
+        let backup_folder = create_backup_folder(Path::new(destination_base), &timestamp)?;
+        self.backup_root = backup_folder.clone();
+        
+        // Many small files go to a slow drive much faster as one stream
+        self.archive = match self.output_format {
+            OutputFormat::FolderTree => None,
+            OutputFormat::Zip => {
+                let path = zip_archive_path(&backup_folder);
+                let file = fs::File::create(&path)
+                    .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+                Some(ZipWriter::new(file))
+            }
+        };
         
         // Process each source path
-        for source in source_paths {
-            let source_path = Path::new(source);
+        for (source_path, folder_name) in destination_names(source_paths) {
+            let dest_folder = backup_folder.join(folder_name);
+            
+            // One bad source shouldn't sink the others; only the walk limits stop everything
+            match self.copy_directory(&source_path, &dest_folder) {
+                Ok(()) => {}
+                Err(SourceError::Failed(e)) => {
+                    log::error!("Skipping source {}: {}", source_path.display(), e);
+                    self.failed_sources.push((source_path.to_string_lossy().to_string(), e));
+                }
+                Err(SourceError::Abort(e)) => {
+                    self.is_running = false;
+                    return Err(e);
+                }
+                Err(SourceError::Cancelled) => {
+                    log::info!("Backup cancelled after {} of {} files", self.copied_files, self.total_files);
+                    self.is_running = false;
+                    return Err("Backup cancelled by the user".to_string());
+                }
+            }
+        }
+        
+        let attempted = source_paths.len() - self.missing_sources.len();
+        if attempted > 0 && self.failed_sources.len() == attempted {
+            self.is_running = false;
+            let errors: Vec<String> = self.failed_sources.iter().map(|(source, e)| format!("{}: {}", source, e)).collect();
+            return Err(format!("No source could be backed up:\n{}", errors.join("\n")));
+        }
+        
+        // Without its central directory the zip can't be opened
+        if let Some(mut archive) = self.archive.take() {
+            if let Err(e) = archive.finish() {
+                self.is_running = false;
+                return Err(format!("Failed to finish {}: {}", zip_archive_path(&backup_folder).display(), e));
+            }
+        }
+        
+        // Clean completion, the crash-diagnosis state is no longer needed
+        fs::remove_file(backup_folder.join(STATE_FILE)).ok();
+        
+        self.is_running = false;
+        Ok(backup_folder.to_string_lossy().to_string())
+    }
+    
+    /// Compare the current state of the sources against the index of the most
+    /// recent backup under `destination_base`
+    pub fn diff_against_previous(&self, source_paths: &[String], destination_base: &str) -> BackupDiff {
+        let previous = find_latest_backup(Path::new(destination_base));
+        let previous_index = previous.as_deref()
+            .and_then(BackupIndex::load)
+            .unwrap_or_default();
+        let current = scan_sources(source_paths, self.skip_hidden, self.skip_system, &self.excluded_paths, &self.exclude);
+        
+        let mut diff = BackupDiff {
+            previous_backup: previous.map(|p| p.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        
+        for (path, entry) in &current.files {
+            match previous_index.files.get(path) {
+                None => diff.added.push(path.clone()),
+                Some(old) if old != entry => diff.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        
+        diff.deleted = previous_index.files
+            .keys()
+            .filter(|path| !current.files.contains_key(*path))
+            .cloned()
+            .collect();
+        
+        diff
+    }
+    
+    /// Make sure the destination volume still has `min_free_space` bytes free after this run. Counts
+    /// what the run would copy: for incremental and archive-bit runs only the changed files. Passes
+    /// when the free space can't be read, which some network shares don't report. The totals of
+    /// that walk are kept for the progress of the `run_backup` that follows.
+    pub fn check_free_space(&mut self, source_paths: &[String], destination_base: &Path) -> Result<(), DestinationProblem> {
+        if self.min_free_space == 0 {
+            return Ok(());
+        }
+        
+        let available = match free_space(destination_base) {
+            Some(available) => available,
+            None => {
+                log::warn!("Could not read the free space at {}, not checking it", destination_base.display());
+                return Ok(());
+            }
+        };
+        
+        let previous = if self.mode == BackupMode::Incremental {
+            find_latest_backup(destination_base).as_deref().and_then(BackupIndex::load).unwrap_or_default()
+        } else {
+            BackupIndex::default()
+        };
+        let sources = dedup_sources(source_paths);
+        let survey = self.survey(&sources, Some(&previous));
+        self.surveyed = Some((sources, survey));
+        let needed = survey.needed;
+        log::info!("Backup needs about {} bytes, {} bytes free at {}", needed, available, destination_base.display());
+        
+        if available < needed.saturating_add(self.min_free_space) {
+            return Err(DestinationProblem::NotEnoughSpace { needed, available });
+        }
+        Ok(())
+    }
+    
+    /// Number and total size of the files under `source_paths` that pass the hidden/system, exclude
+    /// and size filters; an upper bound, since a run may leave unchanged files out. Walks the sources once.
+    pub fn estimate_size(&self, source_paths: &[String]) -> (usize, u64) {
+        let survey = self.survey(source_paths, None);
+        (survey.files, survey.bytes)
+    }
+    
+    /// Count the files `walk_filtered` finds under `source_paths`. With `previous` the `needed`
+    /// bytes also leave out what this run's mode won't copy, files unchanged since `previous`
+    /// (incremental) or without the archive attribute.
+    fn survey(&self, source_paths: &[String], previous: Option<&BackupIndex>) -> Survey {
+        let mut survey = Survey::default();
+        
+        for (source_path, folder_name) in destination_names(source_paths) {
+            for (entry, info) in self.walk_filtered(&source_path) {
+                survey.files += 1;
+                survey.bytes += info.size;
+                
+                let unchanged = match (self.mode, previous) {
+                    (BackupMode::ArchiveBit, Some(_)) => !has_archive_bit(entry.path()),
+                    (BackupMode::Incremental, Some(previous)) => entry.path()
+                        .strip_prefix(&source_path)
+                        .is_ok_and(|relative| previous.files.get(&index_key(&Path::new(&folder_name).join(relative))) == Some(&info)),
+                    _ => false,
+                };
+                if !unchanged {
+                    survey.needed += info.size;
+                }
+            }
+        }
+        
+        survey
+    }
+    
+    /// The files under `source` this engine backs up, with their size and time
+    fn walk_filtered<'a>(&'a self, source: &'a Path) -> impl Iterator<Item = (walkdir::DirEntry, IndexEntry)> + 'a {
+        self.source_filter().files(source)
+    }
+    
+    fn source_filter(&self) -> SourceFilter<'_> {
+        SourceFilter {
+            skip_hidden: self.skip_hidden,
+            skip_system: self.skip_system,
+            excluded_paths: &self.excluded_paths,
+            exclude: &self.exclude,
+            max_file_size: self.max_file_size,
+        }
+    }
+    
+    /// Delete the backups under `destination_base`, folders and `.tar.gz` archives, that `policy`
+    /// no longer keeps. The newest backup and the one this engine just wrote always stay; entries
+    /// not named like a backup and interrupted backups are left alone. An incremental or archive-bit
+    /// backup needs the ones before it back to the last full backup, so those stay while it does.
+    /// Returns the deleted paths.
+    pub fn prune_old_backups(&self, destination_base: &Path, policy: RetentionPolicy) -> Result<Vec<PathBuf>, String> {
+        if !destination_base.is_dir() {
+            return Err(format!("{} is not a folder", destination_base.display()));
+        }
+        
+        // Oldest first, by the timestamp in the name
+        let backups = stored_backups(destination_base);
+        let keep_newest = backups.len().saturating_sub(1);
+        let mut expired = match policy {
+            RetentionPolicy::KeepLast(count) => backups.len().saturating_sub(count.max(1)),
+            RetentionPolicy::KeepDays(days) => {
+                let cutoff = ((self.clock)() - chrono::Duration::days(days as i64)).naive_utc();
+                backups.iter().take_while(|((time, _), _)| *time < cutoff).count().min(keep_newest)
+            }
+        };
+        
+        // Only whole chains go: walk back from the oldest kept backup to the full one it builds on.
+        // A backup of unknown mode might build on any older one, so it keeps them all.
+        while expired > 0 && !is_full_backup(&backups[expired].1) {
+            expired -= 1;
+        }
+        
+        let mut deleted = Vec::new();
+        for (_, path) in &backups[..expired] {
+            if *path == self.backup_root {
+                continue;
+            }
             
-            if !source_path.exists() {
-                log::warn!("Source path does not exist: {}", source);
+            let archive = zip_archive_path(path);
+            let result = if path.is_dir() { fs::remove_dir_all(path) } else { fs::remove_file(path) };
+            match result.and_then(|_| if archive.exists() { fs::remove_file(&archive) } else { Ok(()) }) {
+                Ok(()) => {
+                    log::info!("Deleted old backup {}", path.display());
+                    deleted.push(path.clone());
+                }
+                Err(e) => log::warn!("Failed to delete old backup {}: {}", path.display(), e),
+            }
+        }
+        
+        Ok(deleted)
+    }
+    
+    /// Copy a backup's contents (without DriveGuard's own log and index files) back into
+    /// `destination`, keeping the folder layout. Files an incremental or archive-bit backup
+    /// left unchanged come from the older backup folders next to it. Existing files are only
+    /// replaced when `overwrite` is set, otherwise they count as skipped. Progress and failures
+    /// are tracked like a backup run.
+    pub fn restore(&mut self, backup_folder: &Path, destination: &Path, overwrite: bool) -> Result<(), String> {
+        if !backup_folder.is_dir() {
+            return Err(format!("{} is not a backup folder (compressed backups must be extracted first)",
+                               backup_folder.display()));
+        }
+        let archive = zip_archive_path(backup_folder);
+        if archive.exists() {
+            return Err(format!("{} is a zip backup, extract its files from {} instead", backup_folder.display(),
+                               archive.display()));
+        }
+        
+        self.is_running = true;
+        self.total_files = 0;
+        self.copied_files = 0;
+        self.copied_paths.clear();
+        self.skipped_files = 0;
+        self.failed_files.clear();
+        self.deferred_files.clear();
+        self.superseded_files.clear();
+        self.missing_sources.clear();
+        self.processed_bytes = 0;
+        self.started_at = Utc::now();
+        
+        let files = backup_files(backup_folder);
+        self.expected_files = files.len();
+        self.expected_bytes = files.iter()
+            .filter_map(|(_, stored)| fs::metadata(long_path(stored.as_deref()?)).ok())
+            .map(|m| m.len())
+            .sum();
+        self.throughput = Throughput::start(Instant::now());
+        
+        let result = self.restore_files(backup_folder, files, destination, overwrite);
+        self.is_running = false;
+        result
+    }
+    
+    fn restore_files(
+        &mut self,
+        backup_folder: &Path,
+        files: Vec<(PathBuf, Option<PathBuf>)>,
+        destination: &Path,
+        overwrite: bool,
+    ) -> Result<(), String> {
+        fs::create_dir_all(long_path(destination))
+            .map_err(|e| format!("Failed to create directory {}: {}", destination.display(), e))?;
+        
+        // Empty folders are part of the backup too
+        for entry in WalkDir::new(backup_folder).min_depth(1).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_dir() {
                 continue;
             }
+            let relative = entry.path().strip_prefix(backup_folder)
+                .map_err(|e| format!("Failed to strip prefix: {}", e))?;
+            let dest_path = destination.join(relative);
+            if let Err(e) = fs::create_dir_all(long_path(&dest_path)) {
+                log::warn!("Failed to create directory {}: {}", dest_path.display(), e);
+            }
+        }
+        
+        for (relative, stored) in files {
+            let dest_path = destination.join(&relative);
+            self.total_files += 1;
             
-            // Extract the folder name
-            let folder_name = if let Some(name) = source_path.file_name() {
-                name.to_string_lossy().to_string()
-            } else {
-                // Handle drive roots like C:\
-                source_path.to_string_lossy()
-                    .trim_end_matches(":\\")
-                    .to_string()
-            };
+            if long_path(&dest_path).exists() && !overwrite {
+                self.skipped_files += 1;
+                self.report_progress();
+                continue;
+            }
             
-            // Check for duplicate folder names
-            let final_folder_name = if let Some(count) = folder_counter.get(&folder_name) {
-                let new_count = count + 1;
-                folder_counter.insert(folder_name.clone(), new_count);
-                format!("{}_{}", folder_name, new_count)
-            } else {
-                folder_counter.insert(folder_name.clone(), 0);
-                folder_name
+            let stored = match stored {
+                Some(stored) => stored,
+                None => {
+                    log::warn!("Failed to restore {}: not found in any backup folder", dest_path.display());
+                    self.failed_files.push((dest_path.to_string_lossy().to_string(),
+                                            "Not in this backup or the backup folders before it (compressed backups \
+                                             must be extracted first)".to_string()));
+                    self.report_progress();
+                    continue;
+                }
             };
             
-            let dest_folder = format!("{}\\{}", backup_folder, final_folder_name);
+            if let Some(parent) = dest_path.parent() {
+                fs::create_dir_all(long_path(parent)).ok();
+            }
             
-            // Copy the directory tree
-            self.copy_directory(source_path, Path::new(&dest_folder))?;
+            match copy_file(&long_path(&stored), &long_path(&dest_path), self.copy_buffer_size) {
+                Ok(size) => {
+                    self.copied_files += 1;
+                    self.copied_paths.push(dest_path.to_string_lossy().to_string());
+                    self.file_done(size);
+                }
+                Err(e) => {
+                    log::warn!("Failed to restore {}: {}", dest_path.display(), e);
+                    self.failed_files.push((dest_path.to_string_lossy().to_string(), e.to_string()));
+                    self.report_progress();
+                }
+            }
         }
         
-        self.is_running = false;
-        Ok(backup_folder)
+        Ok(())
+    }
+    
+    fn copy_directory(&mut self, source: &Path, destination: &Path) -> Result<(), SourceError> {
+        // A zip is one stream, and deduplication has to see identical files one after another
+        if self.workers <= 1 || self.archive.is_some() || self.dedup {
+            return self.walk_source(source, destination, None);
+        }
+        
+        let (workers, buffer_size, verify) = (self.workers, self.copy_buffer_size, self.verify);
+        thread::scope(|scope| {
+            let mut pool = CopyPool::start(scope, workers, buffer_size, verify);
+            let result = self.walk_source(source, destination, Some(&mut pool));
+            
+            // Copies still running finish even when the walk was aborted, so they're accounted for
+            for done in pool.finish() {
+                self.copy_done(done);
+            }
+            result
+        })
     }
     
-    fn copy_directory(&mut self, source: &Path, destination: &Path) -> Result<(), String> {
+    /// Walk `source` and back it up into `destination`. With a `pool` the walk only decides what to
+    /// copy and creates the folders, so parents always exist before the workers write into them.
+    fn walk_source(&mut self, source: &Path, destination: &Path, mut pool: Option<&mut CopyPool>) -> Result<(), SourceError> {
         // Create destination directory
-        fs::create_dir_all(destination)
-            .map_err(|e| format!("Failed to create directory {}: {}", destination.display(), e))?;
+        if self.archive.is_none() {
+            fs::create_dir_all(long_path(destination))
+                .map_err(|e| SourceError::Failed(format!("Failed to create directory {}: {}", destination.display(), e)))?;
+        }
+        
+        // Walk through source directory, pruning excluded folders entirely; files too large for
+        // the destination are kept here, to be reported as failed
+        let (excluded_paths, exclude) = (self.excluded_paths.clone(), self.exclude.clone());
+        let filter = SourceFilter {
+            skip_hidden: self.skip_hidden,
+            skip_system: self.skip_system,
+            excluded_paths: &excluded_paths,
+            exclude: &exclude,
+            max_file_size: None,
+        };
+        let walker = WalkDir::new(source)
+            .max_depth(self.max_depth.map_or(usize::MAX, |depth| depth + 1))
+            .into_iter()
+            .filter_entry(|e| filter.keeps(source, e));
         
-        // Walk through source directory
-        for entry in WalkDir::new(source).into_iter().filter_map(|e| e.ok()) {
+        for entry in walker.filter_map(|e| e.ok()) {
             let path = entry.path();
             
             if path == source {
                 continue;
             }
             
+            if self.is_cancelled() {
+                return Err(SourceError::Cancelled);
+            }
+            
+            // A runaway source (a whole system drive) fails fast instead of filling the destination
+            if let Some(max_depth) = self.max_depth {
+                if entry.depth() > max_depth {
+                    return Err(SourceError::Abort(format!(
+                        "Source {} is too deep (more than {} levels) - refine your sources or raise max_depth",
+                        source.display(), max_depth)));
+                }
+            }
+            
             // Calculate relative path
             let relative = path.strip_prefix(source)
-                .map_err(|e| format!("Failed to strip prefix: {}", e))?;
+                .map_err(|e| SourceError::Failed(format!("Failed to strip prefix: {}", e)))?;
             
             let dest_path = destination.join(relative);
             
             if entry.file_type().is_dir() {
-                // Create directory
-                if let Err(e) = fs::create_dir_all(&dest_path) {
+                // Create directory (in a zip, so empty ones are kept too)
+                if let Some(archive) = self.archive.as_mut() {
+                    let name = archive_entry_name(&self.backup_root, &dest_path);
+                    if let Err(e) = archive.add_directory(name, FileOptions::default()) {
+                        log::warn!("Failed to add folder {} to the zip: {}", dest_path.display(), e);
+                    }
+                } else if let Err(e) = fs::create_dir_all(long_path(&dest_path)) {
                     log::warn!("Failed to create directory {}: {}", dest_path.display(), e);
                 }
             } else {
+                // Don't start a file while the machine is going to sleep
+                crate::power::wait_while_suspended();
+                
+                if let Some(max_files) = self.max_files {
+                    if self.total_files >= max_files {
+                        return Err(SourceError::Abort(format!(
+                            "Sources are too large (more than {} files) - refine your sources or raise max_files",
+                            max_files)));
+                    }
+                }
+                
                 // Copy file
                 self.total_files += 1;
+                self.update_state_file(path);
+                let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+                
+                // Of a folder full of dated exports, only the latest is wanted
+                if self.is_superseded(path) {
+                    log::info!("Skipping superseded file: {}", path.display());
+                    self.superseded_files.push(path.to_string_lossy().to_string());
+                    self.file_done(size);
+                    continue;
+                }
+                
+                // In archive-bit and incremental mode, untouched files were already backed up
+                let unchanged = match self.mode {
+                    BackupMode::Full => false,
+                    BackupMode::ArchiveBit => !has_archive_bit(path),
+                    BackupMode::Incremental => self.unchanged_since_previous(path, &dest_path),
+                };
+                
+                if unchanged {
+                    self.skipped_files += 1;
+                    self.record_in_index(path, &dest_path);
+                    self.file_done(size);
+                    continue;
+                }
+                
+                // A file that changed moments ago may still be mid-write; take it next time
+                if self.is_too_recent(path) {
+                    log::info!("Deferring recently modified file: {}", path.display());
+                    self.deferred_files.push(path.to_string_lossy().to_string());
+                    self.file_done(size);
+                    continue;
+                }
+                
+                // Don't start a copy the destination file system can't hold
+                if let Some(error) = self.size_limit_error(path) {
+                    log::warn!("Skipping {}: {}", path.display(), error);
+                    self.failed_files.push((path.to_string_lossy().to_string(), error));
+                    self.oversized_files += 1;
+                    self.file_done(0); // Left out of the counted totals as well
+                    continue;
+                }
                 
                 // Ensure parent directory exists
-                if let Some(parent) = dest_path.parent() {
-                    fs::create_dir_all(parent).ok();
+                if let Some(parent) = dest_path.parent().filter(|_| self.archive.is_none()) {
+                    fs::create_dir_all(long_path(parent)).ok();
                 }
                 
-                match fs::copy(path, &dest_path) {
-                    Ok(_) => {
-                        self.copied_files += 1;
-                    }
-                    Err(e) => {
-                        let error_msg = format!("{}", e);
-                        self.failed_files.push((
-                            path.to_string_lossy().to_string(),
-                            error_msg,
-                        ));
-                        log::warn!("Failed to copy {}: {}", path.display(), e);
+                let job = CopyJob { source: path.to_path_buf(), destination: dest_path, size };
+                if let Some(pool) = pool.as_deref_mut() {
+                    pool.submit(job);
+                    for done in pool.finished() {
+                        self.copy_done(done);
                     }
+                    continue;
                 }
+                
+                let result = if let Some(archive) = self.archive.as_mut() {
+                    add_to_archive(archive, path, archive_entry_name(&self.backup_root, &job.destination), self.copy_buffer_size)
+                } else if self.dedup {
+                    self.copy_deduplicated(path, &job.destination)
+                } else {
+                    copy_file(path, &job.destination, self.copy_buffer_size).map(|_| ())
+                };
+                let verified = verify_copy(&job, &result, self.verify && self.archive.is_none());
+                self.copy_done(CopyDone { job, result, verified });
             }
         }
         
         Ok(())
     }
     
-    pub fn get_progress(&self) -> (usize, usize) {
-        (self.copied_files, self.total_files)
+    /// Account for a finished copy, wherever it ran
+    fn copy_done(&mut self, done: CopyDone) {
+        let CopyDone { job, result, verified } = done;
+        let path = job.source.as_path();
+        
+        // A copy that doesn't match isn't a backup; it stays out of the index so the next run retries it
+        let result = match (result, verified) {
+            (Ok(()), Some(Ok(true))) => {
+                self.verified_files += 1;
+                Ok(())
+            }
+            (Ok(()), Some(Ok(false))) => {
+                self.verify_failures += 1;
+                Err("Verification failed: the copy's SHA-256 differs from the source".to_string())
+            }
+            (Ok(()), Some(Err(e))) => {
+                self.verify_failures += 1;
+                Err(format!("Verification failed: could not read back the copy: {}", e))
+            }
+            (Ok(()), None) => Ok(()),
+            (Err(e), _) => Err(e.to_string()),
+        };
+        
+        match result {
+            Ok(()) => {
+                self.copied_files += 1;
+                self.copied_paths.push(path.to_string_lossy().to_string());
+                self.copied_bytes += job.size;
+                self.record_in_index(path, &job.destination);
+                
+                if self.mode == BackupMode::ArchiveBit {
+                    if let Err(e) = clear_archive_bit(path) {
+                        log::warn!("Failed to clear archive bit on {}: {}", path.display(), e);
+                    }
+                }
+            }
+            Err(e) => {
+                log::warn!("Failed to copy {}: {}", path.display(), e);
+                self.failed_files.push((
+                    path.to_string_lossy().to_string(),
+                    e,
+                ));
+            }
+        }
+        
+        self.file_done(job.size);
     }
     
-    pub fn save_logs(&self, backup_folder: &str) -> std::io::Result<()> {
-        // Save backup log
-        let mut log_content = String::from("DriveGuard Backup Log\n");
-        log_content.push_str(&format!("Timestamp: {}\n", Utc::now().to_rfc3339()));
-        log_content.push_str(&format!("Total files: {}\n", self.total_files));
-        log_content.push_str(&format!("Successfully copied: {}\n", self.copied_files));
-        log_content.push_str(&format!("Failed: {}\n\n", self.failed_files.len()));
+    /// Whether `path` matches a `newest_only` pattern and isn't the newest match in its folder.
+    /// Each folder is listed once per pattern, when the walk reaches its first match.
+    fn is_superseded(&mut self, path: &Path) -> bool {
+        let (folder, name) = match (path.parent(), path.file_name()) {
+            (Some(folder), Some(name)) => (folder, name.to_string_lossy()),
+            _ => return false,
+        };
+        let group = match self.newest_only.iter().position(|pattern| wildcard_matches(pattern, &name)) {
+            Some(group) => group,
+            None => return false,
+        };
         
-        for (path, _) in &self.failed_files {
-            log_content.push_str(&format!("{} - OK\n", path));
-        }
+        let (skip_hidden, skip_system) = (self.skip_hidden, self.skip_system);
+        let pattern = &self.newest_only[group];
+        let newest = self.newest_in_group
+            .entry((folder.to_path_buf(), group))
+            .or_insert_with(|| newest_match(folder, pattern, skip_hidden, skip_system));
         
-        let log_path = format!("{}\\backup.txt", backup_folder);
-        fs::write(&log_path, log_content)?;
+        // Unreadable folder: better a file too many than a lost one
+        newest.as_deref().is_some_and(|newest| newest != path)
+    }
+    
+    /// Copy a file, or hard-link it to an identical file already copied during this run.
+    /// Falls back to a normal copy when linking isn't possible (other volume, FAT, etc.)
+    fn copy_deduplicated(&mut self, source: &Path, destination: &Path) -> std::io::Result<()> {
+        let hash = checksum::sha256_file(source)?;
         
-        // Save error log if there are failures
-        if !self.failed_files.is_empty() {
-            let mut error_content = String::from("DriveGuard Backup Errors\n\n");
-            
-            for (path, error) in &self.failed_files {
-                error_content.push_str(&format!("{} - Failed! ({})\n", path, error));
+        if let Some(existing) = self.dedup_index.get(&hash) {
+            match fs::hard_link(long_path(existing), long_path(destination)) {
+                Ok(()) => {
+                    self.linked_files += 1;
+                    self.bytes_saved += fs::metadata(source).map(|m| m.len()).unwrap_or(0);
+                    return Ok(());
+                }
+                Err(e) => {
+                    log::debug!("Hard link to {} failed ({}), copying instead", existing.display(), e);
+                }
             }
-            
-            let error_path = format!("{}\\backup_errors.txt", backup_folder);
-            fs::write(&error_path, error_content)?;
         }
         
+        copy_file(source, destination, self.copy_buffer_size)?;
+        self.dedup_index.insert(hash, destination.to_path_buf());
         Ok(())
     }
+    
+    fn is_too_recent(&self, path: &Path) -> bool {
+        if self.min_file_age.is_zero() {
+            return false;
+        }
+        
+        fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age < self.min_file_age)
+    }
+    
+    fn size_limit_error(&self, path: &Path) -> Option<String> {
+        let limit = self.max_file_size?;
+        let size = fs::metadata(path).ok()?.len();
+        
+        if size > limit {
+            Some(format!("Too large for FAT32 ({:.2} GB, FAT32 can only store files up to 4 GB)",
+                         size as f64 / 1_073_741_824.0))
+        } else {
+            None
+        }
+    }
+    
+    fn unchanged_since_previous(&self, source: &Path, destination: &Path) -> bool {
+        match (destination.strip_prefix(&self.backup_root), index_entry(source)) {
+            (Ok(relative), Some(entry)) => self.previous_index.files.get(&index_key(relative)) == Some(&entry),
+            _ => false,
+        }
+    }
+    
+    fn record_in_index(&mut self, source: &Path, destination: &Path) {
+        if let (Ok(relative), Some(entry)) = (destination.strip_prefix(&self.backup_root), index_entry(source)) {
+            self.index.files.insert(index_key(relative), entry);
+        }
+    }
+    
+    /// Periodically record where the backup is, so a crash or hang can be diagnosed
+    fn update_state_file(&mut self, current_path: &Path) {
+        self.files_since_flush += 1;
+        if self.files_since_flush < STATE_FLUSH_FILES && self.last_state_flush.elapsed() < STATE_FLUSH_INTERVAL {
+            return;
+        }
+        
+        self.files_since_flush = 0;
+        self.last_state_flush = Instant::now();
+        
+        let state = BackupState {
+            app_version: crate::version::VERSION,
+            started_at: self.started_at.to_rfc3339(),
+            updated_at: Utc::now().to_rfc3339(),
+            current_path: current_path.to_string_lossy().to_string(),
+            total_files: self.total_files,
+            copied_files: self.copied_files,
+            skipped_files: self.skipped_files,
+            failed_files: self.failed_files.len(),
+        };
+        
+        if let Ok(content) = serde_json::to_string_pretty(&state) {
+            if let Err(e) = fs::write(self.backup_root.join(STATE_FILE), content) {
+                log::debug!("Failed to write backup state file: {}", e);
+            }
+        }
+    }
+    
+    /// Whether the cancel token was set since the run started
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+    
+    /// Count a processed (copied, skipped or failed) file of `size` bytes and report progress
+    fn file_done(&mut self, size: u64) {
+        self.processed_bytes += size;
+        self.report_progress();
+    }
+    
+    fn report_progress(&mut self) {
+        self.throughput.update(Instant::now(), self.processed_bytes);
+        let eta = if self.expected_bytes > 0 {
+            self.throughput.eta(self.expected_bytes.saturating_sub(self.processed_bytes))
+        } else {
+            None
+        };
+        
+        let progress = BackupProgress {
+            processed_files: self.copied_files + self.skipped_files + self.failed_files.len() + self.deferred_files.len()
+                + self.superseded_files.len(),
+            total_files: self.total_files,
+            processed_bytes: self.processed_bytes,
+            expected_files: self.expected_files,
+            expected_bytes: self.expected_bytes,
+            eta,
+        };
+        
+        if let Some(callback) = self.on_progress.as_mut() {
+            callback(&progress);
+        }
+    }
+    
+    pub fn summary(&self, backup_folder: &str) -> BackupSummary {
+        BackupSummary {
+            folder: backup_folder.to_string(),
+            total_files: self.total_files,
+            copied_files: self.copied_files,
+            copied_bytes: self.copied_bytes,
+            skipped_files: self.skipped_files,
+            failed_files: self.failed_files.clone(),
+            oversized_files: self.oversized_files,
+            missing_sources: self.missing_sources.clone(),
+            failed_sources: self.failed_sources.clone(),
+            error_log: if self.failed_files.is_empty() {
+                None
+            } else {
+                Some(self.log_path(backup_folder, ERROR_LOG_FILE).to_string_lossy().to_string())
+            },
+        }
+    }
+    
+    pub fn get_progress(&self) -> (usize, usize) {
+        (self.copied_files, self.total_files)
+    }
+    
+    pub fn save_logs(&self, backup_folder: &str) -> std::io::Result<()> {
+        // Save backup log
+        let mut log_content = String::from("DriveGuard Backup Log\n");
+        log_content.push_str(&format!("Timestamp: {}\n", Utc::now().to_rfc3339()));
+        log_content.push_str(&format!("Total files: {}\n", self.total_files));
+        log_content.push_str(&format!("Successfully copied: {}\n", self.copied_files));
+        log_content.push_str(&format!("Skipped (unchanged): {}\n", self.skipped_files));
+        if self.dedup {
+            log_content.push_str(&format!("Hard-linked duplicates: {} ({:.2} MB saved)\n",
+                                          self.linked_files, self.bytes_saved as f64 / 1_048_576.0));
+        }
+        log_content.push_str(&format!("Failed: {}\n", self.failed_files.len()));
+        if self.verify {
+            log_content.push_str(&format!("Verified (SHA-256 matches): {}\n", self.verified_files));
+            log_content.push_str(&format!("Verification failed (included in failed): {}\n", self.verify_failures));
+        }
+        if self.oversized_files > 0 {
+            log_content.push_str(&format!("Too large for FAT32 (included in failed): {}\n", self.oversized_files));
+        }
+        if !self.min_file_age.is_zero() {
+            log_content.push_str(&format!("Deferred (modified in the last {}s): {}\n",
+                                          self.min_file_age.as_secs(), self.deferred_files.len()));
+        }
+        if !self.newest_only.is_empty() {
+            log_content.push_str(&format!("Superseded by a newer file: {}\n", self.superseded_files.len()));
+        }
+        log_content.push_str(&format!("Missing sources skipped: {}\n", self.missing_sources.len()));
+        log_content.push_str(&format!("Failed sources: {}\n\n", self.failed_sources.len()));
+        
+        push_log_lines(&mut log_content, &self.missing_sources, |source| format!("{} - Source not found", source));
+        push_log_lines(&mut log_content, &self.failed_sources, |(source, e)| format!("{} - Source failed ({})", source, e));
+        push_log_lines(&mut log_content, &self.deferred_files, |path| format!("{} - Deferred (recently modified)", path));
+        push_log_lines(&mut log_content, &self.superseded_files, |path| format!("{} - Superseded (a newer file matches)", path));
+        push_log_lines(&mut log_content, &self.failed_files, |(path, error)| format!("{} - Failed ({})", path, error));
+        push_log_lines(&mut log_content, &self.copied_paths, |path| format!("{} - OK", path));
+        
+        self.write_log(&self.log_path(backup_folder, LOG_FILE), &log_content)?;
+        
+        self.index.save(Path::new(backup_folder))?;
+        
+        // Save error log if there are failures
+        if !self.failed_files.is_empty() {
+            let mut error_content = String::from("DriveGuard Backup Errors\n\n");
+            push_log_lines(&mut error_content, &self.failed_files, |(path, error)| format!("{} - Failed! ({})", path, error));
+            
+            self.write_log(&self.log_path(backup_folder, ERROR_LOG_FILE), &error_content)?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Where a log file goes, `.gz` included when logs are compressed
+    fn log_path(&self, backup_folder: &str, file_name: &str) -> PathBuf {
+        if self.compress_logs {
+            Path::new(backup_folder).join(format!("{}.gz", file_name))
+        } else {
+            Path::new(backup_folder).join(file_name)
+        }
+    }
+    
+    fn write_log(&self, path: &Path, content: &str) -> std::io::Result<()> {
+        if !self.compress_logs {
+            return fs::write(path, content);
+        }
+        
+        let mut encoder = GzEncoder::new(fs::File::create(path)?, Compression::default());
+        encoder.write_all(content.as_bytes())?;
+        encoder.finish()?;
+        Ok(())
+    }
+}
+
+/// A file the walk decided to copy
+struct CopyJob {
+    source: PathBuf,
+    destination: PathBuf,
+    size: u64,
+}
+
+struct CopyDone {
+    job: CopyJob,
+    result: std::io::Result<()>,
+    verified: Option<std::io::Result<bool>>, // Whether the copy read back matches the source, if checked
+}
+
+/// Threads copying the files a walk hands them. Results go back to the walking thread, which does
+/// all the counting, so the engine's state is never shared between threads.
+struct CopyPool {
+    jobs: Option<mpsc::SyncSender<CopyJob>>,
+    done: mpsc::Receiver<CopyDone>,
+}
+
+impl CopyPool {
+    fn start<'scope>(scope: &'scope thread::Scope<'scope, '_>, workers: usize, buffer_size: usize, verify: bool) -> Self {
+        // Bounded, so a fast walk can't queue up a whole drive's worth of paths
+        let (jobs, queue) = mpsc::sync_channel::<CopyJob>(workers * 4);
+        let queue = Arc::new(Mutex::new(queue));
+        let (done_sender, done) = mpsc::channel();
+        
+        for _ in 0..workers {
+            let queue = Arc::clone(&queue);
+            let done_sender = done_sender.clone();
+            scope.spawn(move || loop {
+                // The lock is only held while waiting for the next job, not during the copy
+                let job = match queue.lock().map(|queue| queue.recv()) {
+                    Ok(Ok(job)) => job,
+                    _ => break,
+                };
+                let result = copy_file(&job.source, &job.destination, buffer_size).map(|_| ());
+                let verified = verify_copy(&job, &result, verify);
+                if done_sender.send(CopyDone { job, result, verified }).is_err() {
+                    break;
+                }
+            });
+        }
+        
+        Self { jobs: Some(jobs), done }
+    }
+    
+    fn submit(&mut self, job: CopyJob) {
+        if let Some(jobs) = &self.jobs {
+            // Only fails once every worker is gone, which they never are before finish()
+            jobs.send(job).ok();
+        }
+    }
+    
+    /// Copies that finished since the last call, without waiting
+    fn finished(&mut self) -> Vec<CopyDone> {
+        self.done.try_iter().collect()
+    }
+    
+    /// Let the workers run out of jobs and return every copy not collected yet
+    fn finish(mut self) -> Vec<CopyDone> {
+        self.jobs = None;
+        self.done.iter().collect()
+    }
+}
+
+/// After a successful copy, hash the source and the copy (both streamed) and tell whether they match
+fn verify_copy(job: &CopyJob, result: &std::io::Result<()>, verify: bool) -> Option<std::io::Result<bool>> {
+    if !verify || result.is_err() {
+        return None;
+    }
+    Some(checksum::sha256_file(&job.source).and_then(|source| Ok(source == checksum::sha256_file(&job.destination)?)))
+}
+
+/// Why copying one source stopped
+enum SourceError {
+    Failed(String), // Only this source is lost; the backup goes on with the others
+    Abort(String), // The whole backup stops (walk limits)
+    Cancelled, // The user stopped the backup; what was copied so far stays counted
+}
+
+/// One line per item, at most MAX_LOG_LINES and then "... and N more", so a failure across a
+/// huge tree can't produce a giant log. Control characters (newlines in error messages) become spaces.
+fn push_log_lines<T>(content: &mut String, items: &[T], line: impl Fn(&T) -> String) {
+    for item in items.iter().take(MAX_LOG_LINES) {
+        content.extend(line(item).chars().map(|c| if c.is_control() { ' ' } else { c }));
+        content.push('\n');
+    }
+    if items.len() > MAX_LOG_LINES {
+        content.push_str(&format!("... and {} more\n", items.len() - MAX_LOG_LINES));
+    }
+}
+
+/// Copy a file through an explicitly sized buffer instead of `fs::copy`'s fixed one,
+/// then carry over permissions and the modification time like `fs::copy` does
+pub fn copy_file(source: &Path, destination: &Path, buffer_size: usize) -> std::io::Result<u64> {
+    let (source, destination) = (long_path(source), long_path(destination));
+    let (source, destination) = (source.as_path(), destination.as_path());
+    let metadata = fs::metadata(source)?;
+    let mut reader = fs::File::open(source)?;
+    let mut writer = fs::File::create(destination)?;
+    
+    let mut buffer = vec![0u8; buffer_size.max(4096)];
+    let mut copied = 0u64;
+    
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+    }
+    
+    writer.flush()?;
+    drop(writer);
+    
+    // Timestamp first: a read-only permission would get in the way of changing it
+    filetime::set_file_mtime(destination, FileTime::from_last_modification_time(&metadata))?;
+    fs::set_permissions(destination, metadata.permissions())?;
+    
+    Ok(copied)
+}
+
+/// Stream `source` into a zip as the entry `name`, reading `buffer_size` bytes at a time
+fn add_to_archive(archive: &mut ZipWriter<fs::File>, source: &Path, name: String, buffer_size: usize) -> std::io::Result<()> {
+    let metadata = fs::metadata(source)?;
+    // Opened before the entry starts, so a locked file doesn't leave an empty one behind
+    let reader = fs::File::open(source)?;
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(metadata.len() >= u32::MAX as u64)
+        .last_modified_time(zip_time(&metadata));
+    
+    archive.start_file(name, options)?;
+    // A read error past this point leaves the entry truncated; the file is still listed as failed
+    std::io::copy(&mut std::io::BufReader::with_capacity(buffer_size.max(4096), reader), archive)?;
+    Ok(())
+}
+
+/// Where `destination` would be in the backup folder `backup_root`, with the `/` separators zip entries use
+fn archive_entry_name(backup_root: &Path, destination: &Path) -> String {
+    let relative = destination.strip_prefix(backup_root).unwrap_or(destination);
+    relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+/// `source_paths` without repeats of the same folder, such as `C:\Data`, `C:\Data\` and `c:\data`,
+/// which would otherwise be copied twice into `Data` and `Data_1`. The first spelling is kept.
+pub fn dedup_sources(source_paths: &[String]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut unique = Vec::new();
+    
+    for source in source_paths {
+        let key = path_key(Path::new(source.trim()));
+        if seen.insert(key) {
+            unique.push(source.clone());
+        } else {
+            log::warn!("Source listed more than once, backing it up only once: {}", source);
+        }
+    }
+    
+    unique
+}
+
+/// Pair each existing source with the folder name it gets inside a backup
+fn destination_names(source_paths: &[String]) -> Vec<(PathBuf, OsString)> {
+    // Track folder names to avoid duplicates
+    let mut folder_counter: HashMap<OsString, u32> = HashMap::new();
+    let mut names = Vec::new();
+    
+    for source in source_paths {
+        let source_path = Path::new(source);
+        
+        if !source_path.exists() {
+            log::warn!("Source path does not exist: {}", source);
+            continue;
+        }
+        
+        let folder_name = source_folder_name(source_path);
+        
+        // Check for duplicate folder names
+        let final_folder_name = if let Some(count) = folder_counter.get(&folder_name) {
+            let new_count = count + 1;
+            folder_counter.insert(folder_name.clone(), new_count);
+            let mut numbered = folder_name.clone();
+            numbered.push(format!("_{}", new_count));
+            numbered
+        } else {
+            folder_counter.insert(folder_name.clone(), 0);
+            folder_name
+        };
+        
+        names.push((source_path.to_path_buf(), final_folder_name));
+    }
+    
+    names
+}
+
+/// The source's own folder name (Cyrillic, emoji and all), made safe for the destination.
+/// A name that isn't valid Unicode is kept as the OS gave it rather than turned into `�`.
+fn source_folder_name(source_path: &Path) -> OsString {
+    let name = match source_path.file_name() {
+        Some(name) => name.to_os_string(),
+        // Handle drive roots like C:\
+        None => OsString::from(source_path.to_string_lossy().trim_end_matches(":\\")),
+    };
+    
+    match name.to_str() {
+        Some(name) => OsString::from(sanitize_component(name)),
+        None => name,
+    }
+}
+
+/// Make a single generated path component legal on every filesystem DriveGuard writes to:
+/// replaces illegal/control characters, strips trailing dots and spaces,
+/// enforces the length limit and avoids reserved device names like CON or NUL
+pub fn sanitize_component(name: &str) -> String {
+    let mut component: String = name
+        .chars()
+        .map(|c| if c.is_control() || ILLEGAL_CHARS.contains(c) { '_' } else { c })
+        .collect();
+    
+    if component.chars().count() > MAX_COMPONENT_LEN {
+        component = component.chars().take(MAX_COMPONENT_LEN).collect();
+    }
+    
+    let trimmed_len = component.trim_end_matches(['.', ' ']).len();
+    component.truncate(trimmed_len);
+    
+    if component.is_empty() {
+        return "_".to_string();
+    }
+    
+    // "CON", "nul.txt" and "Com1.tar.gz" are all reserved
+    let stem = component.split('.').next().unwrap_or("").to_ascii_uppercase();
+    if RESERVED_NAMES.contains(&stem.as_str()) {
+        component.insert(0, '_');
+    }
+    
+    component
+}
+
+/// Files, and their total size, a walk of the sources found
+#[derive(Debug, Clone, Copy, Default)]
+struct Survey {
+    files: usize,
+    bytes: u64,
+    needed: u64, // What the run will actually copy (see BackupEngine::survey)
+}
+
+/// What a backup leaves out of its sources: hidden and system entries as configured, DriveGuard's
+/// own paths and `exclude` matches, each with everything inside; and files over `max_file_size`
+#[derive(Clone, Copy)]
+struct SourceFilter<'a> {
+    skip_hidden: bool,
+    skip_system: bool,
+    excluded_paths: &'a [PathBuf],
+    exclude: &'a ExcludePatterns,
+    max_file_size: Option<u64>,
+}
+
+impl<'a> SourceFilter<'a> {
+    /// Whether a walk of `source` keeps `entry` (and goes into it, for a folder)
+    fn keeps(&self, source: &Path, entry: &walkdir::DirEntry) -> bool {
+        entry.depth() == 0
+            || !(is_excluded(entry, self.skip_hidden, self.skip_system) || is_excluded_path(entry.path(), self.excluded_paths)
+                 || self.exclude.excludes(source, entry))
+    }
+    
+    /// Every entry under `source` the filter keeps, folders included
+    fn entries(self, source: &'a Path) -> impl Iterator<Item = walkdir::DirEntry> + 'a {
+        WalkDir::new(source)
+            .into_iter()
+            .filter_entry(move |e| self.keeps(source, e))
+            .filter_map(|e| e.ok())
+    }
+    
+    /// The files under `source` the filter keeps, with their size and time
+    fn files(self, source: &'a Path) -> impl Iterator<Item = (walkdir::DirEntry, IndexEntry)> + 'a {
+        self.entries(source)
+            .filter(|e| e.file_type().is_file())
+            .filter_map(|e| index_entry(e.path()).map(|info| (e, info)))
+            .filter(move |(_, info)| info.size <= self.max_file_size.unwrap_or(u64::MAX))
+    }
+}
+
+/// Build an index of the sources as they would be laid out in a backup
+fn scan_sources(
+    source_paths: &[String],
+    skip_hidden: bool,
+    skip_system: bool,
+    excluded_paths: &[PathBuf],
+    exclude: &ExcludePatterns,
+) -> BackupIndex {
+    let filter = SourceFilter { skip_hidden, skip_system, excluded_paths, exclude, max_file_size: None };
+    let mut index = BackupIndex::default();
+    
+    for (source_path, folder_name) in destination_names(source_paths) {
+        for (entry, info) in filter.files(&source_path) {
+            if let Ok(relative) = entry.path().strip_prefix(&source_path) {
+                let key = index_key(&Path::new(&folder_name).join(relative));
+                index.files.insert(key, info);
+            }
+        }
+    }
+    
+    index
+}
+
+/// Whether anything under `source_paths` was created or modified after `since`. Folders count
+/// too, since adding, renaming or deleting an entry updates their time. Stops at the first change.
+pub fn changed_since(
+    source_paths: &[String],
+    since: SystemTime,
+    skip_hidden: bool,
+    skip_system: bool,
+    excluded_paths: &[PathBuf],
+    exclude: &ExcludePatterns,
+) -> bool {
+    let after = |time: std::io::Result<SystemTime>| time.is_ok_and(|time| time > since);
+    let filter = SourceFilter { skip_hidden, skip_system, excluded_paths, exclude, max_file_size: None };
+    
+    source_paths.iter().any(|source| {
+        filter
+            .entries(Path::new(source))
+            .any(|entry| entry.metadata().is_ok_and(|m| after(m.modified()) || after(m.created())))
+    })
+}
+
+fn index_entry(path: &Path) -> Option<IndexEntry> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?
+        .duration_since(UNIX_EPOCH).ok()?
+        .as_secs() as i64;
+    
+    Some(IndexEntry { size: metadata.len(), modified })
+}
+
+/// Index keys always use forward slashes so they compare the same everywhere
+fn index_key(relative: &Path) -> String {
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+/// Why a backup destination can't be used as configured
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DestinationProblem {
+    DriveMissing(PathBuf), // The drive (or network share) the path is on isn't there
+    FolderMissing, // The drive is there but the folder doesn't exist, possibly a typo
+    NotEnoughSpace { needed: u64, available: u64 }, // The backup would eat into min_free_space
+}
+
+/// Make sure `destination_base` exists before a backup, instead of letting the run
+/// silently create a whole mistyped path
+pub fn check_destination(destination_base: &Path) -> Result<(), DestinationProblem> {
+    let root: PathBuf = destination_base
+        .components()
+        .take_while(|c| matches!(c, Component::Prefix(_) | Component::RootDir))
+        .collect();
+    
+    if !root.as_os_str().is_empty() && !root.exists() {
+        return Err(DestinationProblem::DriveMissing(root));
+    }
+    if !destination_base.is_dir() {
+        return Err(DestinationProblem::FolderMissing);
+    }
+    
+    Ok(())
+}
+
+/// Fill in the tokens of a `destination_path` template: `{schedule_name}`, `{year}`, `{month}` and
+/// `{date}` (YYYY-MM-DD, local time). Folders with a token are made legal like source names, so a
+/// schedule called "Work: Laptop" can't add a bad folder. The timestamped backup folder goes inside.
+pub fn expand_destination(template: &str, schedule_name: &str, now: DateTime<Utc>) -> Result<String, String> {
+    let now = now.with_timezone(&Local);
+    let mut expanded = String::new();
+    
+    for part in template.split_inclusive(['\\', '/']) {
+        let component = part.trim_end_matches(['\\', '/']);
+        if !component.contains(['{', '}']) {
+            expanded.push_str(part);
+            continue;
+        }
+        
+        let mut filled = String::new();
+        let mut rest = component;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| format!("destination_path \"{}\" has a {{ without a matching }}", template))?;
+            filled.push_str(&rest[..start]);
+            match &rest[start + 1..end] {
+                "schedule_name" => filled.push_str(schedule_name),
+                "year" => filled.push_str(&now.format("%Y").to_string()),
+                "month" => filled.push_str(&now.format("%m").to_string()),
+                "date" => filled.push_str(&now.format("%Y-%m-%d").to_string()),
+                token => return Err(format!("destination_path \"{}\" has an unknown token {{{}}} \
+                                             (use {{schedule_name}}, {{year}}, {{month}} or {{date}})", template, token)),
+            }
+            rest = &rest[end + 1..];
+        }
+        if rest.contains('}') {
+            return Err(format!("destination_path \"{}\" has a }} without a matching {{", template));
+        }
+        filled.push_str(rest);
+        
+        expanded.push_str(&sanitize_component(&filled));
+        expanded.push_str(&part[component.len()..]);
+    }
+    
+    Ok(expanded)
+}
+
+/// The part of a destination before its first template token; it has to exist already, the rest is
+/// created as backups need it
+pub fn template_root(destination: &Path) -> PathBuf {
+    destination
+        .components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(['{', '}']))
+        .collect()
+}
+
+/// Complete timestamped backup folders under `destination_base`, oldest first.
+/// Folder names are ISO 8601 so they sort chronologically as strings.
+pub fn list_backups(destination_base: &Path) -> Vec<PathBuf> {
+    let mut folders: Vec<PathBuf> = backup_folders(destination_base)
+        .into_iter()
+        .filter(|p| is_complete_backup(p))
+        .collect();
+    
+    folders.sort_by_key(|p| p.file_name().and_then(|n| n.to_str()).and_then(parse_backup_folder_name));
+    folders
+}
+
+/// Complete backups under `destination_base`, as folders or `.tar.gz` archives, oldest first
+/// with the time and sequence number from their names. Anything else in there is skipped.
+fn stored_backups(destination_base: &Path) -> Vec<((NaiveDateTime, u32), PathBuf)> {
+    let entries = match fs::read_dir(destination_base) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    
+    let mut backups: Vec<((NaiveDateTime, u32), PathBuf)> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?;
+            let (stem, complete) = match name.strip_suffix(".tar.gz") {
+                Some(stem) => (stem, path.is_file()),
+                None => (name, path.is_dir() && is_complete_backup(&path)),
+            };
+            match parse_backup_folder_name(stem) {
+                Some(key) if complete => Some((key, path)),
+                Some(_) => None,
+                None => {
+                    log::debug!("{} is not named like a backup, leaving it alone", path.display());
+                    None
+                }
+            }
+        })
+        .collect();
+    
+    backups.sort();
+    backups
+}
+
+/// Whether a stored backup holds all its files itself rather than building on older backups
+fn is_full_backup(path: &Path) -> bool {
+    stored_index(path).and_then(|index| index.mode) == Some(BackupMode::Full)
+}
+
+/// The index of a stored backup, a folder or a `.tar.gz` archive
+fn stored_index(path: &Path) -> Option<BackupIndex> {
+    if path.is_dir() {
+        return BackupIndex::load(path);
+    }
+    
+    // compress_backup_folder stores the index right after the top folder, so the rest isn't unpacked
+    let file = fs::File::open(path).ok()?;
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+    let mut entry = archive.entries().ok()?.nth(1)?.ok()?;
+    if entry.path().ok()?.file_name()? != INDEX_FILE {
+        return None;
+    }
+    
+    let mut content = String::new();
+    entry.read_to_string(&mut content).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// A backup finished if it wrote its index and log and cleared its in-progress state file.
+/// Anything else was interrupted and must not serve as an incremental baseline.
+pub fn is_complete_backup(folder: &Path) -> bool {
+    let has_log = folder.join(LOG_FILE).exists() || folder.join(format!("{}.gz", LOG_FILE)).exists();
+    
+    folder.join(INDEX_FILE).exists()
+        && has_log
+        && !folder.join(STATE_FILE).exists()
+        && !folder.join(INCOMPLETE_MARKER).exists()
+}
+
+/// Flag interrupted backups under `destination_base` with a marker file so they're skipped
+/// from now on. Returns only the folders marked by this call, so each is reported once.
+pub fn mark_incomplete_backups(destination_base: &Path) -> Vec<PathBuf> {
+    let mut marked = Vec::new();
+    
+    for folder in backup_folders(destination_base) {
+        if is_complete_backup(&folder) || folder.join(INCOMPLETE_MARKER).exists() || is_possibly_running(&folder) {
+            continue;
+        }
+        
+        let note = format!("Backup was interrupted (detected {}); it is excluded from incremental \
+                            backups and can be deleted.\n", Utc::now().to_rfc3339());
+        match fs::write(folder.join(INCOMPLETE_MARKER), note) {
+            Ok(()) => marked.push(folder),
+            Err(e) => log::warn!("Failed to mark incomplete backup {}: {}", folder.display(), e),
+        }
+    }
+    
+    marked.sort();
+    marked
+}
+
+fn is_possibly_running(folder: &Path) -> bool {
+    fs::metadata(folder.join(STATE_FILE))
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < ACTIVE_STATE_AGE)
+}
+
+/// Folders under `destination_base` named like the timestamped folders backups create
+fn backup_folders(destination_base: &Path) -> Vec<PathBuf> {
+    match fs::read_dir(destination_base) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .filter(|p| p.file_name()
+                .and_then(|n| n.to_str())
+                .and_then(parse_backup_folder_name)
+                .is_some())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Time and same-second sequence number of a backup folder name, `<timestamp>` or `<timestamp>_<n>`
+fn parse_backup_folder_name(name: &str) -> Option<(NaiveDateTime, u32)> {
+    let (timestamp, sequence) = match name.rsplit_once('_') {
+        Some((timestamp, sequence)) => (timestamp, sequence.parse().ok()?),
+        None => (name, 1),
+    };
+    NaiveDateTime::parse_from_str(timestamp, BACKUP_FOLDER_FORMAT).ok().map(|t| (t, sequence))
+}
+
+/// Create a new, empty folder for a backup started at `timestamp`. Another run in the same second
+/// gets `<timestamp>_2`, `_3`, ... rather than mixing its files into the first run's folder.
+fn create_backup_folder(destination_base: &Path, timestamp: &str) -> Result<PathBuf, String> {
+    fs::create_dir_all(destination_base)
+        .map_err(|e| format!("Failed to create backup folder: {}", e))?;
+    
+    let mut sequence = 1;
+    loop {
+        let name = if sequence == 1 { timestamp.to_string() } else { format!("{}_{}", timestamp, sequence) };
+        let folder = destination_base.join(sanitize_component(&name));
+        
+        // create_dir (unlike create_dir_all) fails on an existing folder, so two runs can't both claim it
+        match fs::create_dir(&folder) {
+            Ok(()) => return Ok(folder),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => sequence += 1,
+            Err(e) => return Err(format!("Failed to create backup folder: {}", e)),
+        }
+    }
+}
+
+/// The archive holding the files of a zip backup, next to its folder
+pub fn zip_archive_path(backup_folder: &Path) -> PathBuf {
+    let name = backup_folder.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    backup_folder.with_file_name(format!("{}.zip", name))
+}
+
+/// A file's modification time as a zip timestamp (local time, 1980 at the earliest)
+fn zip_time(metadata: &fs::Metadata) -> zip::DateTime {
+    let modified: DateTime<Local> = match metadata.modified() {
+        Ok(modified) => modified.into(),
+        Err(_) => return zip::DateTime::default(),
+    };
+    
+    zip::DateTime::from_date_and_time(
+        modified.year().clamp(1980, 2107) as u16,
+        modified.month() as u8,
+        modified.day() as u8,
+        modified.hour() as u8,
+        modified.minute() as u8,
+        modified.second() as u8,
+    )
+    .unwrap_or_default()
+}
+
+/// Most recent backup folder under `destination_base`
+pub fn find_latest_backup(destination_base: &Path) -> Option<PathBuf> {
+    list_backups(destination_base).pop()
+}
+
+/// Files in `backup_folder` that a restore to `destination` would overwrite
+pub fn restore_conflicts(backup_folder: &Path, destination: &Path) -> Vec<PathBuf> {
+    backup_files(backup_folder)
+        .into_iter()
+        .map(|(relative, _)| destination.join(relative))
+        .filter(|path| long_path(path).exists())
+        .collect()
+}
+
+/// The files of a backup as (path relative to `backup_folder`, stored copy). An incremental or
+/// archive-bit backup only holds the files that changed; the rest of its index is looked up in
+/// the older backup folders next to it, newest first, by size and modification time. Files
+/// found in none of them have no stored copy.
+fn backup_files(backup_folder: &Path) -> Vec<(PathBuf, Option<PathBuf>)> {
+    let mut files: Vec<(PathBuf, Option<PathBuf>)> = WalkDir::new(backup_folder)
+        .min_depth(1)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let relative = e.path().strip_prefix(backup_folder).ok()?.to_path_buf();
+            (!is_backup_metadata(&relative)).then(|| (relative, Some(e.path().to_path_buf())))
+        })
+        .collect();
+    
+    let index = BackupIndex::load(backup_folder).unwrap_or_default();
+    let stored: HashSet<String> = files.iter().map(|(relative, _)| index_key(relative)).collect();
+    let unchanged: Vec<(&String, &IndexEntry)> = index.files.iter().filter(|(key, _)| !stored.contains(*key)).collect();
+    if unchanged.is_empty() {
+        return files;
+    }
+    
+    let name_key = |folder: &Path| folder.file_name().and_then(|n| n.to_str()).and_then(parse_backup_folder_name);
+    let this = name_key(backup_folder);
+    let older: Vec<PathBuf> = backup_folder.parent()
+        .map(list_backups)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|folder| this.is_some() && name_key(folder) < this)
+        .rev()
+        .collect();
+    
+    for (key, entry) in unchanged {
+        let relative: PathBuf = key.split('/').collect();
+        let stored = older.iter()
+            .map(|folder| folder.join(&relative))
+            .find(|copy| index_entry(&long_path(copy)).as_ref() == Some(entry));
+        files.push((relative, stored));
+    }
+    
+    files
+}
+
+/// DriveGuard's own files at the top of a backup folder, which aren't backed-up data
+fn is_backup_metadata(relative: &Path) -> bool {
+    if relative.components().count() != 1 {
+        return false;
+    }
+    
+    let name = relative.to_string_lossy();
+    let name = name.strip_suffix(".gz").unwrap_or(&name);
+    [INDEX_FILE, DIFF_FILE, STATE_FILE, LOG_FILE, ERROR_LOG_FILE, INCOMPLETE_MARKER].contains(&name)
+}
+
+/// Pack every backup folder under `destination_base` except the newest `keep_uncompressed`
+/// into `<folder>.tar.gz` and remove the folder. Returns how many were compressed.
+pub fn compress_old_backups(destination_base: &Path, keep_uncompressed: usize) -> Result<usize, String> {
+    if !destination_base.is_dir() {
+        return Err(format!("{} is not a folder", destination_base.display()));
+    }
+    
+    // Oldest first; the newest always stays a plain folder for incremental runs and quick restores.
+    // Interrupted backups aren't listed, so they're left alone for cleanup.
+    let folders = list_backups(destination_base);
+    let keep = keep_uncompressed.max(1);
+    let mut compressed = 0;
+    
+    // A zip backup's folder only holds its logs; the files are compressed already
+    for folder in folders[..folders.len().saturating_sub(keep)].iter().filter(|f| !zip_archive_path(f).exists()) {
+        compress_backup_folder(folder)?;
+        log::info!("Compressed old backup {}", folder.display());
+        compressed += 1;
+    }
+    
+    Ok(compressed)
+}
+
+fn compress_backup_folder(folder: &Path) -> Result<(), String> {
+    let name = folder.file_name()
+        .ok_or_else(|| format!("Invalid backup folder: {}", folder.display()))?
+        .to_string_lossy()
+        .to_string();
+    let archive_path = folder.with_file_name(format!("{}.tar.gz", name));
+    let partial_path = folder.with_file_name(format!("{}.tar.gz.partial", name));
+    
+    // Write to a temporary name so an interrupted run never leaves a truncated archive
+    // next to a deleted folder
+    let write_archive = || -> std::io::Result<()> {
+        let encoder = GzEncoder::new(fs::File::create(&partial_path)?, Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        
+        // The index goes first so pruning can read it without unpacking the whole archive
+        let index = folder.join(INDEX_FILE);
+        builder.append_dir(&name, folder)?;
+        if index.is_file() {
+            builder.append_path_with_name(&index, Path::new(&name).join(INDEX_FILE))?;
+        }
+        for entry in WalkDir::new(folder).min_depth(1).sort_by_file_name() {
+            let entry = entry?;
+            if entry.path() == index {
+                continue;
+            }
+            let archived = match entry.path().strip_prefix(folder) {
+                Ok(relative) => Path::new(&name).join(relative),
+                Err(_) => continue,
+            };
+            if entry.file_type().is_dir() {
+                builder.append_dir(&archived, entry.path())?;
+            } else {
+                builder.append_path_with_name(entry.path(), &archived)?;
+            }
+        }
+        builder.into_inner()?.finish()?.sync_all()
+    };
+    
+    if let Err(e) = write_archive() {
+        fs::remove_file(&partial_path).ok();
+        return Err(format!("Failed to compress {}: {}", folder.display(), e));
+    }
+    
+    fs::rename(&partial_path, &archive_path)
+        .map_err(|e| format!("Failed to finish {}: {}", archive_path.display(), e))?;
+    fs::remove_dir_all(folder)
+        .map_err(|e| format!("Compressed {} but failed to remove the folder: {}", folder.display(), e))
+}
+
+/// Check the archive attribute; files we can't inspect are treated as changed
+#[cfg(windows)]
+fn has_archive_bit(path: &Path) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    use windows::Win32::Storage::FileSystem::FILE_ATTRIBUTE_ARCHIVE;
+    
+    fs::metadata(path)
+        .map(|m| m.file_attributes() & FILE_ATTRIBUTE_ARCHIVE.0 != 0)
+        .unwrap_or(true)
+}
+
+#[cfg(not(windows))]
+fn has_archive_bit(_path: &Path) -> bool {
+    true
+}
+
+/// Case-insensitive match where `*` stands for any run of characters, for drive labels
+/// ("BACKUP*") and file names ("report_*.csv")
+pub fn wildcard_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+    
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+    
+    // First part anchors the start, last part the end, the rest must appear in order between
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || text.len() < first.len() + last.len() || !text.ends_with(last) {
+        return false;
+    }
+    
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    
+    true
+}
+
+/// The most recently modified file in `folder` whose name matches `pattern`, leaving out the ones
+/// the hidden/system options skip; on equal times the name sorting last wins
+fn newest_match(folder: &Path, pattern: &str, skip_hidden: bool, skip_system: bool) -> Option<PathBuf> {
+    WalkDir::new(folder)
+        .min_depth(1)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && !is_excluded(e, skip_hidden, skip_system))
+        .filter(|e| wildcard_matches(pattern, &e.file_name().to_string_lossy()))
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.into_path())))
+        .max()
+        .map(|(_, path)| path)
+}
+
+/// `path` spelled the way Windows compares paths: backslashes, no trailing separator, lowercase
+fn path_key(path: &Path) -> String {
+    path.to_string_lossy().replace('/', "\\").trim_end_matches('\\').to_lowercase()
+}
+
+/// `path` is one of `excluded_paths`; compared the way Windows does, ignoring case and slash direction
+fn is_excluded_path(path: &Path, excluded_paths: &[PathBuf]) -> bool {
+    let path = path_key(path);
+    let excluded = excluded_paths.iter().any(|excluded| path_key(excluded) == path);
+    if excluded {
+        log::debug!("Excluding DriveGuard's own file or folder: {}", path);
+    }
+    excluded
+}
+
+/// Whether a walked entry is left out by the hidden/system options
+#[cfg(windows)]
+fn is_excluded(entry: &walkdir::DirEntry, skip_hidden: bool, skip_system: bool) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    use windows::Win32::Storage::FileSystem::{FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_SYSTEM};
+    
+    if !skip_hidden && !skip_system {
+        return false;
+    }
+    
+    let attributes = match entry.metadata() {
+        Ok(m) => m.file_attributes(),
+        Err(_) => return false,
+    };
+    
+    let excluded = (skip_hidden && attributes & FILE_ATTRIBUTE_HIDDEN.0 != 0)
+        || (skip_system && attributes & FILE_ATTRIBUTE_SYSTEM.0 != 0);
+    if excluded {
+        log::debug!("Excluding hidden/system entry: {}", entry.path().display());
+    }
+    excluded
+}
+
+/// Dot files count as hidden; there is no system attribute
+#[cfg(not(windows))]
+fn is_excluded(entry: &walkdir::DirEntry, skip_hidden: bool, _skip_system: bool) -> bool {
+    skip_hidden && entry.file_name().to_string_lossy().starts_with('.')
+}
+
+/// Bytes available to this user on the volume holding `path`, or its nearest existing parent
+#[cfg(windows)]
+fn free_space(path: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows::core::PCWSTR;
+    use windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+    
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let mut path_wide: Vec<u16> = existing.as_os_str().encode_wide().collect();
+    path_wide.push(0);
+    
+    let mut available: u64 = 0;
+    unsafe { GetDiskFreeSpaceExW(PCWSTR(path_wide.as_ptr()), Some(&mut available as *mut u64), None, None) }.ok()?;
+    Some(available)
+}
+
+#[cfg(not(windows))]
+fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// `path` with the `\\?\` extended-length prefix when it's too long for the classic Windows API.
+/// That prefix switches off Windows' own path parsing, so the path is made absolute and `.`/`..`
+/// and `/` are resolved here first. Shorter paths are left as they are, so logs stay readable.
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> PathBuf {
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Prefix;
+    
+    if path.as_os_str().encode_wide().count() < MAX_PATH {
+        return path.to_path_buf();
+    }
+    
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        match std::env::current_dir() {
+            Ok(dir) => dir.join(path),
+            Err(_) => return path.to_path_buf(),
+        }
+    };
+    
+    let mut prefixed = OsString::new();
+    let mut parts: Vec<&std::ffi::OsStr> = Vec::new();
+    for component in absolute.components() {
+        match component {
+            Component::Prefix(prefix) => match prefix.kind() {
+                Prefix::Disk(letter) => prefixed.push(format!("\\\\?\\{}:", letter as char)),
+                Prefix::UNC(server, share) => {
+                    prefixed.push("\\\\?\\UNC\\");
+                    prefixed.push(server);
+                    prefixed.push("\\");
+                    prefixed.push(share);
+                }
+                // Already extended-length, or a device path
+                _ => return path.to_path_buf(),
+            },
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(part) => parts.push(part),
+        }
+    }
+    
+    for part in parts {
+        prefixed.push("\\");
+        prefixed.push(part);
+    }
+    PathBuf::from(prefixed)
+}
+
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+/// Clear the archive attribute, keeping read-only/hidden/system bits intact
+#[cfg(windows)]
+fn clear_archive_bit(path: &Path) -> std::io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::fs::MetadataExt;
+    use windows::core::PCWSTR;
+    use windows::Win32::Storage::FileSystem::{
+        SetFileAttributesW, FILE_ATTRIBUTE_ARCHIVE, FILE_FLAGS_AND_ATTRIBUTES,
+    };
+    
+    let attributes = fs::metadata(path)?.file_attributes();
+    if attributes & FILE_ATTRIBUTE_ARCHIVE.0 == 0 {
+        return Ok(());
+    }
+    
+    let mut path_wide: Vec<u16> = long_path(path).as_os_str().encode_wide().collect();
+    path_wide.push(0);
+    
+    unsafe {
+        SetFileAttributesW(
+            PCWSTR(path_wide.as_ptr()),
+            FILE_FLAGS_AND_ATTRIBUTES(attributes & !FILE_ATTRIBUTE_ARCHIVE.0),
+        )
+    }
+    .map_err(std::io::Error::from)
+}
+
+#[cfg(not(windows))]
+fn clear_archive_bit(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Scratch folder under the temp dir, removed again when the test ends
+    struct TempDir(PathBuf);
+    
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            TempDir(std::env::temp_dir().join(format!("driveguard_{}_test_{}", name, std::process::id())))
+        }
+    }
+    
+    impl std::ops::Deref for TempDir {
+        type Target = Path;
+        
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+    
+    impl AsRef<Path> for TempDir {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+    
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            fs::remove_dir_all(long_path(&self.0)).ok();
+        }
+    }
+    
+    #[test]
+    fn test_sanitize_component() {
+        // Already-safe names are untouched
+        assert_eq!(sanitize_component("Documents"), "Documents");
+        assert_eq!(sanitize_component("2025-11-19T12-00-00"), "2025-11-19T12-00-00");
+        
+        // Illegal characters are replaced
+        assert_eq!(sanitize_component("a<b>c:d\"e/f\\g|h?i*j"), "a_b_c_d_e_f_g_h_i_j");
+        assert_eq!(sanitize_component("tab\there"), "tab_here");
+        
+        // Trailing dots and spaces are stripped
+        assert_eq!(sanitize_component("folder."), "folder");
+        assert_eq!(sanitize_component("folder. . "), "folder");
+        assert_eq!(sanitize_component("..."), "_");
+        assert_eq!(sanitize_component(""), "_");
+        
+        // Reserved device names
+        assert_eq!(sanitize_component("CON"), "_CON");
+        assert_eq!(sanitize_component("nul"), "_nul");
+        assert_eq!(sanitize_component("NUL.txt"), "_NUL.txt");
+        assert_eq!(sanitize_component("com1."), "_com1");
+        assert_eq!(sanitize_component("CONSOLE"), "CONSOLE");
+        
+        // Length limit
+        let long = "x".repeat(300);
+        assert_eq!(sanitize_component(&long).chars().count(), MAX_COMPONENT_LEN);
+    }
+    
+    #[test]
+    fn test_copy_file_matches_source() {
+        let dir = TempDir::new("copy");
+        fs::create_dir_all(&dir).unwrap();
+        
+        let source = dir.join("source.bin");
+        let destination = dir.join("destination.bin");
+        
+        // Larger than the buffer and not a multiple of it
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&source, &data).unwrap();
+        let mtime = FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(&source, mtime).unwrap();
+        
+        let copied = copy_file(&source, &destination, 4096).unwrap();
+        
+        assert_eq!(copied, data.len() as u64);
+        assert_eq!(fs::read(&destination).unwrap(), data);
+        assert_eq!(FileTime::from_last_modification_time(&fs::metadata(&destination).unwrap()), mtime);
+        assert_eq!(index_entry(&source), index_entry(&destination));
+    }
+    
+    #[test]
+    fn test_compress_old_backups_keeps_newest() {
+        let dir = TempDir::new("compress");
+        let names = ["2025-01-01T00-00-00", "2025-02-01T00-00-00", "2025-03-01T00-00-00"];
+        for name in names {
+            fs::create_dir_all(dir.join(name).join("Documents")).unwrap();
+            fs::write(dir.join(name).join(INDEX_FILE), "{}").unwrap();
+            fs::write(dir.join(name).join(LOG_FILE), "").unwrap();
+            fs::write(dir.join(name).join("Documents").join("a.txt"), name).unwrap();
+        }
+        
+        assert_eq!(compress_old_backups(&dir, 1).unwrap(), 2);
+        
+        assert!(dir.join(names[2]).is_dir());
+        for name in &names[..2] {
+            assert!(!dir.join(name).exists());
+            
+            let archive = fs::File::open(dir.join(format!("{}.tar.gz", name))).unwrap();
+            let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+            let mut entry = archive.entries().unwrap()
+                .map(|e| e.unwrap())
+                .find(|e| e.path().unwrap().ends_with("Documents/a.txt"))
+                .unwrap();
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            assert_eq!(content, *name);
+        }
+        
+        // The newest backup is never compressed, even with keep = 0
+        assert_eq!(compress_old_backups(&dir, 0).unwrap(), 0);
+        assert_eq!(find_latest_backup(&dir), Some(dir.join(names[2])));
+    }
+    
+    #[test]
+    fn test_prune_old_backups() {
+        let dir = TempDir::new("prune");
+        let names = ["2025-01-01T00-00-00", "2025-05-01T00-00-00", "2025-05-20T00-00-00", "2025-06-01T12-00-00"];
+        for name in names {
+            fs::create_dir_all(dir.join(name)).unwrap();
+            fs::write(dir.join(name).join(INDEX_FILE), r#"{"files": {}, "mode": "full"}"#).unwrap();
+            fs::write(dir.join(name).join(LOG_FILE), "").unwrap();
+        }
+        fs::create_dir_all(dir.join("Old stuff")).unwrap();
+        fs::create_dir_all(dir.join("2024-12-01T00-00-00")).unwrap(); // Interrupted
+        compress_old_backups(&dir, 3).unwrap(); // The oldest becomes an archive
+        
+        let mut engine = BackupEngine::new();
+        engine.clock = || DateTime::parse_from_rfc3339("2025-06-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        engine.backup_root = dir.join(names[3]);
+        
+        let deleted = engine.prune_old_backups(&dir, RetentionPolicy::KeepDays(30)).unwrap();
+        assert_eq!(deleted, vec![dir.join(format!("{}.tar.gz", names[0])), dir.join(names[1])]);
+        assert!(dir.join("Old stuff").is_dir());
+        assert!(dir.join("2024-12-01T00-00-00").is_dir());
+        
+        // The backup just made survives even when the policy would drop everything
+        assert!(engine.prune_old_backups(&dir, RetentionPolicy::KeepDays(0)).unwrap() == vec![dir.join(names[2])]);
+        assert!(engine.prune_old_backups(&dir, RetentionPolicy::KeepLast(0)).unwrap().is_empty());
+        assert_eq!(list_backups(&dir), vec![dir.join(names[3])]);
+    }
+    
+    #[test]
+    fn test_prune_keeps_incremental_chains() {
+        let dir = TempDir::new("prune_chain");
+        let source = dir.join("Documents");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "alpha").unwrap();
+        fs::write(source.join("b.txt"), "beta").unwrap();
+        let sources = vec![source.to_string_lossy().to_string()];
+        let destination = dir.join("backups");
+        
+        let mut engine = BackupEngine::new();
+        let backup = |engine: &mut BackupEngine, mode: BackupMode| {
+            engine.mode = mode;
+            let folder = engine.run_backup(&sources, &destination.to_string_lossy()).unwrap();
+            engine.save_logs(&folder).unwrap();
+            PathBuf::from(folder)
+        };
+        
+        let first_full = backup(&mut engine, BackupMode::Full);
+        fs::write(source.join("b.txt"), "beta, edited").unwrap();
+        backup(&mut engine, BackupMode::Incremental);
+        fs::write(source.join("c.txt"), "gamma").unwrap();
+        backup(&mut engine, BackupMode::Incremental);
+        
+        // The newest backup builds on both before it
+        assert!(engine.prune_old_backups(&destination, RetentionPolicy::KeepLast(1)).unwrap().is_empty());
+        
+        let second_full = backup(&mut engine, BackupMode::Full);
+        fs::write(source.join("a.txt"), "alpha, edited").unwrap();
+        let latest = backup(&mut engine, BackupMode::Incremental);
+        assert_eq!(engine.copied_files, 1);
+        
+        // The first chain goes as a whole, the second stays although only its newest backup is wanted
+        let deleted = engine.prune_old_backups(&destination, RetentionPolicy::KeepLast(2)).unwrap();
+        assert_eq!(deleted.len(), 3);
+        assert_eq!(deleted[0], first_full);
+        assert!(engine.prune_old_backups(&destination, RetentionPolicy::KeepLast(1)).unwrap().is_empty());
+        assert_eq!(list_backups(&destination), vec![second_full.clone(), latest.clone()]);
+        
+        // Every file of the newest backup can still be restored
+        let target = dir.join("restored");
+        engine.restore(&latest, &target, false).unwrap();
+        assert!(engine.failed_files.is_empty(), "{:?}", engine.failed_files);
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            assert_eq!(fs::read(target.join("Documents").join(name)).unwrap(), fs::read(source.join(name)).unwrap());
+        }
+        
+        // A compressed full backup is still recognised as one
+        compress_old_backups(&destination, 1).unwrap();
+        let mut archive = second_full.into_os_string();
+        archive.push(".tar.gz");
+        assert!(is_full_backup(Path::new(&archive)));
+        assert!(!is_full_backup(&latest));
+    }
+    
+    #[test]
+    fn test_estimate_size() {
+        let dir = TempDir::new("estimate");
+        let source = dir.join("Documents");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "12345").unwrap();
+        fs::write(source.join("b.txt"), "1234567890").unwrap();
+        let sources = vec![source.to_string_lossy().to_string()];
+        let destination = dir.join("backups");
+        
+        let mut engine = BackupEngine::new();
+        engine.mode = BackupMode::Incremental;
+        assert_eq!(engine.estimate_size(&sources), (2, 15));
+        
+        let backup_folder = engine.run_backup(&sources, &destination.to_string_lossy()).unwrap();
+        engine.save_logs(&backup_folder).unwrap();
+        fs::write(source.join("a.txt"), "123456").unwrap();
+        
+        // Only the changed file counts for an incremental run; the estimate stays the upper bound
+        let previous = find_latest_backup(&destination).as_deref().and_then(BackupIndex::load).unwrap();
+        assert_eq!(engine.survey(&sources, Some(&previous)).needed, 6);
+        assert_eq!(engine.estimate_size(&sources), (2, 16));
+        
+        engine.max_file_size = Some(8);
+        assert_eq!(engine.estimate_size(&sources), (1, 6));
+    }
+    
+    #[test]
+    fn test_exclude_patterns() {
+        let dir = TempDir::new("exclude");
+        let source = dir.join("Documents");
+        for folder in ["app/node_modules/left-pad", "node_modules", "notes"] {
+            fs::create_dir_all(source.join(folder)).unwrap();
+        }
+        for file in ["app/main.js", "app/node_modules/left-pad/index.js", "node_modules/x.js", "notes/draft.TMP",
+                     "notes/plan.txt", "notes/Thumbs.db", "notes.tmp.txt"] {
+            fs::write(source.join(file), file).unwrap();
+        }
+        
+        let mut engine = BackupEngine::new();
+        engine.exclude = ExcludePatterns::new(&["*.tmp".to_string(), "**/node_modules/**".to_string(),
+                                                "thumbs.db".to_string()]).unwrap();
+        let sources = vec![source.to_string_lossy().to_string()];
+        let backup_folder = PathBuf::from(engine.run_backup(&sources, &dir.join("backups").to_string_lossy()).unwrap());
+        let copy = backup_folder.join("Documents");
+        
+        // Excluded by extension and by name, at any depth; not even counted
+        assert!(copy.join("notes").join("plan.txt").exists());
+        assert!(copy.join("notes.tmp.txt").exists());
+        assert!(!copy.join("notes").join("draft.TMP").exists());
+        assert!(!copy.join("notes").join("Thumbs.db").exists());
+        assert_eq!(engine.total_files, 3);
+        
+        // Excluded folders are skipped whole, so not even an empty copy of them is made
+        assert!(copy.join("app").join("main.js").exists());
+        assert!(!copy.join("app").join("node_modules").exists());
+        assert!(!copy.join("node_modules").exists());
+        let kept = ["app/main.js", "notes/plan.txt", "notes.tmp.txt"];
+        assert_eq!(engine.estimate_size(&sources), (3, kept.iter().map(|f| f.len() as u64).sum::<u64>()));
+        
+        assert!(ExcludePatterns::new(&["{unclosed".to_string()]).is_err());
+    }
+    
+    #[test]
+    fn test_zip_backup() {
+        let dir = TempDir::new("zip");
+        let source = dir.join("Documents");
+        fs::create_dir_all(source.join("Letters")).unwrap();
+        fs::create_dir_all(source.join("Empty")).unwrap();
+        fs::write(source.join("Letters").join("a.txt"), "first").unwrap();
+        fs::write(source.join("b.txt"), "second").unwrap();
+        let sources = vec![source.to_string_lossy().to_string()];
+        let destination = dir.join("backups").to_string_lossy().to_string();
+        
+        let mut engine = BackupEngine::new();
+        engine.output_format = OutputFormat::Zip;
+        engine.mode = BackupMode::Incremental;
+        let backup_folder = engine.run_backup(&sources, &destination).unwrap();
+        engine.save_logs(&backup_folder).unwrap();
+        
+        // The folder keeps the logs and index, so the backup counts as complete; the files are in the zip
+        let backup_folder = PathBuf::from(backup_folder);
+        assert_eq!(list_backups(&dir.join("backups")), vec![backup_folder.clone()]);
+        assert!(!backup_folder.join("Documents").exists());
+        assert_eq!(engine.copied_files, 2);
+        
+        let read_archive = |folder: &Path| {
+            let mut archive = zip::ZipArchive::new(fs::File::open(zip_archive_path(folder)).unwrap()).unwrap();
+            let mut entries = BTreeMap::new();
+            for i in 0..archive.len() {
+                let mut entry = archive.by_index(i).unwrap();
+                let mut content = String::new();
+                entry.read_to_string(&mut content).unwrap();
+                entries.insert(entry.name().to_string(), content);
+            }
+            entries
+        };
+        let entries = read_archive(&backup_folder);
+        assert_eq!(entries.get("Documents/Letters/a.txt").map(String::as_str), Some("first"));
+        assert_eq!(entries.get("Documents/b.txt").map(String::as_str), Some("second"));
+        assert!(entries.contains_key("Documents/Empty/"));
+        
+        // Incremental zips hold only what changed since the previous backup
+        fs::write(source.join("b.txt"), "second, edited").unwrap();
+        engine.clock = || Utc::now() + chrono::Duration::seconds(5);
+        let second = PathBuf::from(engine.run_backup(&sources, &destination).unwrap());
+        let entries = read_archive(&second);
+        assert_eq!(entries.get("Documents/b.txt").map(String::as_str), Some("second, edited"));
+        assert!(!entries.contains_key("Documents/Letters/a.txt"));
+        
+        assert!(engine.restore(&second, &dir.join("restored"), false).is_err());
+    }
+    
+    #[test]
+    fn test_parallel_copy() {
+        let dir = TempDir::new("parallel");
+        let source = dir.join("Photos");
+        for i in 0..1000 {
+            let folder = source.join(format!("{:02}", i % 40)).join(format!("{}", i % 3));
+            fs::create_dir_all(&folder).unwrap();
+            fs::write(folder.join(format!("{}.jpg", i)), i.to_string().repeat(i % 50 + 1)).unwrap();
+        }
+        
+        let mut engine = BackupEngine::new();
+        engine.workers = 4;
+        let sources = vec![source.to_string_lossy().to_string()];
+        let backup_folder = PathBuf::from(engine.run_backup(&sources, &dir.join("backups").to_string_lossy()).unwrap());
+        
+        assert_eq!(engine.total_files, 1000);
+        assert_eq!(engine.copied_files, 1000);
+        assert!(engine.failed_files.is_empty());
+        assert_eq!(engine.index.files.len(), 1000);
+        for i in 0..1000 {
+            let copy = backup_folder.join("Photos").join(format!("{:02}", i % 40)).join(format!("{}", i % 3)).join(format!("{}.jpg", i));
+            assert_eq!(fs::read_to_string(copy).unwrap(), i.to_string().repeat(i % 50 + 1));
+        }
+    }
+    
+    #[test]
+    fn test_verify_after_copy() {
+        let dir = TempDir::new("verify");
+        let source = dir.join("Documents");
+        fs::create_dir_all(&source).unwrap();
+        for i in 0..20 {
+            fs::write(source.join(format!("{}.txt", i)), i.to_string()).unwrap();
+        }
+        
+        let sources = vec![source.to_string_lossy().to_string()];
+        for workers in [1, 4] {
+            let mut engine = BackupEngine::new();
+            engine.verify = true;
+            engine.workers = workers;
+            let backup_folder = engine.run_backup(&sources, &dir.join("backups").to_string_lossy()).unwrap();
+            assert_eq!(engine.verified_files, 20);
+            
+            engine.save_logs(&backup_folder).unwrap();
+            let log = fs::read_to_string(Path::new(&backup_folder).join(LOG_FILE)).unwrap();
+            assert!(log.contains("Verified (SHA-256 matches): 20\n"));
+        }
+        
+        // A copy that differs from its source is a failure with its own reason
+        let copy = dir.join("copy.txt");
+        fs::write(&copy, "not the same").unwrap();
+        let job = CopyJob { source: source.join("1.txt"), destination: copy, size: 1 };
+        let verified = verify_copy(&job, &Ok(()), true);
+        assert!(matches!(verified, Some(Ok(false))));
+        
+        let mut engine = BackupEngine::new();
+        engine.copy_done(CopyDone { job, result: Ok(()), verified });
+        assert_eq!((engine.copied_files, engine.verify_failures), (0, 1));
+        assert!(engine.failed_files[0].1.contains("SHA-256 differs"));
+        assert!(engine.index.files.is_empty());
+    }
+    
+    #[test]
+    #[cfg(windows)]
+    fn test_long_paths() {
+        let dir = TempDir::new("long_path");
+        let mut folder = dir.join("Documents");
+        while folder.as_os_str().len() < 300 {
+            folder = folder.join("A folder with a fairly long name");
+        }
+        fs::create_dir_all(long_path(&folder)).unwrap();
+        fs::write(long_path(&folder.join("report.txt")), "deep").unwrap();
+        
+        let prefixed = long_path(&folder.join("..").join("report.txt"));
+        assert!(prefixed.to_string_lossy().starts_with("\\\\?\\"));
+        assert!(!prefixed.to_string_lossy().contains(".."));
+        assert_eq!(long_path(Path::new("C:\\Data")), Path::new("C:\\Data"));
+        
+        let sources = vec![dir.join("Documents").to_string_lossy().to_string()];
+        let mut engine = BackupEngine::new();
+        engine.run_backup(&sources, &dir.join("backups").to_string_lossy()).unwrap();
+        assert_eq!(engine.copied_files, 1);
+        assert!(engine.failed_files.is_empty(), "{:?}", engine.failed_files);
+    }
+    
+    #[test]
+    fn test_cancel_mid_copy() {
+        let dir = TempDir::new("cancel");
+        let source = dir.join("Documents");
+        fs::create_dir_all(&source).unwrap();
+        for i in 0..50 {
+            fs::write(source.join(format!("{}.txt", i)), i.to_string()).unwrap();
+        }
+        
+        let sources = vec![source.to_string_lossy().to_string()];
+        for workers in [1, 4] {
+            // Cancel as the tenth file is done, like the Cancel button would from the UI thread
+            let cancel = Arc::new(AtomicBool::new(false));
+            let token = Arc::clone(&cancel);
+            let mut engine = BackupEngine::new();
+            engine.workers = workers;
+            engine.cancel = Some(Arc::clone(&cancel));
+            engine.on_progress = Some(Box::new(move |progress: &BackupProgress| {
+                if progress.processed_files >= 10 {
+                    token.store(true, Ordering::Relaxed);
+                }
+            }));
+            
+            let error = engine.run_backup(&sources, &dir.join(format!("backups{}", workers)).to_string_lossy()).unwrap_err();
+            assert!(error.contains("cancelled"));
+            assert!(!engine.is_running);
+            assert!(engine.copied_files >= 10 && engine.copied_files < 50, "{} copied", engine.copied_files);
+            assert!(engine.total_files < 50);
+            assert_eq!(engine.copied_paths.len(), engine.copied_files);
+            assert_eq!(engine.index.files.len(), engine.copied_files);
+            assert!(engine.failed_files.is_empty());
+        }
+    }
+    
+    #[test]
+    fn test_non_ascii_source_names_are_kept() {
+        let dir = TempDir::new("unicode");
+        let source = dir.join("Фото");
+        fs::create_dir_all(source.join("Літо 🌻")).unwrap();
+        fs::write(source.join("Літо 🌻").join("море.txt"), "хвилі").unwrap();
+        
+        let mut engine = BackupEngine::new();
+        let backup_folder = engine.run_backup(&[source.to_string_lossy().to_string()], &dir.join("backups").to_string_lossy())
+            .unwrap();
+        
+        let copied = Path::new(&backup_folder).join("Фото").join("Літо 🌻").join("море.txt");
+        assert_eq!(fs::read_to_string(copied).unwrap(), "хвилі");
+        assert_eq!(engine.index.files.keys().collect::<Vec<_>>(), vec!["Фото/Літо 🌻/море.txt"]);
+    }
+    
+    #[test]
+    fn test_excluded_paths_are_skipped() {
+        let dir = TempDir::new("own_files");
+        let source = dir.join("Apps");
+        fs::create_dir_all(source.join("DriveGuard").join("updates")).unwrap();
+        fs::write(source.join("DriveGuard").join("updates").join("driveguard_v0.2.0.exe"), "new").unwrap();
+        fs::write(source.join("DriveGuard").join("settings.toml"), "[general]").unwrap();
+        fs::write(source.join("DriveGuard").join("notes.txt"), "keep").unwrap();
+        
+        let mut engine = BackupEngine::new();
+        engine.excluded_paths = vec![source.join("DriveGuard").join("updates"), source.join("DriveGuard").join("SETTINGS.TOML")];
+        let backup_folder = engine.run_backup(&[source.to_string_lossy().to_string()], &dir.join("backups").to_string_lossy())
+            .unwrap();
+        
+        let copied = Path::new(&backup_folder).join("Apps").join("DriveGuard");
+        assert!(copied.join("notes.txt").exists());
+        assert!(!copied.join("settings.toml").exists());
+        assert!(!copied.join("updates").exists());
+        assert_eq!(engine.index.files.len(), 1);
+    }
+    
+    #[test]
+    fn test_failed_source_does_not_stop_the_others() {
+        let dir = TempDir::new("failed_source");
+        let sources: Vec<String> = ["A", "B", "C"]
+            .iter()
+            .map(|name| {
+                fs::create_dir_all(dir.join(name)).unwrap();
+                fs::write(dir.join(name).join("file.txt"), name).unwrap();
+                dir.join(name).to_string_lossy().to_string()
+            })
+            .collect();
+        let base = dir.join("backups");
+        
+        // While A is copied, a file takes the place B's folder needs
+        let mut engine = BackupEngine::new();
+        let planted = base.clone();
+        engine.on_progress = Some(Box::new(move |_: &BackupProgress| {
+            if let Some(Ok(folder)) = fs::read_dir(&planted).unwrap().next() {
+                fs::write(folder.path().join("B"), "in the way").ok();
+            }
+        }));
+        let backup_folder = PathBuf::from(engine.run_backup(&sources, &base.to_string_lossy()).unwrap());
+        
+        assert!(backup_folder.join("A").join("file.txt").exists());
+        assert!(backup_folder.join("C").join("file.txt").exists());
+        assert_eq!(engine.failed_sources.len(), 1);
+        assert_eq!(engine.failed_sources[0].0, sources[1]);
+        assert_eq!(engine.summary(&backup_folder.to_string_lossy()).failed_sources.len(), 1);
+    }
+    
+    #[test]
+    fn test_log_tells_copied_from_failed_files() {
+        let dir = TempDir::new("log_status");
+        let source = dir.join("Data");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "alpha").unwrap();
+        fs::write(source.join("b.txt"), "beta").unwrap();
+        let base = dir.join("backups");
+        
+        // Once the first file is copied, a folder takes the place the other one needs
+        let mut engine = BackupEngine::new();
+        let planted = base.clone();
+        engine.on_progress = Some(Box::new(move |_: &BackupProgress| {
+            if let Some(Ok(folder)) = fs::read_dir(&planted).unwrap().next() {
+                for name in ["a.txt", "b.txt"] {
+                    let destination = folder.path().join("Data").join(name);
+                    if !destination.exists() {
+                        fs::create_dir_all(destination).ok();
+                    }
+                }
+            }
+        }));
+        let backup_folder = engine.run_backup(&[source.to_string_lossy().to_string()], &base.to_string_lossy()).unwrap();
+        engine.save_logs(&backup_folder).unwrap();
+        
+        assert_eq!((engine.copied_paths.len(), engine.failed_files.len()), (1, 1));
+        let log = fs::read_to_string(Path::new(&backup_folder).join(LOG_FILE)).unwrap();
+        let copied = &engine.copied_paths[0];
+        let failed = &engine.failed_files[0].0;
+        assert!(log.contains(&format!("{} - OK\n", copied)));
+        assert!(log.contains(&format!("{} - Failed (", failed)));
+        assert!(!log.contains(&format!("{} - OK", failed)));
+        assert!(fs::read_to_string(Path::new(&backup_folder).join(ERROR_LOG_FILE)).unwrap().contains(failed.as_str()));
+    }
+    
+    #[test]
+    fn test_log_lines_are_capped() {
+        let dir = TempDir::new("log_cap");
+        fs::create_dir_all(&dir).unwrap();
+        
+        let mut engine = BackupEngine::new();
+        engine.failed_files = (0..2500)
+            .map(|i| (format!("C:\\Data\\file{}.txt", i), "Access is denied.\r\n(os error 5)".to_string()))
+            .collect();
+        engine.total_files = 2500;
+        engine.save_logs(&dir.to_string_lossy()).unwrap();
+        
+        let errors = fs::read_to_string(dir.join(ERROR_LOG_FILE)).unwrap();
+        assert_eq!(errors.lines().filter(|l| l.contains("Failed!")).count(), MAX_LOG_LINES);
+        assert!(errors.contains("C:\\Data\\file0.txt - Failed! (Access is denied.  (os error 5))\n"));
+        assert!(errors.ends_with("... and 1500 more\n"));
+        
+        let log = fs::read_to_string(dir.join(LOG_FILE)).unwrap();
+        assert!(log.contains("Total files: 2500\n") && log.contains("Failed: 2500\n"));
+        assert!(log.contains("... and 1500 more\n"));
+        assert!(log.lines().count() < MAX_LOG_LINES + 20);
+    }
+    
+    #[test]
+    fn test_throughput_eta() {
+        let start = Instant::now();
+        let mut throughput = Throughput::start(start);
+        assert_eq!(throughput.eta(1000), None);
+        
+        // A burst of small files inside one sample period is measured together
+        throughput.update(start + Duration::from_millis(100), 5_000_000);
+        assert_eq!(throughput.eta(1000), None);
+        throughput.update(start + Duration::from_secs(1), 10_000_000);
+        assert_eq!(throughput.eta(10_000_000), Some(Duration::from_secs(1)));
+        
+        // One huge file finishing after a minute at a tenth of the speed dominates the average
+        throughput.update(start + Duration::from_secs(61), 70_000_000);
+        let eta = throughput.eta(10_000_000).unwrap();
+        assert!(eta > Duration::from_secs(6) && eta < Duration::from_secs(10), "{:?}", eta);
+        
+        let progress = BackupProgress { processed_files: 1, expected_files: 4, processed_bytes: 90, expected_bytes: 100, ..Default::default() };
+        assert_eq!(progress.files_percent(), Some(25.0));
+        assert_eq!(progress.bytes_percent(), Some(90.0));
+        assert_eq!(BackupProgress::default().bytes_percent(), None);
+    }
+    
+    #[test]
+    fn test_changed_since() {
+        let dir = TempDir::new("changed_since");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("a.txt"), "alpha").unwrap();
+        let sources = [dir.to_string_lossy().to_string(), dir.join("missing").to_string_lossy().to_string()];
+        
+        let none = ExcludePatterns::default();
+        assert!(changed_since(&sources, UNIX_EPOCH, false, false, &[], &none));
+        assert!(!changed_since(&sources, SystemTime::now() + Duration::from_secs(3600), false, false, &[], &none));
+        assert!(!changed_since(&sources[1..], UNIX_EPOCH, false, false, &[], &none));
+    }
+    
+    #[test]
+    fn test_incremental_backup_skips_unchanged_files() {
+        let dir = TempDir::new("incremental");
+        let source = dir.join("Documents");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        for (name, content) in [("a.txt", "alpha"), ("sub/b.txt", "beta"), ("c.txt", "gamma"), ("old.txt", "old")] {
+            fs::write(source.join(name), content).unwrap();
+        }
+        let sources = vec![source.to_string_lossy().to_string()];
+        let destination = dir.join("backups").to_string_lossy().to_string();
+        
+        // With nothing to build on, the first run copies everything and counts as a full backup
+        let mut engine = BackupEngine::new();
+        engine.mode = BackupMode::Incremental;
+        let first = engine.run_backup(&sources, &destination).unwrap();
+        engine.save_logs(&first).unwrap();
+        assert_eq!((engine.copied_files, engine.skipped_files), (4, 0));
+        let index = BackupIndex::load(Path::new(&first)).unwrap();
+        assert_eq!(index.mode, Some(BackupMode::Full));
+        assert_eq!(index.files.keys().map(String::as_str).collect::<Vec<_>>(),
+                   vec!["Documents/a.txt", "Documents/c.txt", "Documents/old.txt", "Documents/sub/b.txt"]);
+        assert_eq!(index.files.get("Documents/a.txt"), index_entry(&source.join("a.txt")).as_ref());
+        
+        // A new size, a new time at the same size, a new file and a deleted one
+        fs::write(source.join("a.txt"), "alpha, edited").unwrap();
+        filetime::set_file_mtime(source.join("c.txt"), FileTime::from_unix_time(1_600_000_000, 0)).unwrap();
+        fs::write(source.join("d.txt"), "delta").unwrap();
+        fs::remove_file(source.join("old.txt")).unwrap();
+        
+        let second = engine.run_backup(&sources, &destination).unwrap();
+        engine.save_logs(&second).unwrap();
+        assert_eq!((engine.total_files, engine.copied_files, engine.skipped_files), (4, 3, 1));
+        assert!(!Path::new(&second).join("Documents").join("sub").join("b.txt").exists());
+        assert!(Path::new(&second).join("Documents").join("c.txt").exists());
+        assert!(!engine.copied_paths.iter().any(|path| path.ends_with("b.txt")));
+        
+        // The index still lists the skipped file, so the next run compares against it too
+        let index = BackupIndex::load(Path::new(&second)).unwrap();
+        assert_eq!(index.mode, Some(BackupMode::Incremental));
+        assert_eq!(index.files.keys().map(String::as_str).collect::<Vec<_>>(),
+                   vec!["Documents/a.txt", "Documents/c.txt", "Documents/d.txt", "Documents/sub/b.txt"]);
+        
+        let third = engine.run_backup(&sources, &destination).unwrap();
+        engine.save_logs(&third).unwrap();
+        assert_eq!((engine.copied_files, engine.skipped_files), (0, 4));
+        assert_eq!(BackupIndex::load(Path::new(&third)).unwrap().files, index.files);
+        
+        // A full run ignores the previous backup
+        engine.mode = BackupMode::Full;
+        engine.run_backup(&sources, &destination).unwrap();
+        assert_eq!((engine.copied_files, engine.skipped_files), (4, 0));
+    }
+    
+    #[test]
+    fn test_walk_limits() {
+        let dir = TempDir::new("walk_limits");
+        let source = dir.join("Data");
+        fs::create_dir_all(source.join("a").join("b")).unwrap();
+        for name in ["1.txt", "2.txt", "3.txt"] {
+            fs::write(source.join(name), name).unwrap();
+        }
+        fs::write(source.join("a").join("b").join("deep.txt"), "deep").unwrap();
+        let sources = [source.to_string_lossy().to_string()];
+        let destination = dir.join("backups").to_string_lossy().to_string();
+        
+        let mut engine = BackupEngine::new();
+        engine.max_files = Some(3);
+        assert!(engine.run_backup(&sources, &destination).unwrap_err().contains("more than 3 files"));
+        
+        engine.max_files = None;
+        engine.max_depth = Some(2);
+        assert!(engine.run_backup(&sources, &destination).unwrap_err().contains("more than 2 levels"));
+        
+        engine.max_files = Some(4);
+        engine.max_depth = Some(3);
+        engine.run_backup(&sources, &destination).unwrap();
+        assert_eq!(engine.copied_files, 4);
+    }
+    
+    #[test]
+    fn test_restore_round_trip() {
+        let dir = TempDir::new("restore");
+        let source = dir.join("Documents");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("a.txt"), "alpha").unwrap();
+        fs::write(source.join("sub").join("b.txt"), "beta").unwrap();
+        
+        let mut engine = BackupEngine::new();
+        let backup_folder = engine.run_backup(&[source.to_string_lossy().to_string()], &dir.join("backups").to_string_lossy())
+            .unwrap();
+        engine.save_logs(&backup_folder).unwrap();
+        let backup_folder = PathBuf::from(backup_folder);
+        assert_eq!(list_backups(&dir.join("backups")), vec![backup_folder.clone()]);
+        
+        // Fresh restore copies the data but not DriveGuard's own files
+        let target = dir.join("restored");
+        engine.restore(&backup_folder, &target, false).unwrap();
+        assert_eq!((engine.total_files, engine.copied_files, engine.skipped_files), (2, 2, 0));
+        assert_eq!(fs::read_to_string(target.join("Documents").join("sub").join("b.txt")).unwrap(), "beta");
+        assert!(!target.join(LOG_FILE).exists());
+        assert!(!target.join(INDEX_FILE).exists());
+        
+        // Existing files are kept unless overwriting
+        fs::write(target.join("Documents").join("a.txt"), "changed").unwrap();
+        assert_eq!(restore_conflicts(&backup_folder, &target).len(), 2);
+        
+        engine.restore(&backup_folder, &target, false).unwrap();
+        assert_eq!((engine.copied_files, engine.skipped_files), (0, 2));
+        assert_eq!(fs::read_to_string(target.join("Documents").join("a.txt")).unwrap(), "changed");
+        
+        engine.restore(&backup_folder, &target, true).unwrap();
+        assert_eq!((engine.copied_files, engine.skipped_files), (2, 0));
+        assert_eq!(fs::read_to_string(target.join("Documents").join("a.txt")).unwrap(), "alpha");
+        
+        assert!(engine.restore(&dir.join("missing"), &target, true).is_err());
+    }
+    
+    #[test]
+    fn test_restore_incremental_backup() {
+        let dir = TempDir::new("restore_incremental");
+        let source = dir.join("Documents");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "alpha").unwrap();
+        fs::write(source.join("b.txt"), "beta").unwrap();
+        let sources = vec![source.to_string_lossy().to_string()];
+        let destination = dir.join("backups");
+        
+        let mut engine = BackupEngine::new();
+        engine.mode = BackupMode::Incremental;
+        let full = engine.run_backup(&sources, &destination.to_string_lossy()).unwrap();
+        engine.save_logs(&full).unwrap();
+        fs::write(source.join("b.txt"), "beta, edited").unwrap();
+        let incremental = engine.run_backup(&sources, &destination.to_string_lossy()).unwrap();
+        engine.save_logs(&incremental).unwrap();
+        assert_eq!((engine.copied_files, engine.skipped_files), (1, 1));
+        
+        // The unchanged file comes from the full backup before it
+        let target = dir.join("restored");
+        assert!(restore_conflicts(Path::new(&incremental), &target).is_empty());
+        engine.restore(Path::new(&incremental), &target, false).unwrap();
+        assert_eq!((engine.total_files, engine.copied_files), (2, 2));
+        assert!(engine.failed_files.is_empty(), "{:?}", engine.failed_files);
+        assert_eq!(fs::read_to_string(target.join("Documents").join("a.txt")).unwrap(), "alpha");
+        assert_eq!(fs::read_to_string(target.join("Documents").join("b.txt")).unwrap(), "beta, edited");
+        assert_eq!(restore_conflicts(Path::new(&incremental), &target).len(), 2);
+        
+        // Without it the file can't be found, which is reported rather than skipped silently
+        fs::remove_dir_all(&full).unwrap();
+        engine.restore(Path::new(&incremental), &target, true).unwrap();
+        assert_eq!((engine.copied_files, engine.failed_files.len()), (1, 1));
+        assert!(engine.failed_files[0].0.ends_with("a.txt"));
+    }
+    
+    #[test]
+    fn test_incomplete_backups_are_excluded() {
+        let dir = TempDir::new("incomplete");
+        let complete = dir.join("2025-01-01T00-00-00");
+        let interrupted = dir.join("2025-02-01T00-00-00"); // Still has its state file
+        let no_log = dir.join("2025-03-01T00-00-00");
+        let unrelated = dir.join("My Documents");
+        
+        for folder in [&complete, &interrupted, &no_log, &unrelated] {
+            fs::create_dir_all(folder).unwrap();
+            fs::write(folder.join(INDEX_FILE), "{}").unwrap();
+        }
+        fs::write(complete.join(LOG_FILE), "").unwrap();
+        fs::write(interrupted.join(LOG_FILE), "").unwrap();
+        fs::write(interrupted.join(STATE_FILE), "{}").unwrap();
+        filetime::set_file_mtime(interrupted.join(STATE_FILE), FileTime::from_unix_time(1_600_000_000, 0)).unwrap();
+        fs::write(unrelated.join(LOG_FILE), "").unwrap();
+        
+        // The newest complete backup is the baseline, not the newer broken ones
+        assert_eq!(list_backups(&dir), vec![complete.clone()]);
+        assert_eq!(find_latest_backup(&dir), Some(complete.clone()));
+        
+        // Marked once, then not reported again
+        assert_eq!(mark_incomplete_backups(&dir), vec![interrupted.clone(), no_log.clone()]);
+        assert!(interrupted.join(INCOMPLETE_MARKER).exists());
+        assert!(mark_incomplete_backups(&dir).is_empty());
+        
+        // Finishing the log later doesn't make a marked backup trustworthy again
+        fs::write(no_log.join(LOG_FILE), "").unwrap();
+        assert!(!is_complete_backup(&no_log));
+        assert!(!unrelated.join(INCOMPLETE_MARKER).exists());
+    }
+    
+    #[test]
+    fn test_same_second_backups_get_own_folders() {
+        let dir = TempDir::new("collision");
+        let source = dir.join("Documents");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "alpha").unwrap();
+        let sources = [source.to_string_lossy().to_string()];
+        let destination = dir.join("backups").to_string_lossy().to_string();
+        
+        let mut engine = BackupEngine::new();
+        engine.clock = || DateTime::parse_from_rfc3339("2025-06-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        let first = engine.run_backup(&sources, &destination).unwrap();
+        engine.save_logs(&first).unwrap();
+        let second = engine.run_backup(&sources, &destination).unwrap();
+        engine.save_logs(&second).unwrap();
+        
+        assert_ne!(first, second);
+        assert!(first.ends_with("2025-06-01T12-00-00"));
+        assert!(second.ends_with("2025-06-01T12-00-00_2"));
+        
+        // Both count as backups, the later one as the latest
+        assert_eq!(list_backups(Path::new(&destination)), vec![PathBuf::from(&first), PathBuf::from(&second)]);
+        assert_eq!(find_latest_backup(Path::new(&destination)), Some(PathBuf::from(&second)));
+    }
+    
+    #[test]
+    fn test_dedup_sources() {
+        let sources = ["C:\\Data", "C:\\Data\\", "c:\\data", "C:/Data/", "C:\\Other"].map(String::from);
+        assert_eq!(dedup_sources(&sources), vec!["C:\\Data".to_string(), "C:\\Other".to_string()]);
+    }
+    
+    #[test]
+    fn test_duplicate_sources_are_copied_once() {
+        let dir = TempDir::new("duplicate_source");
+        let source = dir.join("Data");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "alpha").unwrap();
+        let source = source.to_string_lossy().to_string();
+        
+        let mut engine = BackupEngine::new();
+        let backup_folder = engine.run_backup(&[source.clone(), format!("{}/", source)], &dir.join("backups").to_string_lossy())
+            .unwrap();
+        
+        assert!(Path::new(&backup_folder).join("Data").join("a.txt").exists());
+        assert!(!Path::new(&backup_folder).join("Data_1").exists());
+        assert_eq!(engine.copied_files, 1);
+    }
+    
+    #[test]
+    fn test_expand_destination() {
+        let now = DateTime::parse_from_rfc3339("2025-06-15T12:00:00Z").unwrap().with_timezone(&Utc);
+        
+        assert_eq!(expand_destination("D:\\Backups\\{schedule_name}\\{year}\\{month}", "Laptop", now).unwrap(),
+                   "D:\\Backups\\Laptop\\2025\\06");
+        assert_eq!(expand_destination("D:/Backups/{date} daily/", "Laptop", now).unwrap(), "D:/Backups/2025-06-15 daily/");
+        assert_eq!(expand_destination("E:\\Backups", "Laptop", now).unwrap(), "E:\\Backups");
+        
+        // The name can't add folders or characters Windows rejects
+        assert_eq!(expand_destination("D:\\{schedule_name}", "Work: C:\\Docs", now).unwrap(), "D:\\Work_ C__Docs");
+        
+        assert!(expand_destination("D:\\{drive}", "Laptop", now).is_err());
+        assert!(expand_destination("D:\\{year", "Laptop", now).is_err());
+        assert!(expand_destination("D:\\year}", "Laptop", now).is_err());
+        
+        assert_eq!(template_root(Path::new("backups/{schedule_name}/{year}")), PathBuf::from("backups"));
+        assert_eq!(template_root(Path::new("backups/Laptop")), PathBuf::from("backups/Laptop"));
+    }
+    
+    #[test]
+    fn test_check_destination() {
+        let dir = TempDir::new("destination");
+        fs::create_dir_all(&dir).unwrap();
+        
+        assert_eq!(check_destination(&dir), Ok(()));
+        assert_eq!(check_destination(&dir.join("bakcups")), Err(DestinationProblem::FolderMissing));
+        
+        // A file where the folder should be is not a usable destination either
+        fs::write(dir.join("file"), "x").unwrap();
+        assert_eq!(check_destination(&dir.join("file")), Err(DestinationProblem::FolderMissing));
+    }
+    
+    #[test]
+    fn test_newest_only_keeps_latest_per_folder() {
+        let dir = TempDir::new("newest_only");
+        let source = dir.join("Exports");
+        let put = |relative: &str, age_days: i64| {
+            let path = source.join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, relative).unwrap();
+            let time = FileTime::from_unix_time(1_700_000_000 - age_days * 86_400, 0);
+            filetime::set_file_mtime(&path, time).unwrap();
+        };
+        put("report_2025-01.csv", 60);
+        put("Report_2025-03.CSV", 2);
+        put("report_2025-02.csv", 30);
+        put("summary.txt", 90);
+        put("old/report_2024-12.csv", 400);
+        
+        let mut engine = BackupEngine::new();
+        engine.newest_only = vec!["report_*.csv".to_string()];
+        let backup_folder = engine.run_backup(&[source.to_string_lossy().to_string()], &dir.join("backups").to_string_lossy())
+            .unwrap();
+        
+        // Groups are per folder; files matching no pattern are untouched
+        let mut copied: Vec<&String> = engine.index.files.keys().collect();
+        copied.sort();
+        assert_eq!(copied, vec!["Exports/Report_2025-03.CSV", "Exports/old/report_2024-12.csv", "Exports/summary.txt"]);
+        assert_eq!(engine.superseded_files.len(), 2);
+        
+        engine.save_logs(&backup_folder).unwrap();
+        let log = fs::read_to_string(Path::new(&backup_folder).join(LOG_FILE)).unwrap();
+        assert!(log.contains("Superseded by a newer file: 2"));
+    }
 }
@@ -0,0 +1,195 @@
+// Backup run history
+// Every backup run is appended to history.json; the tray's statistics window summarizes it
+// per schedule so backups that suddenly take much longer or keep failing stand out
+
+use std::fs;
+use std::path::Path;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use crate::backup::BackupSummary;
+
+const HISTORY_FILE: &str = "history.json";
+
+/// Older runs are dropped once the history holds this many
+const MAX_ENTRIES: usize = 1000;
+
+/// Failures are counted over this many days
+const RECENT_DAYS: i64 = 30;
+
+/// One backup run of one schedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub schedule_id: String,
+    pub started: String, // ISO 8601 format
+    pub duration_seconds: u64,
+    pub copied_files: usize,
+    pub copied_bytes: u64,
+    pub failed_files: usize,
+    pub error: Option<String>, // Set when the backup failed as a whole
+}
+
+impl HistoryEntry {
+    pub fn new(schedule_id: &str, started: DateTime<Utc>, finished: DateTime<Utc>,
+               result: &Result<BackupSummary, String>) -> Self {
+        let mut entry = Self {
+            schedule_id: schedule_id.to_string(),
+            started: started.to_rfc3339(),
+            duration_seconds: finished.signed_duration_since(started).num_seconds().max(0) as u64,
+            copied_files: 0,
+            copied_bytes: 0,
+            failed_files: 0,
+            error: None,
+        };
+        
+        match result {
+            Ok(summary) => {
+                entry.copied_files = summary.copied_files;
+                entry.copied_bytes = summary.copied_bytes;
+                entry.failed_files = summary.failed_files.len();
+            }
+            Err(e) => entry.error = Some(e.clone()),
+        }
+        entry
+    }
+    
+    fn started_at(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.started).ok().map(|t| t.with_timezone(&Utc))
+    }
+}
+
+/// Statistics of one schedule's runs; averages only cover backups that completed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScheduleStats {
+    pub runs: usize,
+    pub average_seconds: u64,
+    pub last_seconds: Option<u64>, // Duration of the latest completed backup
+    pub average_files: usize,
+    pub average_bytes: u64,
+    pub recent_runs: usize, // Within the last RECENT_DAYS days
+    pub recent_failures: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackupHistory {
+    pub entries: Vec<HistoryEntry>, // Oldest first
+}
+
+impl BackupHistory {
+    pub fn load() -> Self {
+        if !Path::new(HISTORY_FILE).exists() {
+            return Self::default();
+        }
+        
+        match fs::read_to_string(HISTORY_FILE).map(|c| serde_json::from_str(&c)) {
+            Ok(Ok(history)) => history,
+            Ok(Err(e)) => {
+                log::warn!("Ignoring unreadable {}: {}", HISTORY_FILE, e);
+                Self::default()
+            }
+            Err(e) => {
+                log::warn!("Failed to read {}: {}", HISTORY_FILE, e);
+                Self::default()
+            }
+        }
+    }
+    
+    pub fn save(&self) {
+        match serde_json::to_string_pretty(self) {
+            Ok(content) => {
+                if let Err(e) = fs::write(HISTORY_FILE, content) {
+                    log::warn!("Failed to write {}: {}", HISTORY_FILE, e);
+                }
+            }
+            Err(e) => log::warn!("Failed to serialize backup history: {}", e),
+        }
+    }
+    
+    /// Append a finished run to history.json
+    pub fn record(entry: HistoryEntry) {
+        let mut history = Self::load();
+        history.push(entry);
+        history.save();
+    }
+    
+    fn push(&mut self, entry: HistoryEntry) {
+        self.entries.push(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            let excess = self.entries.len() - MAX_ENTRIES;
+            self.entries.drain(..excess);
+        }
+    }
+    
+    pub fn stats(&self, schedule_id: &str, now: DateTime<Utc>) -> ScheduleStats {
+        let runs: Vec<&HistoryEntry> = self.entries.iter().filter(|e| e.schedule_id == schedule_id).collect();
+        let completed: Vec<&HistoryEntry> = runs.iter().copied().filter(|e| e.error.is_none()).collect();
+        let recent: Vec<&HistoryEntry> = runs
+            .iter()
+            .copied()
+            .filter(|e| e.started_at().is_some_and(|t| now.signed_duration_since(t) <= Duration::days(RECENT_DAYS)))
+            .collect();
+        
+        let mut stats = ScheduleStats {
+            runs: runs.len(),
+            last_seconds: completed.last().map(|e| e.duration_seconds),
+            recent_runs: recent.len(),
+            recent_failures: recent.iter().filter(|e| e.error.is_some()).count(),
+            ..Default::default()
+        };
+        
+        if !completed.is_empty() {
+            let count = completed.len() as u64;
+            stats.average_seconds = completed.iter().map(|e| e.duration_seconds).sum::<u64>() / count;
+            stats.average_files = (completed.iter().map(|e| e.copied_files as u64).sum::<u64>() / count) as usize;
+            stats.average_bytes = completed.iter().map(|e| e.copied_bytes).sum::<u64>() / count;
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn run(schedule_id: &str, started: DateTime<Utc>, seconds: u64, bytes: u64, error: Option<&str>) -> HistoryEntry {
+        HistoryEntry {
+            schedule_id: schedule_id.to_string(),
+            started: started.to_rfc3339(),
+            duration_seconds: seconds,
+            copied_files: (bytes / 100) as usize,
+            copied_bytes: bytes,
+            failed_files: 0,
+            error: error.map(str::to_string),
+        }
+    }
+    
+    #[test]
+    fn test_schedule_stats() {
+        let now = Utc::now();
+        let mut history = BackupHistory::default();
+        history.push(run("weekly", now - Duration::days(60), 100, 1000, None));
+        history.push(run("weekly", now - Duration::days(40), 0, 0, Some("Destination drive E:\\ is not connected")));
+        history.push(run("weekly", now - Duration::days(10), 200, 3000, None));
+        history.push(run("weekly", now - Duration::days(3), 1, 0, Some("No source paths configured in backup list")));
+        history.push(run("daily", now, 5, 10, None));
+        
+        let stats = history.stats("weekly", now);
+        assert_eq!(stats, ScheduleStats {
+            runs: 4,
+            average_seconds: 150, // failed runs don't drag the averages down
+            last_seconds: Some(200),
+            average_files: 20,
+            average_bytes: 2000,
+            recent_runs: 2,
+            recent_failures: 1,
+        });
+        
+        assert_eq!(history.stats("missing", now), ScheduleStats::default());
+        
+        // The oldest runs make room for new ones
+        for _ in 0..MAX_ENTRIES {
+            history.push(run("daily", now, 5, 10, None));
+        }
+        assert_eq!(history.entries.len(), MAX_ENTRIES);
+        assert_eq!(history.stats("weekly", now).runs, 0);
+    }
+}
@@ -0,0 +1,154 @@
+use native_windows_gui as nwg;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::thread;
+
+/// Files that failed in a finished batch, with the reports to open for details
+#[derive(Debug, Clone, Default)]
+pub struct FailureReport {
+    pub summary: String, // Same per-schedule text the completion message shows
+    pub failed_files: Vec<(String, String)>, // (path, error)
+    pub error_logs: Vec<String>, // backup_errors.txt of each schedule with failures
+}
+
+/// Completion dialog for a backup where some files couldn't be copied
+pub struct BackupReportWindow {
+    window: nwg::Window,
+    
+    // Never read, but the controls only stay on screen while the struct owns them
+    #[allow(dead_code)]
+    label_summary: nwg::Label,
+    #[allow(dead_code)]
+    list_failed: nwg::ListBox<String>,
+    
+    btn_view_errors: nwg::Button,
+    btn_close: nwg::Button,
+    
+    error_logs: Vec<String>,
+    
+    handler: RefCell<Option<nwg::EventHandler>>,
+}
+
+impl BackupReportWindow {
+    pub fn show(report: FailureReport) {
+        if crate::status::is_headless() {
+            return;
+        }
+        
+        thread::spawn(move || {
+            if let Err(e) = nwg::init() {
+                log::error!("Failed to init NWG in report thread: {:?}", e);
+                return;
+            }
+            
+            if let Err(e) = Self::build_and_run(report) {
+                log::error!("Failed to build backup report window: {:?}", e);
+            }
+        });
+    }
+    
+    fn build_and_run(report: FailureReport) -> Result<(), nwg::NwgError> {
+        let mut window = Default::default();
+        nwg::Window::builder()
+            .size((600, 420))
+            .position((300, 300))
+            .title(&crate::localization::t("report_title"))
+            .flags(nwg::WindowFlags::WINDOW | nwg::WindowFlags::VISIBLE)
+            .build(&mut window)?;
+        
+        let mut label_summary = Default::default();
+        nwg::Label::builder()
+            .text(&format!("{}\n\n{}", report.summary.trim_end(),
+                           crate::localization::tf("report_failed_files", &[&report.failed_files.len().to_string()])))
+            .parent(&window)
+            .position((20, 10))
+            .size((560, 100))
+            .build(&mut label_summary)?;
+        
+        // Similar errors end up next to each other
+        let mut failed_files = report.failed_files;
+        failed_files.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        let items: Vec<String> = failed_files
+            .iter()
+            .map(|(path, error)| format!("{} — {}", path, error))
+            .collect();
+        
+        let mut list_failed = Default::default();
+        nwg::ListBox::builder()
+            .collection(items)
+            .parent(&window)
+            .position((20, 115))
+            .size((560, 230))
+            .build(&mut list_failed)?;
+        
+        let mut btn_view_errors = Default::default();
+        nwg::Button::builder()
+            .text(&crate::localization::t("button_view_errors"))
+            .parent(&window)
+            .position((280, 360))
+            .size((140, 40))
+            .enabled(!report.error_logs.is_empty())
+            .build(&mut btn_view_errors)?;
+        
+        let mut btn_close = Default::default();
+        nwg::Button::builder()
+            .text(&crate::localization::t("button_close"))
+            .parent(&window)
+            .position((440, 360))
+            .size((140, 40))
+            .build(&mut btn_close)?;
+        
+        let app = Rc::new(BackupReportWindow {
+            window,
+            label_summary,
+            list_failed,
+            btn_view_errors,
+            btn_close,
+            error_logs: report.error_logs,
+            handler: RefCell::new(None),
+        });
+        
+        let app_clone = app.clone();
+        let handler = nwg::full_bind_event_handler(&app.window.handle, move |evt, _evt_data, handle| {
+            use nwg::Event;
+            
+            if handle == app_clone.btn_view_errors {
+                if let Event::OnButtonClick = evt {
+                    app_clone.open_error_logs();
+                }
+            } else if handle == app_clone.btn_close {
+                if let Event::OnButtonClick = evt {
+                    nwg::stop_thread_dispatch();
+                }
+            } else if handle == app_clone.window {
+                if let Event::OnWindowClose = evt {
+                    nwg::stop_thread_dispatch();
+                }
+            }
+        });
+        
+        *app.handler.borrow_mut() = Some(handler);
+        
+        nwg::dispatch_thread_events();
+        Ok(())
+    }
+    
+    /// Open each error report with its default program
+    fn open_error_logs(&self) {
+        for path in &self.error_logs {
+            if let Err(e) = std::process::Command::new("explorer").arg(path).spawn() {
+                log::warn!("Failed to open {}: {}", path, e);
+                nwg::modal_error_message(&self.window, "DriveGuard", &format!("Failed to open {}:\n\n{}", path, e));
+            }
+        }
+    }
+}
+
+impl Drop for BackupReportWindow {
+    fn drop(&mut self) {
+        let handler = self.handler.borrow();
+        if let Some(h) = handler.as_ref() {
+            nwg::unbind_event_handler(h);
+        }
+    }
+}
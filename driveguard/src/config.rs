@@ -1,12 +1,30 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use chrono::{DateTime, Utc, Duration};
-use driveguard_shared::manifest::UpdateSettings;
+use chrono::{Datelike, DateTime, Duration, Local, NaiveTime, TimeZone, Utc, Weekday};
+use std::ops::RangeInclusive;
+use std::thread;
+use driveguard_shared::checksum;
+use driveguard_shared::manifest::{SourceStatus, UpdateSettings};
+use driveguard_shared::update_state;
+use crate::backup::{BackupMode, ExcludePatterns, MissingSourcePolicy, OutputFormat, RetentionPolicy};
 
 const CONFIG_FILE: &str = "settings.toml";
+const SCHEDULES_HASH_FILE: &str = "settings.toml.sha256"; // Hash of the schedules as DriveGuard last saved them
 const SCHEDULES_DIR: &str = "schedules";
 
+// Allowed ranges for the background thread intervals (seconds)
+const MONITOR_POLL_RANGE: RangeInclusive<u64> = 1..=300;
+const SCHEDULE_POLL_RANGE: RangeInclusive<u64> = 10..=3600;
+const COPY_BUFFER_KB_RANGE: RangeInclusive<u64> = 4..=65536;
+const CHANGE_CHECK_MINUTES_RANGE: RangeInclusive<u64> = 5..=1440;
+const MAX_COPY_WORKERS: usize = 32;
+const DEFAULT_MAX_COPY_WORKERS: usize = 8;
+
+// A last_backup further ahead than this was saved while the clock was wrong
+const CLOCK_SKEW_TOLERANCE_MINUTES: i64 = 10;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub general: GeneralSettings,
@@ -15,14 +33,58 @@ pub struct AppConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeneralSettings {
+    #[serde(default = "default_language", alias = "language")]
+    pub ui_language: String, // Windows, tray and balloons
     #[serde(default = "default_language")]
-    pub language: String,
+    pub log_language: String, // driveguard.log and other support-facing output; English keeps bug reports readable
     #[serde(default = "default_min_free_space")]
     pub min_free_space_gb: u64,
     #[serde(default = "default_true")]
     pub warn_before_delete: bool,
     #[serde(default)]
     pub update_settings: Option<UpdateSettings>,
+    #[serde(default = "default_monitor_poll")]
+    pub monitor_poll_seconds: u64, // How often to look for connected drives
+    #[serde(default = "default_schedule_poll")]
+    pub schedule_poll_seconds: u64, // How often to check for due scheduled backups
+    #[serde(default)]
+    pub countdown_style: CountdownStyle,
+    #[serde(default)]
+    pub detect_config_changes: bool, // Warn when the schedules were edited outside DriveGuard
+    #[serde(default)]
+    pub ignored_drives: Vec<String>, // Drive letters never looked at: "A", "D:", or ranges like "M-Z"
+    #[serde(default = "default_change_check_minutes")]
+    pub change_check_minutes: u64, // How often to look for source changes not backed up yet, shown in the tray (0 = off)
+    #[serde(default = "default_true")]
+    pub backups_enabled: bool, // Master switch: when off, drives and schedules are still watched but nothing is backed up
+    #[serde(default)]
+    pub remember_backups_off: bool, // Save the tray's Backups Enabled toggle as backups_enabled, so it survives a restart
+    #[serde(default)]
+    pub copy_workers: usize, // Files copied at the same time; 0 = one per processor core (at most 8), 1 = one after another
+}
+
+/// Which identity of a drive a schedule's serials refer to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriveMatchKind {
+    /// The volume serial ("A1B2-C3D4"); changes when the drive is reformatted
+    #[default]
+    VolumeSerial,
+    /// The serial number the device itself reports; survives reformatting
+    HardwareSerial,
+    /// Only the `.driveGuardID` file at the root of the drive
+    IdFile,
+}
+
+/// How the countdown before a connect-triggered backup is presented
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CountdownStyle {
+    /// Countdown window with Start Now / Hide / Cancel buttons
+    #[default]
+    Window,
+    /// Tray tooltip only; Start Now / Cancel from the tray menu
+    Tray,
 }
 
 // Default value functions for serde
@@ -38,6 +100,18 @@ fn default_true() -> bool {
     true
 }
 
+fn default_change_check_minutes() -> u64 {
+    30
+}
+
+fn default_monitor_poll() -> u64 {
+    2
+}
+
+fn default_schedule_poll() -> u64 {
+    60
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackupSchedule {
     pub id: String,
@@ -46,13 +120,78 @@ pub struct BackupSchedule {
     
     // Drive identification
     pub drive_serial: Option<String>,
+    #[serde(default)]
+    pub drive_serials: Vec<String>, // More accepted serials, e.g. two drives rotated off-site
+    #[serde(default)]
+    pub drive_label: Option<String>, // Volume label to accept; `*` is a wildcard ("BACKUP*")
     pub drive_id_file: bool,
+    #[serde(default)]
+    pub match_by: DriveMatchKind, // What drive_serial / drive_serials are compared with
     
     // Backup settings
     pub source_paths: Vec<String>,
-    pub destination_path: String,
+    #[serde(default)]
+    pub backup_list_source: Option<String>, // URL or UNC path of a centrally managed backup list, cached for offline use
+    #[serde(default)]
+    pub destination_path: String, // May be left out when `destinations` is set
+    #[serde(default)]
+    pub destinations: Vec<RotationDestination>, // Rotated drives: back up to whichever is connected, instead of destination_path
     pub interval_days: u64,
+    #[serde(default)]
+    pub time_of_day: Option<String>, // "HH:MM" local time; when set, replaces interval_days ("every day at 02:00")
+    #[serde(default)]
+    pub days_of_week: Vec<String>, // With time_of_day: only on these days ("mon", "fri"); empty = every day
     pub last_backup: Option<String>, // ISO 8601 format
+    #[serde(default)]
+    pub first_backup_confirmed: bool, // The user OK'd the summary shown before the first backup
+    #[serde(default)]
+    pub backup_mode: BackupMode,
+    #[serde(default)]
+    pub dedup_identical_files: bool, // Hard-link duplicate files within a backup
+    #[serde(default)]
+    pub eject_after_backup: bool, // Safely remove the destination drive when done
+    #[serde(default)]
+    pub diff_report: bool, // Write backup_diff.txt listing changes since the last backup
+    #[serde(default)]
+    pub missing_source_policy: MissingSourcePolicy,
+    #[serde(default)]
+    pub skip_hidden: Option<bool>, // Unset = skip when backing up to a removable drive
+    #[serde(default)]
+    pub skip_system: Option<bool>, // Unset = skip when backing up to a removable drive
+    #[serde(default)]
+    pub min_file_age_seconds: u64, // Skip files modified more recently than this (0 = copy everything)
+    #[serde(default = "default_copy_buffer_kb")]
+    pub copy_buffer_kb: u64, // Copy buffer size; raise it for slow USB flash drives and network shares
+    #[serde(default)]
+    pub lifecycle: Option<LifecyclePolicy>, // When set, picks full/incremental per drive instead of backup_mode
+    #[serde(default)]
+    pub compress_logs: bool, // Store backup.txt/backup_errors.txt as .gz
+    #[serde(default)]
+    pub output_format: OutputFormat, // "zip" streams the files into one <timestamp>.zip instead of copying them
+    #[serde(default)]
+    pub verify_after_copy: bool, // Read every copied file back and compare its SHA-256 with the source
+    #[serde(default)]
+    pub keep_uncompressed_backups: usize, // Newest N backups stay as folders, older ones become .tar.gz (0 = off)
+    #[serde(default)]
+    pub retention: Option<RetentionPolicy>, // Old backups to delete after a successful run; unset = keep all
+    #[serde(default)]
+    pub max_files: Option<usize>, // Abort a run over more files than this; unset = 100000 for a whole-drive source, 0 = no limit
+    #[serde(default)]
+    pub max_depth: Option<usize>, // Abort a run over folders nested deeper; unset = 32 for a whole-drive source, 0 = no limit
+    #[serde(default)]
+    pub newest_only: Vec<String>, // File name patterns ("report_*.csv"); of the matches in a folder only the newest is backed up
+    #[serde(default)]
+    pub exclude: Vec<String>, // Globs relative to each source ("*.tmp", "**/node_modules/**"); matches aren't backed up
+    #[serde(skip)]
+    pub shares_destination: bool, // Set by validate(): another enabled schedule uses the same destination_path
+    
+    // Tray balloons for this schedule's backups
+    #[serde(default)]
+    pub notify_on_start: bool,
+    #[serde(default)]
+    pub notify_on_success: bool,
+    #[serde(default = "default_true")]
+    pub notify_on_failure: bool,
     
     // Trigger settings
     pub trigger_on_connect: bool,
@@ -60,14 +199,56 @@ pub struct BackupSchedule {
     pub countdown_minutes: u64,
 }
 
+/// One of the drives a schedule rotates between, identified by serial and/or label
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RotationDestination {
+    #[serde(default)]
+    pub drive_serial: Option<String>,
+    #[serde(default)]
+    pub drive_label: Option<String>, // `*` is a wildcard, as in the schedule's drive_label
+    #[serde(default)]
+    pub folder: String, // On that drive, e.g. "Backups\\Laptop"; empty = the drive root
+}
+
+/// Full backup the first time a drive connects, incremental afterwards
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LifecyclePolicy {
+    #[serde(default = "default_full_every_runs")]
+    pub full_every_runs: u32, // Force a full backup after this many incrementals (0 = never)
+    #[serde(default = "default_full_every_days")]
+    pub full_every_days: u32, // Force a full backup once the last one is this old (0 = never)
+}
+
+fn default_copy_buffer_kb() -> u64 {
+    (crate::backup::DEFAULT_COPY_BUFFER_SIZE / 1024) as u64
+}
+
+fn default_full_every_runs() -> u32 {
+    10
+}
+
+fn default_full_every_days() -> u32 {
+    30
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             general: GeneralSettings {
-                language: "en".to_string(),
+                ui_language: "en".to_string(),
+                log_language: "en".to_string(),
                 min_free_space_gb: 10,
                 warn_before_delete: true,
                 update_settings: Some(UpdateSettings::default()),
+                monitor_poll_seconds: default_monitor_poll(),
+                schedule_poll_seconds: default_schedule_poll(),
+                countdown_style: CountdownStyle::Window,
+                detect_config_changes: false,
+                ignored_drives: Vec::new(),
+                change_check_minutes: default_change_check_minutes(),
+                backups_enabled: true,
+                remember_backups_off: false,
+                copy_workers: 0,
             },
             schedules: Vec::new(),
         }
@@ -78,7 +259,7 @@ impl AppConfig {
     pub fn load_or_create() -> Self {
         if Path::new(CONFIG_FILE).exists() {
             log::info!("Loading config from {}", CONFIG_FILE);
-            let content = fs::read_to_string(CONFIG_FILE)
+            let content = read_text_file(Path::new(CONFIG_FILE))
                 .expect("Failed to read config file");
             
             log::info!("Config file content:\n{}", content);
@@ -87,6 +268,11 @@ impl AppConfig {
                 Ok(mut config) => {
                     log::info!("Successfully parsed config with {} schedules", config.schedules.len());
                     
+                    // Before anything below saves the config and records a new hash
+                    if config.general.detect_config_changes {
+                        config.check_schedules_hash();
+                    }
+                    
                     // Merge with defaults for any missing fields
                     if config.general.update_settings.is_none() {
                         log::info!("Update settings missing, using defaults");
@@ -97,6 +283,8 @@ impl AppConfig {
                     for schedule in &config.schedules {
                         log::info!("  - Schedule: {} (enabled: {})", schedule.name, schedule.enabled);
                     }
+                    
+                    config.validate();
                     config
                 }
                 Err(e) => {
@@ -126,11 +314,148 @@ impl AppConfig {
         }
     }
     
+    /// Fix up out-of-range values, warning about each one
+    pub fn validate(&mut self) {
+        self.general.monitor_poll_seconds = clamp_setting(
+            "monitor_poll_seconds", self.general.monitor_poll_seconds, MONITOR_POLL_RANGE);
+        self.general.schedule_poll_seconds = clamp_setting(
+            "schedule_poll_seconds", self.general.schedule_poll_seconds, SCHEDULE_POLL_RANGE);
+        if self.general.change_check_minutes != 0 {
+            self.general.change_check_minutes = clamp_setting(
+                "change_check_minutes", self.general.change_check_minutes, CHANGE_CHECK_MINUTES_RANGE);
+        }
+        for entry in &self.general.ignored_drives {
+            if parse_drive_letters(entry).is_none() {
+                log::warn!("ignored_drives entry \"{}\" is not a drive letter or range like \"M-Z\", ignoring it", entry);
+            }
+        }
+        
+        for schedule in &mut self.schedules {
+            schedule.copy_buffer_kb = clamp_setting(
+                "copy_buffer_kb", schedule.copy_buffer_kb, COPY_BUFFER_KB_RANGE);
+            
+            if let Err(e) = schedule.time_spec() {
+                log::warn!("Schedule '{}': {}, using interval_days = {} instead", schedule.name, e, schedule.interval_days);
+            }
+            
+            if let Err(e) = schedule.exclude_patterns() {
+                log::warn!("Schedule '{}': {}, its backups will fail until it is fixed", schedule.name, e);
+            }
+            
+            let volume_serials = schedule.match_by == DriveMatchKind::VolumeSerial;
+            for serial in schedule.accepted_serials().iter().filter(|s| volume_serials && parse_volume_serial(s).is_none()) {
+                log::warn!("Schedule '{}': drive serial \"{}\" is neither decimal nor hex like \"A1B2-C3D4\", no drive will match it",
+                           schedule.name, serial);
+            }
+            
+            if schedule.rotates_destinations() {
+                for destination in &schedule.destinations {
+                    let has = |value: &Option<String>| value.as_deref().is_some_and(|v| !v.trim().is_empty());
+                    if !has(&destination.drive_serial) && !has(&destination.drive_label) {
+                        log::warn!("Schedule '{}': destination \"{}\" has neither drive_serial nor drive_label, it can never be selected",
+                                   schedule.name, destination.folder);
+                    }
+                }
+                continue;
+            }
+            
+            if overlaps_app_dir(&schedule.destination().to_string_lossy()) {
+                log::warn!("Schedule '{}': destination {} overlaps DriveGuard's own folder {}; updates can disturb backups there, choose another folder",
+                           schedule.name, schedule.destination().display(), app_dir().display());
+            }
+            
+            if schedule.has_relative_destination() && !schedule.destination_path.trim().is_empty() {
+                log::warn!("Schedule '{}': destination_path \"{}\" is relative, backing up to {} (use a full path like E:\\Backups)",
+                           schedule.name, schedule.destination_path, schedule.destination().display());
+            }
+            
+            if let Err(e) = schedule.destination_folder(Utc::now()) {
+                log::warn!("Schedule '{}': {}; its backups will fail until it is fixed", schedule.name, e);
+            }
+        }
+        
+        let shared: Vec<String> = self.shared_destinations()
+            .iter()
+            .map(|(destination, _)| normalize_destination(destination))
+            .collect();
+        for schedule in &mut self.schedules {
+            schedule.shares_destination = schedule.enabled && shared.contains(&schedule.destination_key());
+        }
+        for warning in self.shared_destination_warnings() {
+            log::warn!("{}", warning);
+        }
+    }
+    
+    /// Destinations used by more than one enabled schedule, with those schedules' names.
+    /// Their backups, indices and retention would interfere with each other.
+    pub fn shared_destinations(&self) -> Vec<(String, Vec<String>)> {
+        let mut by_destination: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+        
+        for schedule in self.schedules.iter().filter(|s| s.enabled && !s.rotates_destinations()) {
+            by_destination
+                .entry(schedule.destination_key())
+                .or_insert_with(|| (schedule.destination_path.clone(), Vec::new()))
+                .1
+                .push(schedule.name.clone());
+        }
+        
+        by_destination.into_values().filter(|(_, names)| names.len() > 1).collect()
+    }
+    
+    /// One user-facing warning per shared destination
+    pub fn shared_destination_warnings(&self) -> Vec<String> {
+        self.shared_destinations()
+            .into_iter()
+            .map(|(destination, names)| format!(
+                "Schedules {} all back up to {}. Give each its own subfolder (e.g. {}\\{}) so their \
+                 incremental history and old-backup compression don't interfere.",
+                names.iter().map(|n| format!("'{}'", n)).collect::<Vec<_>>().join(", "),
+                destination,
+                destination.trim_end_matches(['\\', '/']),
+                names[0],
+            ))
+            .collect()
+    }
+    
     pub fn save(&self) {
         let content = toml::to_string_pretty(self)
             .expect("Failed to serialize config");
         fs::write(CONFIG_FILE, content)
             .expect("Failed to write config file");
+        
+        if self.general.detect_config_changes {
+            self.save_schedules_hash();
+        }
+    }
+    
+    /// SHA-256 of the schedules as DriveGuard sees them, independent of formatting and comments
+    pub fn schedules_hash(&self) -> String {
+        let json = serde_json::to_vec(&self.schedules).expect("Failed to serialize schedules");
+        checksum::sha256_reader(&mut json.as_slice()).expect("Failed to hash schedules")
+    }
+    
+    fn save_schedules_hash(&self) {
+        if let Err(e) = fs::write(SCHEDULES_HASH_FILE, self.schedules_hash()) {
+            log::warn!("Failed to write {}: {}", SCHEDULES_HASH_FILE, e);
+        }
+    }
+    
+    /// Compare the schedules with the hash from the last save and warn if they were changed
+    /// outside DriveGuard. The new state is accepted afterwards so the warning shows once.
+    fn check_schedules_hash(&self) {
+        let saved = match fs::read_to_string(SCHEDULES_HASH_FILE) {
+            Ok(saved) => saved,
+            Err(_) => {
+                log::info!("No {} yet, recording the current schedules", SCHEDULES_HASH_FILE);
+                self.save_schedules_hash();
+                return;
+            }
+        };
+        
+        if saved.trim() != self.schedules_hash() {
+            crate::status::notify_localized("DriveGuard", "config_changed_externally", &[CONFIG_FILE], true);
+            self.save_schedules_hash();
+        }
     }
     
     pub fn add_schedule(&mut self, schedule: BackupSchedule) {
@@ -150,31 +475,213 @@ impl AppConfig {
         }
     }
     
-    pub fn check_scheduled_backups(&self) {
-        let now = Utc::now();
-        
-        for schedule in &self.schedules {
-            if !schedule.enabled || !schedule.trigger_on_schedule {
-                continue;
-            }
-            
-            let should_backup = if let Some(last_backup_str) = &schedule.last_backup {
-                if let Ok(last_backup) = DateTime::parse_from_rfc3339(last_backup_str) {
-                    let elapsed = now.signed_duration_since(last_backup);
-                    elapsed >= Duration::days(schedule.interval_days as i64)
-                } else {
-                    true
-                }
-            } else {
-                true // Never backed up before
-            };
-            
-            if should_backup {
-                log::info!("Schedule '{}' is due for backup", schedule.name);
-                // TODO: Trigger backup countdown window
-            }
+    /// Remember that the user OK'd the summary before `schedule_id`'s first backup
+    pub fn confirm_first_backup(&mut self, schedule_id: &str) {
+        if let Some(schedule) = self.schedules.iter_mut().find(|s| s.id == schedule_id) {
+            schedule.first_backup_confirmed = true;
+            self.save();
         }
     }
+    
+    /// Save the tray's Backups Enabled toggle, if remember_backups_off asks for it to survive a restart
+    pub fn remember_backups_enabled(&mut self, enabled: bool) {
+        if self.general.remember_backups_off && self.general.backups_enabled != enabled {
+            self.general.backups_enabled = enabled;
+            self.save();
+        }
+    }
+    
+    /// Remember when updates were last checked, so check_frequency_days is honoured across restarts,
+    /// and how each source answered
+    pub fn record_update_check(&mut self, source_status: &BTreeMap<String, SourceStatus>) {
+        let update_settings = self.general.update_settings.get_or_insert_with(Default::default);
+        update_settings.last_check = Some(Utc::now().to_rfc3339());
+        update_settings.source_status = source_status.clone();
+        self.save();
+    }
+    
+    /// Don't offer `version` again
+    pub fn skip_version(&mut self, version: &str) {
+        let update_settings = self.general.update_settings.get_or_insert_with(Default::default);
+        if !update_settings.skipped_versions.iter().any(|v| v == version) {
+            update_settings.skipped_versions.push(version.to_string());
+            self.save();
+        }
+    }
+    
+    /// Forget that the user skipped `version` so it can be offered again
+    pub fn unskip_version(&mut self, version: &str) {
+        if let Some(ref mut update_settings) = self.general.update_settings {
+            update_settings.skipped_versions.retain(|v| v != version);
+            self.save();
+        }
+    }
+    
+    /// Remember the answer to the usage report prompt
+    pub fn set_telemetry_consent(&mut self, consent: bool) {
+        self.general.update_settings.get_or_insert_with(Default::default).telemetry_consent = Some(consent);
+        self.save();
+    }
+    
+    /// Enabled schedules with `trigger_on_schedule` that are due at `now`, copied so the
+    /// caller can run them without holding the config lock
+    pub fn due_scheduled_backups(&self, now: DateTime<Utc>) -> Vec<BackupSchedule> {
+        self.schedules
+            .iter()
+            .filter(|s| s.enabled && s.trigger_on_schedule && s.is_due(now))
+            .cloned()
+            .collect()
+    }
+}
+
+impl GeneralSettings {
+    /// Threads a backup copies with; `copy_workers = 0` picks one per processor core
+    pub fn copy_workers(&self) -> usize {
+        match self.copy_workers {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()).min(DEFAULT_MAX_COPY_WORKERS),
+            workers => workers.min(MAX_COPY_WORKERS),
+        }
+    }
+    
+    /// None when the check for pending source changes is off
+    pub fn change_check_interval(&self) -> Option<std::time::Duration> {
+        (self.change_check_minutes != 0).then(|| std::time::Duration::from_secs(self.change_check_minutes * 60))
+    }
+    
+    pub fn monitor_poll_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.monitor_poll_seconds)
+    }
+    
+    pub fn schedule_poll_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.schedule_poll_seconds)
+    }
+    
+    /// Letters from `ignored_drives`, uppercase; entries that don't parse are left out
+    pub fn ignored_drive_letters(&self) -> Vec<char> {
+        self.ignored_drives.iter().filter_map(|entry| parse_drive_letters(entry)).flatten().collect()
+    }
+}
+
+/// "d", "D:", "D:\\" or a range like "M-Z"
+fn parse_drive_letters(entry: &str) -> Option<Vec<char>> {
+    let letter = |s: &str| {
+        let s = s.trim().trim_end_matches('\\').trim_end_matches(':');
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_alphabetic() => Some(c.to_ascii_uppercase()),
+            _ => None,
+        }
+    };
+    
+    match entry.split_once('-') {
+        Some((first, last)) => {
+            let (first, last) = (letter(first)?, letter(last)?);
+            (first <= last).then(|| (first..=last).collect())
+        }
+        None => letter(entry).map(|c| vec![c]),
+    }
+}
+
+/// A volume serial the way `vol` and Explorer show it, e.g. "A1B2-C3D4"
+pub fn format_volume_serial(serial: u32) -> String {
+    format!("{:04X}-{:04X}", serial >> 16, serial & 0xFFFF)
+}
+
+/// A configured serial: decimal ("2712847316"), hyphenated hex as `vol` shows it ("A1B2-C3D4")
+/// or "0x"-prefixed hex
+pub fn parse_volume_serial(text: &str) -> Option<u32> {
+    let text = text.trim();
+    let hex = |s: &str| {
+        (!s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit())).then(|| u32::from_str_radix(s, 16).ok()).flatten()
+    };
+    
+    if let Some((high, low)) = text.split_once('-') {
+        return (high.len() == 4 && low.len() == 4).then(|| hex(&format!("{}{}", high, low))).flatten();
+    }
+    if let Some(digits) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        return hex(digits);
+    }
+    text.chars().all(|c| c.is_ascii_digit()).then(|| text.parse().ok()).flatten()
+}
+
+/// Folder containing driveguard.exe; relative destinations are resolved against it
+pub fn app_dir() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// DriveGuard's own settings, schedules, downloaded updates and executables, which backups leave out
+pub fn own_paths() -> Vec<PathBuf> {
+    let dir = app_dir();
+    let mut paths: Vec<PathBuf> = [CONFIG_FILE, SCHEDULES_HASH_FILE, SCHEDULES_DIR, update_state::UPDATES_DIR, "driveguard.exe", "updater.exe"]
+        .iter()
+        .map(|name| dir.join(name))
+        .collect();
+    
+    // In %LOCALAPPDATA% when the install folder is write-protected
+    let updates = update_state::updates_dir();
+    paths.push(if updates.is_absolute() { updates } else { dir.join(updates) });
+    paths
+}
+
+/// `path` is DriveGuard's folder, inside it or contains it
+pub fn overlaps_app_dir(path: &str) -> bool {
+    let path = normalize_destination(path);
+    let own = normalize_destination(&app_dir().to_string_lossy());
+    path == own || path.starts_with(&format!("{}\\", own)) || own.starts_with(&format!("{}\\", path))
+}
+
+/// First `time` on one of `days` (any day if empty) strictly after `after`. A time skipped by a
+/// daylight saving change runs an hour later that day.
+fn next_slot<Tz: TimeZone>(after: &DateTime<Tz>, time: NaiveTime, days: &[Weekday]) -> Option<DateTime<Tz>> {
+    let timezone = after.timezone();
+    let first_day = after.date_naive();
+    
+    (0..=7)
+        .filter_map(|offset| first_day.checked_add_signed(Duration::days(offset)))
+        .filter(|day| days.is_empty() || days.contains(&day.weekday()))
+        .filter_map(|day| {
+            let local = day.and_time(time);
+            timezone.from_local_datetime(&local).earliest()
+                .or_else(|| timezone.from_local_datetime(&(local + Duration::hours(1))).earliest())
+        })
+        .find(|slot| slot > after)
+}
+
+/// `E:\...`, `\\server\share` or anything the platform itself calls absolute
+fn is_absolute_destination(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    let drive_absolute = bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'\\' || bytes[2] == b'/');
+    
+    drive_absolute || path.starts_with("\\\\") || Path::new(path).is_absolute()
+}
+
+fn resolve_destination(destination: &str, base: &Path) -> PathBuf {
+    let destination = destination.trim();
+    if is_absolute_destination(destination) {
+        PathBuf::from(destination)
+    } else {
+        base.join(destination)
+    }
+}
+
+/// Compare destinations the way Windows does: case-insensitive, either slash, no trailing slash
+fn normalize_destination(path: &str) -> String {
+    path.trim().replace('/', "\\").trim_end_matches('\\').to_lowercase()
+}
+
+fn clamp_setting(name: &str, value: u64, range: RangeInclusive<u64>) -> u64 {
+    let clamped = value.clamp(*range.start(), *range.end());
+    if clamped != value {
+        log::warn!("{} = {} is out of range ({}..={}), using {}",
+                   name, value, range.start(), range.end(), clamped);
+    }
+    clamped
 }
 
 impl BackupSchedule {
@@ -191,27 +698,255 @@ impl BackupSchedule {
             name,
             enabled: true,
             drive_serial: None,
+            drive_serials: Vec::new(),
+            drive_label: None,
             drive_id_file: true,
+            match_by: DriveMatchKind::VolumeSerial,
             source_paths: Vec::new(),
+            backup_list_source: None,
             destination_path: String::new(),
+            destinations: Vec::new(),
             interval_days: 7,
+            time_of_day: None,
+            days_of_week: Vec::new(),
             last_backup: None,
+            first_backup_confirmed: false,
+            backup_mode: BackupMode::Full,
+            dedup_identical_files: false,
+            eject_after_backup: false,
+            diff_report: false,
+            missing_source_policy: MissingSourcePolicy::SkipMissing,
+            skip_hidden: None,
+            skip_system: None,
+            min_file_age_seconds: 0,
+            copy_buffer_kb: default_copy_buffer_kb(),
+            lifecycle: None,
+            compress_logs: false,
+            output_format: OutputFormat::FolderTree,
+            verify_after_copy: false,
+            keep_uncompressed_backups: 0,
+            retention: None,
+            max_files: None,
+            max_depth: None,
+            newest_only: Vec::new(),
+            exclude: Vec::new(),
+            shares_destination: false,
+            notify_on_start: false,
+            notify_on_success: false,
+            notify_on_failure: true,
             trigger_on_connect: true,
             trigger_on_schedule: false,
             countdown_minutes: 5,
         }
     }
     
+    /// `time_of_day` and `days_of_week`, if a time of day is set
+    pub fn time_spec(&self) -> Result<Option<(NaiveTime, Vec<Weekday>)>, String> {
+        let time = match &self.time_of_day {
+            Some(time) if !time.trim().is_empty() => NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|_| format!("time_of_day \"{}\" is not a time like \"02:00\"", time))?,
+            _ => return Ok(None),
+        };
+        
+        let days = self.days_of_week
+            .iter()
+            .map(|day| day.trim().parse::<Weekday>()
+                .map_err(|_| format!("days_of_week entry \"{}\" is not a day like \"mon\"", day)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some((time, days)))
+    }
+    
+    /// When the next scheduled backup is due: the first `time_of_day` (on one of `days_of_week`)
+    /// after the last backup, otherwise `interval_days` after it. None if it never ran.
+    pub fn next_due(&self) -> Option<DateTime<Utc>> {
+        let last_backup = self.last_backup
+            .as_deref()
+            .and_then(|last| DateTime::parse_from_rfc3339(last).ok())?
+            .with_timezone(&Utc);
+        
+        let slot = match self.time_spec() {
+            Ok(Some((time, days))) => next_slot(&last_backup.with_timezone(&Local), time, &days),
+            _ => None,
+        };
+        Some(match slot {
+            Some(slot) => slot.with_timezone(&Utc),
+            None => last_backup + Duration::days(self.interval_days as i64),
+        })
+    }
+    
+    /// The first backup waits until the user OKs a summary of what goes where; a schedule that
+    /// already has a backup is past that point
+    pub fn needs_first_backup_confirmation(&self) -> bool {
+        !self.first_backup_confirmed && self.last_backup.is_none()
+    }
+    
+    /// Whether the sources changed since the last backup (or there was none); a cheap estimate
+    /// from file and folder times, for the tray
+    pub fn has_pending_changes(&self) -> bool {
+        let last_backup = match self.last_backup.as_deref().and_then(|last| DateTime::parse_from_rfc3339(last).ok()) {
+            Some(last) => std::time::SystemTime::from(last.with_timezone(&Utc)),
+            None => return true,
+        };
+        
+        // The destination isn't connected now; assume the usual removable drive for the hidden/system defaults
+        crate::backup::changed_since(&self.load_backup_list(), last_backup, self.skips_hidden(true),
+                                     self.skips_system(true), &own_paths(), &self.exclude_patterns().unwrap_or_default())
+    }
+    
+    /// Never backed up (or the date is unreadable) counts as due. So does a last backup in the
+    /// future: the clock was wrong when it was saved, and waiting for it would stall the schedule.
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        let last_backup = self.last_backup.as_deref().and_then(|last| DateTime::parse_from_rfc3339(last).ok());
+        if let Some(last_backup) = last_backup {
+            if last_backup > now + Duration::minutes(CLOCK_SKEW_TOLERANCE_MINUTES) {
+                log::warn!("Schedule '{}': last_backup {} is in the future (was the clock changed?), treating it as due",
+                           self.name, last_backup.to_rfc3339());
+                return true;
+            }
+        }
+        
+        match self.next_due() {
+            Some(due) => now >= due,
+            None => true,
+        }
+    }
+    
+    /// "every 7 days" or "at 02:00 on Mon, Fri"
+    pub fn timing_description(&self) -> String {
+        match self.time_spec() {
+            Ok(Some((time, days))) if days.is_empty() => format!("every day at {}", time.format("%H:%M")),
+            Ok(Some((time, days))) => format!(
+                "at {} on {}",
+                time.format("%H:%M"),
+                days.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(", "),
+            ),
+            _ => format!("every {} days", self.interval_days),
+        }
+    }
+    
+    /// The `exclude` globs, ready for matching
+    pub fn exclude_patterns(&self) -> Result<ExcludePatterns, String> {
+        ExcludePatterns::new(&self.exclude)
+    }
+    
+    /// `max_files` and `max_depth` for a backup of `sources` (None = no limit); a source that is
+    /// a whole drive gets conservative limits unless the schedule sets its own
+    pub fn walk_limits(&self, sources: &[String]) -> (Option<usize>, Option<usize>) {
+        let whole_drive = sources.iter().any(|s| Path::new(s.trim()).parent().is_none());
+        let limit = |configured: Option<usize>, whole_drive_default: usize| match configured {
+            Some(0) => None,
+            Some(limit) => Some(limit),
+            None => whole_drive.then_some(whole_drive_default),
+        };
+        (limit(self.max_files, crate::backup::VOLUME_ROOT_MAX_FILES), limit(self.max_depth, crate::backup::VOLUME_ROOT_MAX_DEPTH))
+    }
+    
+    /// `drive_serial` and `drive_serials` combined, trimmed, without blanks or duplicates
+    pub fn accepted_serials(&self) -> Vec<String> {
+        let mut serials: Vec<String> = Vec::new();
+        for serial in self.drive_serial.iter().chain(&self.drive_serials) {
+            let serial = serial.trim();
+            if !serial.is_empty() && !serials.iter().any(|s| s == serial) {
+                serials.push(serial.to_string());
+            }
+        }
+        serials
+    }
+    
+    /// Whether hidden files are left out; defaults to on for removable destinations
+    pub fn skips_hidden(&self, destination_removable: bool) -> bool {
+        self.skip_hidden.unwrap_or(destination_removable)
+    }
+    
+    /// Whether system files are left out; defaults to on for removable destinations
+    pub fn skips_system(&self, destination_removable: bool) -> bool {
+        self.skip_system.unwrap_or(destination_removable)
+    }
+    
+    /// Where backups go: `destination_path`, with a relative path taken as relative to the folder
+    /// containing driveguard.exe rather than whatever the working directory happens to be
+    pub fn destination(&self) -> PathBuf {
+        resolve_destination(&self.destination_path, &app_dir())
+    }
+    
+    /// The folder a backup started at `now` goes in: `destination()` with the tokens of a
+    /// `destination_path` template like `D:\\Backups\\{schedule_name}\\{year}` filled in
+    pub fn destination_folder(&self, now: DateTime<Utc>) -> Result<PathBuf, String> {
+        let path = crate::backup::expand_destination(&self.destination_path, &self.name, now)?;
+        Ok(resolve_destination(&path, &app_dir()))
+    }
+    
+    /// Destination for comparing schedules; backups from templates that differ only by schedule
+    /// name don't mix
+    fn destination_key(&self) -> String {
+        let folder = self.destination_folder(Utc::now()).unwrap_or_else(|_| self.destination());
+        normalize_destination(&folder.to_string_lossy())
+    }
+    
+    /// Whether the schedule picks one of `destinations` at backup time instead of using `destination_path`
+    pub fn rotates_destinations(&self) -> bool {
+        !self.destinations.is_empty()
+    }
+    
+    /// This schedule backing up to `path`, as chosen from its rotating `destinations`
+    pub fn with_destination(&self, path: String) -> BackupSchedule {
+        BackupSchedule {
+            destination_path: path,
+            destinations: Vec::new(),
+            ..self.clone()
+        }
+    }
+    
+    /// Where backups go, for display; rotating schedules list their drives
+    pub fn destination_description(&self) -> String {
+        if !self.rotates_destinations() {
+            return self.destination().display().to_string();
+        }
+        
+        let drives: Vec<String> = self.destinations
+            .iter()
+            .map(|d| {
+                let drive = match (d.drive_serial.as_deref(), d.drive_label.as_deref()) {
+                    (Some(serial), Some(label)) => format!("serial {} or label {}", serial, label),
+                    (Some(serial), None) => format!("serial {}", serial),
+                    (None, Some(label)) => format!("label {}", label),
+                    (None, None) => "no drive".to_string(),
+                };
+                format!("{}\\{}", drive, d.folder.trim_start_matches(['\\', '/']))
+            })
+            .collect();
+        format!("whichever is connected of {}", drives.join(", "))
+    }
+    
+    /// Whether `destination_path` is relative (and so resolved against the exe folder)
+    pub fn has_relative_destination(&self) -> bool {
+        !is_absolute_destination(self.destination_path.trim())
+    }
+    
+    /// Drive letter of the destination path, e.g. 'E' for "E:\\Backups"
+    pub fn destination_drive_letter(&self) -> Option<char> {
+        let destination = self.destination().to_string_lossy().to_string();
+        let mut chars = destination.chars();
+        match (chars.next(), chars.next()) {
+            (Some(letter), Some(':')) if letter.is_ascii_alphabetic() => Some(letter.to_ascii_uppercase()),
+            _ => None,
+        }
+    }
+    
     pub fn load_backup_list(&self) -> Vec<String> {
+        // A central list wins; its cached copy, then the local list, cover being offline
+        if let Some(source) = self.backup_list_source.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            let cache_file = format!("{}/{}_backup_list_cached.txt", SCHEDULES_DIR, self.id);
+            if let Some(paths) = load_remote_backup_list(source, Path::new(&cache_file)) {
+                return paths;
+            }
+            log::warn!("Schedule '{}': no copy of the backup list {} is available, using the local one", self.name, source);
+        }
+        
         let list_file = format!("{}/{}_backup_list.txt", SCHEDULES_DIR, self.id);
         
         if Path::new(&list_file).exists() {
-            fs::read_to_string(&list_file)
-                .unwrap_or_default()
-                .lines()
-                .filter(|line| !line.trim().is_empty() && !line.trim().starts_with('#'))
-                .map(|s| s.to_string())
-                .collect()
+            parse_backup_list(&read_text_file(Path::new(&list_file)).unwrap_or_default())
         } else {
             // Create default backup list file with instructions
             let default_content = r#"# DriveGuard Backup List
@@ -231,4 +966,124 @@ impl BackupSchedule {
         let content = paths.join("\n");
         fs::write(&list_file, content).ok();
     }
-}
\ No newline at end of file
+}
+
+/// Read a text file saved by any Windows editor: UTF-8 with or without a byte order mark, or
+/// UTF-16 ("Unicode" in Notepad), so non-English paths survive whichever encoding was picked
+fn read_text_file(path: &Path) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    
+    let utf16 = |bytes: &[u8], to_unit: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| to_unit([pair[0], pair[1]])).collect();
+        String::from_utf16(&units).map_err(|_| format!("{} is not valid UTF-16", path.display()))
+    };
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        return utf16(rest, u16::from_le_bytes);
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        return utf16(rest, u16::from_be_bytes);
+    }
+    
+    let utf8 = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(&bytes);
+    String::from_utf8(utf8.to_vec()).map_err(|_| format!("{} is not valid UTF-8; save it as UTF-8", path.display()))
+}
+
+/// Paths in a backup list, without blank lines, # comments and repeats of the same folder
+fn parse_backup_list(content: &str) -> Vec<String> {
+    let paths: Vec<String> = content
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim().starts_with('#'))
+        .map(|s| s.to_string())
+        .collect();
+    crate::backup::dedup_sources(&paths)
+}
+
+/// Fetch a central backup list and cache it locally; if that fails, use the last cached copy
+fn load_remote_backup_list(source: &str, cache_file: &Path) -> Option<Vec<String>> {
+    let fetched = fetch_backup_list(source)
+        .and_then(|content| validate_backup_list(&content).map(|paths| (content, paths)));
+    
+    match fetched {
+        Ok((content, paths)) => {
+            if let Err(e) = fs::write(cache_file, &content) {
+                log::warn!("Failed to cache backup list in {}: {}", cache_file.display(), e);
+            }
+            log::info!("Loaded {} path(s) from backup list {}", paths.len(), source);
+            Some(paths)
+        }
+        Err(e) => {
+            log::warn!("Failed to load backup list from {}: {}", source, e);
+            let cached = fs::read_to_string(cache_file).ok()?;
+            log::info!("Using the cached copy of the backup list ({})", cache_file.display());
+            Some(parse_backup_list(&cached))
+        }
+    }
+}
+
+/// Read a backup list from an http(s) URL, or from a UNC (`\\server\share\list.txt`) or other file path
+fn fetch_backup_list(source: &str) -> Result<String, String> {
+    let lower = source.to_ascii_lowercase();
+    if !lower.starts_with("http://") && !lower.starts_with("https://") {
+        return read_text_file(Path::new(source));
+    }
+    
+    let client = reqwest::blocking::Client::builder()
+        .user_agent(format!("DriveGuard/{}", crate::version::VERSION))
+        .timeout(std::time::Duration::from_secs(30))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    client
+        .get(source)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.text())
+        .map_err(|e| e.to_string())
+}
+
+/// Turn down what is more likely a failed fetch than a real list: an HTML page or no paths at all
+fn validate_backup_list(content: &str) -> Result<Vec<String>, String> {
+    if content.trim_start().starts_with('<') {
+        return Err("got an HTML page instead of a backup list".to_string());
+    }
+    
+    let paths = parse_backup_list(content);
+    if paths.is_empty() {
+        return Err("the list contains no paths".to_string());
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_future_last_backup_is_due() {
+        let now = Utc.with_ymd_and_hms(2025, 11, 19, 12, 0, 0).unwrap();
+        let mut schedule = BackupSchedule::new("Weekly".to_string());
+        schedule.trigger_on_schedule = true;
+        
+        schedule.last_backup = Some((now - Duration::days(1)).to_rfc3339());
+        assert!(!schedule.is_due(now));
+        
+        // Saved while the clock was a year ahead; must not wait until then
+        schedule.last_backup = Some((now + Duration::days(365)).to_rfc3339());
+        assert!(schedule.is_due(now));
+        
+        // A few minutes of skew between machines is not worth a backup
+        schedule.last_backup = Some((now + Duration::minutes(2)).to_rfc3339());
+        assert!(!schedule.is_due(now));
+        
+        // The scheduler uses the same check
+        schedule.time_of_day = Some("02:00".to_string());
+        schedule.last_backup = Some((now + Duration::days(30)).to_rfc3339());
+        let config = AppConfig { schedules: vec![schedule], ..AppConfig::default() };
+        assert_eq!(config.due_scheduled_backups(now).len(), 1);
+    }
+    
+    #[test]
+    fn test_backup_list_drops_repeated_sources() {
+        let paths = parse_backup_list("# Work\nC:\\Data\nC:\\Data\\\n\nc:\\DATA\nD:\\Photos\n");
+        assert_eq!(paths, vec!["C:\\Data".to_string(), "D:\\Photos".to_string()]);
+    }
+}
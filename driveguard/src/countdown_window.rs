@@ -1,10 +1,42 @@
 use native_windows_gui as nwg;
 use std::sync::{Arc, Mutex};
-use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::cell::{Cell, RefCell};
 use std::thread;
-use std::time::Duration;
-use crate::config::BackupSchedule;
-use crate::backup::BackupEngine;
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use crate::config::{AppConfig, BackupSchedule, CountdownStyle, GeneralSettings};
+use crate::backup_report::{BackupReportWindow, FailureReport};
+use crate::backup_history::{BackupHistory, HistoryEntry};
+use crate::backup::{
+    BackupEngine, BackupMode, BackupProgress, BackupSummary, DestinationProblem, ProgressCallback, RetentionPolicy,
+    FAT32_MAX_FILE_SIZE,
+};
+use crate::drive_monitor::{self, DriveInfo, DriveMonitor};
+use crate::drive_state::DriveStates;
+use crate::status::{self, AppStatus, CountdownAction};
+use crate::progress_bar::{format_bytes, format_count, format_duration, shorten_path};
+
+// Snooze choices offered in the countdown window, in minutes; the second is preselected
+const SNOOZE_MINUTES: [i64; 4] = [15, 60, 240, 480];
+
+// Steps of the backup progress bar, finer than percent so large backups still visibly move
+const PROGRESS_STEPS: usize = 1000;
+
+// Longer backup folder paths are shortened in balloons, which only fit a few lines
+const BALLOON_PATH_CHARS: usize = 60;
+
+// Only one countdown window is shown at a time
+static COUNTDOWN_OPEN: AtomicBool = AtomicBool::new(false);
+
+/// Clears COUNTDOWN_OPEN when the countdown thread ends, however it ends
+struct CountdownOpenGuard;
+
+impl Drop for CountdownOpenGuard {
+    fn drop(&mut self) {
+        COUNTDOWN_OPEN.store(false, Ordering::SeqCst);
+    }
+}
 
 pub struct CountdownWindow {
     window: nwg::Window,
@@ -12,26 +44,61 @@ pub struct CountdownWindow {
     label_title: nwg::Label,
     label_countdown: nwg::Label,
     label_warning: nwg::Label,
+    progress_bar: nwg::ProgressBar,
+    progress_counting: Cell<bool>, // Marquee while the files are counted, before there's a total
     
     btn_start_now: nwg::Button,
     btn_hide: nwg::Button,
     btn_cancel: nwg::Button,
+    combo_snooze: nwg::ComboBox<String>,
+    btn_snooze: nwg::Button,
     
     timer: nwg::AnimationTimer,
     
-    schedule: Arc<Mutex<BackupSchedule>>,
+    schedules: Arc<Mutex<Vec<BackupSchedule>>>, // All schedules due for this drive, run in order
+    drive_letter: char,
+    drive_key: String, // Identity used for per-drive backup history
     seconds_remaining: Arc<Mutex<u64>>,
-    cancelled: Arc<Mutex<bool>>,
+    cancel: Arc<AtomicBool>, // Stops the countdown, or the running backup before its next file
+    backup: RefCell<Option<thread::JoinHandle<BatchResult>>>, // The running backup; the timer waits for it
+    config: Arc<Mutex<AppConfig>>,
     
     handler: RefCell<Option<nwg::EventHandler>>,
 }
 
 impl CountdownWindow {
-    pub fn show(schedule: BackupSchedule, drive_letter: char) {
-        log::info!("CountdownWindow::show called for drive {}", drive_letter);
+    pub fn show(schedules: Vec<BackupSchedule>, drive: DriveInfo, style: CountdownStyle, config: Arc<Mutex<AppConfig>>) {
+        let drive_letter = drive.letter;
+        let drive_key = drive.state_key();
+        log::info!("CountdownWindow::show called for drive {} with {} schedule(s)", drive_letter, schedules.len());
+        
+        if schedules.is_empty() {
+            return;
+        }
+        
+        // Without a desktop there is nobody to show a countdown to, so back up right away
+        if status::is_headless() {
+            thread::spawn(move || run_headless(&schedules, &drive, &config));
+            return;
+        }
+        
+        if COUNTDOWN_OPEN.swap(true, Ordering::SeqCst) {
+            log::info!("A countdown window is already open, skipping drive {}", drive_letter);
+            return;
+        }
+        
+        if style == CountdownStyle::Tray {
+            thread::spawn(move || {
+                let _open_guard = CountdownOpenGuard;
+                run_tray_countdown(&schedules, &drive, &config);
+            });
+            return;
+        }
+        
         log::info!("Creating countdown window for drive {}", drive_letter);
         
         thread::spawn(move || {
+            let _open_guard = CountdownOpenGuard;
             log::info!("Countdown window thread started for drive {}", drive_letter);
             
             if let Err(e) = nwg::init() {
@@ -41,14 +108,19 @@ impl CountdownWindow {
             
             log::info!("NWG initialized in countdown thread");
             
-            let seconds = schedule.countdown_minutes * 60;
-            let schedule = Arc::new(Mutex::new(schedule));
+            // The most impatient schedule decides when the batch starts
+            let seconds = schedules.iter().map(|s| s.countdown_minutes).min().unwrap_or(0) * 60;
+            let schedule_names: Vec<&str> = schedules.iter().map(|s| s.name.as_str()).collect();
+            let title_text = format!("{}\n{}",
+                crate::localization::tf("backup_starting", &[&drive_letter.to_string()]),
+                schedule_names.join(", "));
+            let schedules = Arc::new(Mutex::new(schedules));
             let seconds_remaining = Arc::new(Mutex::new(seconds));
-            let cancelled = Arc::new(Mutex::new(false));
+            let cancel = Arc::new(AtomicBool::new(false));
             
             let mut window = Default::default();
             if let Err(e) = nwg::Window::builder()
-                .size((500, 250))
+                .size((500, 320))
                 .position((300, 300))
                 .title("DriveGuard - Backup Starting")
                 .flags(nwg::WindowFlags::WINDOW | nwg::WindowFlags::VISIBLE)
@@ -61,10 +133,10 @@ impl CountdownWindow {
             
             let mut label_title = Default::default();
             nwg::Label::builder()
-                .text(&crate::localization::tf("backup_starting", &[&drive_letter.to_string()]))
+                .text(&title_text)
                 .parent(&window)
-                .position((20, 20))
-                .size((460, 30))
+                .position((20, 10))
+                .size((460, 45))
                 .build(&mut label_title)
                 .expect("Failed to build title label");
             
@@ -82,16 +154,27 @@ impl CountdownWindow {
             nwg::Label::builder()
                 .text(&crate::localization::t("do_not_disconnect"))
                 .parent(&window)
-                .position((20, 110))
+                .position((20, 140))
                 .size((460, 40))
                 .build(&mut label_warning)
                 .expect("Failed to build warning label");
             
+            // Shown once the backup starts
+            let mut progress_bar = Default::default();
+            nwg::ProgressBar::builder()
+                .flags(nwg::ProgressBarFlags::empty())
+                .range(0..PROGRESS_STEPS as u32)
+                .parent(&window)
+                .position((20, 105))
+                .size((460, 22))
+                .build(&mut progress_bar)
+                .expect("Failed to build progress bar");
+            
             let mut btn_start_now = Default::default();
             nwg::Button::builder()
                 .text(&crate::localization::t("button_start_now"))
                 .parent(&window)
-                .position((20, 170))
+                .position((20, 200))
                 .size((140, 40))
                 .build(&mut btn_start_now)
                 .expect("Failed to build start button");
@@ -100,7 +183,7 @@ impl CountdownWindow {
             nwg::Button::builder()
                 .text(&crate::localization::t("button_hide"))
                 .parent(&window)
-                .position((180, 170))
+                .position((180, 200))
                 .size((140, 40))
                 .build(&mut btn_hide)
                 .expect("Failed to build hide button");
@@ -109,11 +192,31 @@ impl CountdownWindow {
             nwg::Button::builder()
                 .text(&crate::localization::t("button_cancel"))
                 .parent(&window)
-                .position((340, 170))
+                .position((340, 200))
                 .size((140, 40))
                 .build(&mut btn_cancel)
                 .expect("Failed to build cancel button");
             
+            let snooze_choices: Vec<String> = SNOOZE_MINUTES.iter().map(|&m| snooze_label(m)).collect();
+            let mut combo_snooze = Default::default();
+            nwg::ComboBox::builder()
+                .collection(snooze_choices)
+                .selected_index(Some(1))
+                .parent(&window)
+                .position((180, 258))
+                .size((140, 30))
+                .build(&mut combo_snooze)
+                .expect("Failed to build snooze duration box");
+            
+            let mut btn_snooze = Default::default();
+            nwg::Button::builder()
+                .text(&crate::localization::t("button_snooze"))
+                .parent(&window)
+                .position((340, 255))
+                .size((140, 40))
+                .build(&mut btn_snooze)
+                .expect("Failed to build snooze button");
+            
             let mut timer = Default::default();
             nwg::AnimationTimer::builder()
                 .parent(&window)
@@ -126,13 +229,21 @@ impl CountdownWindow {
                 label_title,
                 label_countdown,
                 label_warning,
+                progress_bar,
+                progress_counting: Cell::new(false),
                 btn_start_now,
                 btn_hide,
                 btn_cancel,
+                combo_snooze,
+                btn_snooze,
                 timer,
-                schedule,
+                schedules,
+                drive_letter,
+                drive_key,
                 seconds_remaining,
-                cancelled,
+                cancel,
+                backup: RefCell::new(None),
+                config,
                 handler: RefCell::new(None),
             };
             
@@ -140,10 +251,13 @@ impl CountdownWindow {
             
             // Setup event handlers
             let app_clone = app.clone();
-            let handler = nwg::full_bind_event_handler(&app.window.handle, move |evt, _evt_data, handle| {
+            let handler = nwg::full_bind_event_handler(&app.window.handle, move |evt, evt_data, handle| {
                 use nwg::Event;
                 
-                if handle == app_clone.timer {
+                // Keys reach whichever control has the focus
+                if let Event::OnKeyPress = evt {
+                    app_clone.on_key_press(evt_data.on_key());
+                } else if handle == app_clone.timer {
                     if let Event::OnTimerTick = evt {
                         app_clone.on_timer_tick();
                     }
@@ -159,8 +273,18 @@ impl CountdownWindow {
                     if let Event::OnButtonClick = evt {
                         app_clone.cancel_backup();
                     }
+                } else if handle == app_clone.btn_snooze {
+                    if let Event::OnButtonClick = evt {
+                        app_clone.snooze_backup();
+                    }
                 } else if handle == app_clone.window {
                     if let Event::OnWindowClose = evt {
+                        // Closing mid-backup cancels it; the window stays open to say so
+                        if app_clone.is_backing_up() {
+                            if let nwg::EventData::OnWindowClose(close) = &evt_data {
+                                close.close(false);
+                            }
+                        }
                         app_clone.cancel_backup();
                     }
                 }
@@ -176,6 +300,12 @@ impl CountdownWindow {
     }
     
     fn on_timer_tick(&self) {
+        if self.is_backing_up() {
+            self.update_progress();
+            self.check_backup();
+            return;
+        }
+        
         let mut seconds = self.seconds_remaining.lock().unwrap();
         
         if *seconds > 0 {
@@ -191,51 +321,149 @@ impl CountdownWindow {
     }
     
     fn start_backup_now(&self) {
+        if self.is_backing_up() {
+            return;
+        }
         log::info!("Starting backup now!");
-        self.timer.stop();
         
-        let schedule = self.schedule.lock().unwrap().clone();
+        let schedules = self.schedules.lock().unwrap().clone();
         self.label_countdown.set_text("Backup in progress...");
         self.btn_start_now.set_enabled(false);
-        self.btn_cancel.set_enabled(false);
+        self.btn_cancel.set_enabled(true);
+        self.btn_snooze.set_enabled(false);
+        self.progress_bar.set_pos(0);
+        self.set_progress_counting(true);
+        self.progress_bar.set_visible(true);
         
-        // Run backup
-        let result = self.run_backup(&schedule);
+        // The backup gets its own thread so Cancel stays clickable; each timer tick checks whether it's done
+        self.cancel.store(false, Ordering::SeqCst);
+        let (drive_key, config, cancel) = (self.drive_key.clone(), Arc::clone(&self.config), Arc::clone(&self.cancel));
+        *self.backup.borrow_mut() = Some(thread::spawn(move || run_schedules(&schedules, &drive_key, &config, Some(&cancel))));
+        self.timer.start();
+    }
+    
+    /// Move the bar to the share of files done; the tray status has the engine's latest counts.
+    /// Each schedule of a batch fills it anew.
+    fn update_progress(&self) {
+        let (processed, total) = match status::get() {
+            AppStatus::BackingUp { processed, total, .. } => (processed, total),
+            _ => return,
+        };
         
-        match result {
-            Ok(backup_folder) => {
-                log::info!("Backup completed successfully to: {}", backup_folder);
-                nwg::modal_info_message(&self.window, "Backup Complete", 
-                    &format!("Backup completed successfully!\n\nSaved to:\n{}", backup_folder));
-            }
-            Err(e) => {
-                log::error!("Backup failed: {}", e);
-                nwg::modal_error_message(&self.window, "Backup Failed", 
-                    &format!("Backup failed:\n\n{}", e));
-            }
+        // Until the files are counted there's nothing to measure against
+        self.set_progress_counting(total == 0);
+        if let Some(steps) = (processed.min(total) * PROGRESS_STEPS).checked_div(total) {
+            self.progress_bar.set_pos(steps as u32);
+        }
+    }
+    
+    fn set_progress_counting(&self, counting: bool) {
+        if self.progress_counting.replace(counting) == counting {
+            return;
         }
         
-        nwg::stop_thread_dispatch();
+        if counting {
+            self.progress_bar.add_flags(nwg::ProgressBarFlags::MARQUEE);
+            self.progress_bar.set_marquee(true, 30);
+        } else {
+            self.progress_bar.set_marquee(false, 0);
+            self.progress_bar.remove_flags(nwg::ProgressBarFlags::MARQUEE);
+        }
     }
     
-    fn run_backup(&self, schedule: &BackupSchedule) -> Result<String, String> {
-        let mut engine = BackupEngine::new();
+    fn is_backing_up(&self) -> bool {
+        self.backup.borrow().is_some()
+    }
+    
+    /// Report the backup once its thread is done
+    fn check_backup(&self) {
+        if !self.backup.borrow().as_ref().is_some_and(|backup| backup.is_finished()) {
+            return;
+        }
+        
+        // No ticks while the result dialogs are up, they'd start the countdown's backup again
+        self.timer.stop();
+        let backup = self.backup.borrow_mut().take();
+        let result = match backup.map(thread::JoinHandle::join) {
+            Some(Ok(result)) => result,
+            _ => {
+                log::error!("The backup thread panicked");
+                status::set(AppStatus::BackupFailed("The backup stopped unexpectedly".to_string()));
+                nwg::modal_error_message(&self.window, "Backup Failed", "The backup stopped unexpectedly, see the log for details.");
+                nwg::stop_thread_dispatch();
+                return;
+            }
+        };
+        
+        // Back to the countdown's choices; schedules that finished before the cancel aren't run again
+        if result.cancelled {
+            self.schedules.lock().unwrap().retain(|s| !result.completed.contains(&s.id));
+            self.progress_bar.set_visible(false);
+            self.label_countdown.set_text(&crate::localization::t("backup_cancelled"));
+            self.btn_start_now.set_enabled(true);
+            self.btn_cancel.set_enabled(true);
+            self.btn_snooze.set_enabled(true);
+            return;
+        }
         
-        // Load backup list
-        let source_paths = schedule.load_backup_list();
+        self.set_progress_counting(false);
+        self.progress_bar.set_pos(PROGRESS_STEPS as u32);
         
-        if source_paths.is_empty() {
-            return Err("No source paths configured in backup list".to_string());
+        let schedules = self.schedules.lock().unwrap().clone();
+        let report = result.failure_report();
+        let BatchResult { message, errors, .. } = result;
+        
+        if !errors.is_empty() {
+            nwg::modal_error_message(&self.window, "Backup Failed",
+                &format!("Backup failed:\n\n{}", message.trim_end()));
+        } else if report.is_none() {
+            nwg::modal_info_message(&self.window, "Backup Complete",
+                &format!("Backup completed successfully!\n\n{}", message.trim_end()));
         }
         
-        log::info!("Backing up {} paths to {}", source_paths.len(), schedule.destination_path);
+        // Some files failed: list them instead of a plain success message
+        if let Some(report) = report {
+            BackupReportWindow::show(report);
+        }
         
-        let backup_folder = engine.run_backup(&source_paths, &schedule.destination_path)?;
+        // Only eject once everything for this drive is done
+        if let Some(schedule) = schedules.iter().find(|s| s.eject_after_backup) {
+            if errors.is_empty() {
+                self.eject_destination(schedule);
+            }
+        }
+        
+        nwg::stop_thread_dispatch();
+    }
+    
+    /// Eject the backup drive once the backups are done
+    fn eject_destination(&self, schedule: &BackupSchedule) {
+        let letter = self.drive_letter;
         
-        // Save logs
-        engine.save_logs(&backup_folder).ok();
+        if !should_eject(schedule, letter) {
+            return;
+        }
         
-        Ok(backup_folder)
+        match DriveMonitor::eject_drive(letter) {
+            Ok(()) => {
+                nwg::modal_info_message(&self.window, "DriveGuard",
+                    &crate::localization::tf("drive_safe_to_remove", &[&letter.to_string()]));
+            }
+            Err(e) => {
+                log::warn!("Failed to eject drive {}: {}", letter, e);
+                nwg::modal_error_message(&self.window, "DriveGuard",
+                    &crate::localization::tf("drive_eject_failed", &[&letter.to_string(), &e]));
+            }
+        }
+    }
+    
+    /// Esc cancels (the countdown or the running backup) and Enter starts the backup, unless those buttons are disabled
+    fn on_key_press(&self, key: u32) {
+        match key {
+            nwg::keys::ESCAPE if self.btn_cancel.enabled() => self.cancel_backup(),
+            nwg::keys::RETURN if self.btn_start_now.enabled() => self.start_backup_now(),
+            _ => {}
+        }
     }
     
     fn hide_window(&self) {
@@ -244,8 +472,31 @@ impl CountdownWindow {
     }
     
     fn cancel_backup(&self) {
+        // A running backup stops before its next file, then the timer reports it
+        if self.is_backing_up() {
+            log::info!("Cancelling the running backup");
+            self.cancel.store(true, Ordering::SeqCst);
+            self.btn_cancel.set_enabled(false);
+            self.label_countdown.set_text("Cancelling...");
+            return;
+        }
+        
         log::info!("Backup cancelled by user");
-        *self.cancelled.lock().unwrap() = true;
+        self.cancel.store(true, Ordering::SeqCst);
+        nwg::stop_thread_dispatch();
+    }
+    
+    /// Put this drive's backups off for the chosen time; unlike Cancel they are offered
+    /// again once it's over (right away if the drive is still connected)
+    fn snooze_backup(&self) {
+        let minutes = SNOOZE_MINUTES[self.combo_snooze.selection().unwrap_or(1)];
+        let until = Utc::now() + chrono::Duration::minutes(minutes);
+        log::info!("Backup for drive {} snoozed for {} minutes", self.drive_letter, minutes);
+        
+        for schedule in self.schedules.lock().unwrap().iter() {
+            status::snooze(&self.drive_key, &schedule.id, until);
+        }
+        self.cancel.store(true, Ordering::SeqCst);
         nwg::stop_thread_dispatch();
     }
 }
@@ -257,4 +508,542 @@ impl Drop for CountdownWindow {
             nwg::unbind_event_handler(h);
         }
     }
-}
\ No newline at end of file
+}
+
+fn snooze_label(minutes: i64) -> String {
+    if minutes < 60 {
+        crate::localization::tf("snooze_minutes", &[&minutes.to_string()])
+    } else {
+        crate::localization::tf("snooze_hours", &[&(minutes / 60).to_string()])
+    }
+}
+
+/// Outcome of running every due schedule for one drive
+struct BatchResult {
+    message: String, // Per-schedule report for the user
+    errors: Vec<String>,
+    failed_files: Vec<(String, String)>, // Files that couldn't be copied, across all schedules
+    error_logs: Vec<String>,
+    outcomes: Vec<ScheduleOutcome>,
+    completed: Vec<String>, // Ids of the schedules that succeeded
+    cancelled: bool, // The user stopped the batch; the schedules not in `completed` didn't run (fully)
+}
+
+/// How one schedule of a batch went, for its notification preferences
+struct ScheduleOutcome {
+    succeeded: bool,
+    notify: bool, // notify_on_success / notify_on_failure of the schedule, whichever applies
+    balloon: String,
+}
+
+impl BatchResult {
+    /// Tray balloons for the schedules that asked for one: failures first, then successes
+    fn notify(&self) {
+        let texts = |succeeded: bool| -> Vec<&str> {
+            self.outcomes.iter()
+                .filter(|o| o.notify && o.succeeded == succeeded)
+                .map(|o| o.balloon.trim_end())
+                .collect()
+        };
+        
+        let failed = texts(false);
+        if !failed.is_empty() {
+            status::notify("Backup Failed", &failed.join("\n\n"), true);
+        }
+        
+        let succeeded = texts(true);
+        if !succeeded.is_empty() {
+            status::notify(&crate::localization::t("backup_complete"), &succeeded.join("\n\n"), false);
+        }
+    }
+    
+    fn failure_report(&self) -> Option<FailureReport> {
+        if self.failed_files.is_empty() {
+            return None;
+        }
+        
+        Some(FailureReport {
+            summary: self.message.clone(),
+            failed_files: self.failed_files.clone(),
+            error_logs: self.error_logs.clone(),
+        })
+    }
+}
+
+/// Run each due schedule in turn, keeping the tray status up to date; setting `cancel` stops
+/// the running backup and skips the rest
+fn run_schedules(
+    schedules: &[BackupSchedule],
+    drive_key: &str,
+    config: &Arc<Mutex<AppConfig>>,
+    cancel: Option<&Arc<AtomicBool>>,
+) -> BatchResult {
+    let mut message = String::new();
+    let mut errors = Vec::new();
+    let mut failed_files = Vec::new();
+    let mut error_logs = Vec::new();
+    let mut outcomes = Vec::new();
+    let mut completed = Vec::new();
+    let mut cancelled = false;
+    let cancel_requested = || cancel.is_some_and(|cancel| cancel.load(Ordering::SeqCst));
+    let settings = match config.lock() {
+        Ok(cfg) => cfg.general.clone(),
+        Err(_) => AppConfig::default().general,
+    };
+    
+    for schedule in schedules {
+        if status::is_shutting_down() {
+            log::info!("Shutting down, not starting backup '{}'", schedule.name);
+            break;
+        }
+        if cancel_requested() {
+            log::info!("Backups cancelled, not starting '{}'", schedule.name);
+            cancelled = true;
+            break;
+        }
+        
+        // Nothing is copied anywhere new until the user has seen what and where; headless there
+        // is nobody to ask, and running without a countdown there was a deliberate choice
+        if schedule.needs_first_backup_confirmation() && !status::is_headless() {
+            if !confirm_first_backup(schedule) {
+                log::info!("First backup of '{}' was not confirmed, skipping it", schedule.name);
+                message.push_str(&format!("{}: not started, the first backup wasn't confirmed\n\n", schedule.name));
+                continue;
+            }
+            if let Ok(mut cfg) = config.lock() {
+                cfg.confirm_first_backup(&schedule.id);
+            }
+        }
+        
+        status::set(AppStatus::BackingUp { processed: 0, total: 0, eta: None });
+        
+        let mut text = String::new();
+        let mut balloon = None;
+        let started = Instant::now();
+        let succeeded = match run_backup(schedule, drive_key, &settings, cancel.cloned()) {
+            Ok(summary) => {
+                log::info!("Backup '{}' completed successfully to: {}", schedule.name, summary.folder);
+                completed.push(schedule.id.clone());
+                // Otherwise the same backup is offered again on every reconnect
+                if let Ok(mut cfg) = config.lock() {
+                    cfg.update_last_backup(&schedule.id);
+                }
+                balloon = Some(success_balloon(&schedule.name, &summary, started.elapsed()));
+                
+                text.push_str(&format!("{}: saved to\n{}\n", schedule.name, summary.folder));
+                if !summary.missing_sources.is_empty() {
+                    text.push_str(&format!("Skipped {} missing source(s):\n{}\n",
+                                           summary.missing_sources.len(), summary.missing_sources.join("\n")));
+                }
+                for (source, e) in &summary.failed_sources {
+                    text.push_str(&format!("Could not back up {}: {}\n", source, e));
+                }
+                if !summary.failed_files.is_empty() {
+                    text.push_str(&format!("{} file(s) could not be copied\n", summary.failed_files.len()));
+                    failed_files.extend(summary.failed_files);
+                    error_logs.extend(summary.error_log);
+                }
+                if summary.oversized_files > 0 {
+                    text.push_str(&format!("{} file(s) over 4 GB were skipped because the drive is formatted as FAT32.\n\
+                                            Reformat it as exFAT or NTFS to back them up (see backup_errors.txt).\n",
+                                           summary.oversized_files));
+                }
+                true
+            }
+            Err(_) if cancel_requested() => {
+                log::info!("Backup '{}' cancelled by user", schedule.name);
+                message.push_str(&format!("{}: {}\n\n", schedule.name, crate::localization::t("backup_cancelled")));
+                cancelled = true;
+                break;
+            }
+            Err(e) => {
+                log::error!("Backup '{}' failed: {}", schedule.name, e);
+                text.push_str(&format!("{}: FAILED\n{}\n", schedule.name, e));
+                errors.push(format!("{}: {}", schedule.name, e));
+                false
+            }
+        };
+        
+        message.push_str(&text);
+        message.push('\n');
+        outcomes.push(ScheduleOutcome {
+            succeeded,
+            notify: if succeeded { schedule.notify_on_success } else { schedule.notify_on_failure },
+            balloon: balloon.unwrap_or(text),
+        });
+    }
+    
+    if errors.is_empty() {
+        status::set(AppStatus::Idle);
+    } else {
+        status::set(AppStatus::BackupFailed(errors.join("\n")));
+    }
+    
+    BatchResult { message, errors, failed_files, error_logs, outcomes, completed, cancelled }
+}
+
+/// "Weekly: 9,800 files copied (3.2 GB) in 4m 12s" with the (shortened) backup folder below
+fn success_balloon(name: &str, summary: &BackupSummary, elapsed: Duration) -> String {
+    let mut balloon = crate::localization::tf("backup_balloon_summary", &[
+        name,
+        &crate::localization::tf("files_copied", &[&format_count(summary.copied_files)]),
+        &format_bytes(summary.copied_bytes),
+        &format_duration(elapsed),
+    ]);
+    balloon.push_str(&format!("\n→ {}", shorten_path(&summary.folder, BALLOON_PATH_CHARS)));
+    if !summary.failed_files.is_empty() {
+        balloon.push_str(&format!("\n{} file(s) could not be copied", summary.failed_files.len()));
+    }
+    balloon
+}
+
+fn run_backup(
+    schedule: &BackupSchedule,
+    drive_key: &str,
+    settings: &GeneralSettings,
+    cancel: Option<Arc<AtomicBool>>,
+) -> Result<BackupSummary, String> {
+    let on_progress = Box::new(|progress: &BackupProgress| {
+        status::set(AppStatus::BackingUp {
+            processed: progress.processed_files,
+            total: progress.expected_files.max(progress.total_files),
+            eta: progress.eta,
+        });
+    });
+    // Counting first gives the tray totals and an ETA
+    run_schedule_backup(schedule, drive_key, true, settings, cancel, on_progress)
+}
+
+/// Back up one schedule, reporting progress to `on_progress`; `prescan` counts the
+/// files first so progress has totals (used by `--run-backup`). A run that would leave less than
+/// `settings.min_free_space_gb` free on the destination doesn't start; setting `cancel` stops it
+/// before the next file. Every run goes into history.json.
+pub fn run_schedule_backup(
+    schedule: &BackupSchedule,
+    drive_key: &str,
+    prescan: bool,
+    settings: &GeneralSettings,
+    cancel: Option<Arc<AtomicBool>>,
+    on_progress: ProgressCallback,
+) -> Result<BackupSummary, String> {
+    let started = Utc::now();
+    let result = backup_schedule(schedule, drive_key, prescan, settings, cancel, on_progress);
+    if result.is_ok() {
+        // Stale until the next check; better unknown than claiming changes are pending
+        status::set_pending_changes(None);
+    }
+    BackupHistory::record(HistoryEntry::new(&schedule.id, started, Utc::now(), &result));
+    result
+}
+
+fn backup_schedule(
+    schedule: &BackupSchedule,
+    drive_key: &str,
+    prescan: bool,
+    settings: &GeneralSettings,
+    cancel: Option<Arc<AtomicBool>>,
+    on_progress: ProgressCallback,
+) -> Result<BackupSummary, String> {
+    // A rotating schedule backs up to whichever of its drives is connected; lifecycle history follows that drive
+    let rotated;
+    let (schedule, drive_key) = if schedule.rotates_destinations() {
+        let (chosen, drive) = drive_monitor::select_destination(schedule, &DriveMonitor::scan_drives(&[]), drive_key)?;
+        rotated = (chosen, drive.state_key());
+        (&rotated.0, rotated.1.as_str())
+    } else {
+        (schedule, drive_key)
+    };
+    
+    // With a lifecycle policy the drive's history decides between full and incremental
+    let mut mode = match schedule.lifecycle {
+        Some(ref policy) => DriveStates::load().get(drive_key, &schedule.id).choose_mode(policy, Utc::now()),
+        None => schedule.backup_mode,
+    };
+    
+    // Incremental runs compare against the newest backup in the destination, which may
+    // belong to another schedule sharing it; a full backup is the only safe choice there
+    if schedule.shares_destination && mode == BackupMode::Incremental {
+        log::warn!("'{}' shares {} with another schedule, running a full backup instead of incremental",
+                   schedule.name, schedule.destination().display());
+        mode = BackupMode::Full;
+    }
+    log::info!("Backup mode for '{}': {:?}", schedule.name, mode);
+    
+    let mut engine = BackupEngine::new();
+    engine.mode = mode;
+    engine.dedup = schedule.dedup_identical_files;
+    engine.copy_buffer_size = schedule.copy_buffer_kb as usize * 1024;
+    engine.min_file_age = Duration::from_secs(schedule.min_file_age_seconds);
+    let removable = schedule.destination_drive_letter().is_some_and(DriveMonitor::is_removable);
+    engine.skip_hidden = schedule.skips_hidden(removable);
+    engine.skip_system = schedule.skips_system(removable);
+    if let Some(file_system) = schedule.destination_drive_letter().and_then(DriveMonitor::file_system) {
+        if drive_monitor::is_fat32(&file_system) {
+            log::info!("Destination is FAT32, files over 4 GB will be skipped");
+            engine.max_file_size = Some(FAT32_MAX_FILE_SIZE);
+        }
+    }
+    engine.missing_source_policy = schedule.missing_source_policy;
+    engine.compress_logs = schedule.compress_logs;
+    engine.output_format = schedule.output_format;
+    engine.verify = schedule.verify_after_copy;
+    engine.prescan = prescan;
+    engine.workers = settings.copy_workers();
+    engine.min_free_space = settings.min_free_space_gb.saturating_mul(1024 * 1024 * 1024);
+    engine.on_progress = Some(on_progress);
+    engine.cancel = cancel;
+    
+    // Load backup list
+    let source_paths = schedule.load_backup_list();
+    
+    if source_paths.is_empty() {
+        return Err("No source paths configured in backup list".to_string());
+    }
+    
+    // Backing up DriveGuard's folder would capture settings and half-downloaded updates
+    for source in source_paths.iter().filter(|s| crate::config::overlaps_app_dir(s)) {
+        log::warn!("Source {} overlaps DriveGuard's own folder; its settings, schedules, updates and executables are left out",
+                   source);
+    }
+    engine.excluded_paths = crate::config::own_paths();
+    (engine.max_files, engine.max_depth) = schedule.walk_limits(&source_paths);
+    engine.newest_only = schedule.newest_only.clone();
+    engine.exclude = schedule.exclude_patterns()?;
+    
+    // Relative destinations are resolved against the exe folder, never the working directory;
+    // a template's {year} and the like become folders that run_backup creates as needed
+    let destination = schedule.destination_folder(Utc::now())?;
+    let destination_path = destination.to_string_lossy().to_string();
+    log::info!("Backing up {} paths to {}", source_paths.len(), destination_path);
+    
+    // A missing folder is more likely a typo than a wish for a new one; don't create it silently
+    let root = crate::backup::template_root(&schedule.destination());
+    let root_path = root.to_string_lossy().to_string();
+    match crate::backup::check_destination(&root) {
+        Ok(()) => {}
+        Err(DestinationProblem::DriveMissing(drive)) => {
+            return Err(format!("Destination drive {} is not connected", drive.display()));
+        }
+        Err(DestinationProblem::FolderMissing) => {
+            if !confirm_create_destination(&root_path) {
+                return Err(format!("Destination folder {} does not exist; create it or correct destination_path in settings.toml",
+                                   root_path));
+            }
+            std::fs::create_dir_all(&root)
+                .map_err(|e| format!("Failed to create destination folder {}: {}", root_path, e))?;
+            log::info!("Created destination folder {}", root_path);
+        }
+        Err(DestinationProblem::NotEnoughSpace { .. }) => {} // Only check_free_space reports this
+    }
+    
+    // Better not to start than to fill the drive up halfway through
+    if let Err(DestinationProblem::NotEnoughSpace { needed, available }) = engine.check_free_space(&source_paths, &destination) {
+        return Err(format!("Not enough free space at {}: this backup needs about {}, {} is free and {} GB should stay free \
+                            (min_free_space_gb in settings.toml). Free up space there or lower min_free_space_gb",
+                           root_path, format_bytes(needed), format_bytes(available), settings.min_free_space_gb));
+    }
+    
+    // Interrupted earlier runs are never used as a baseline; tell the user once about each
+    let incomplete = crate::backup::mark_incomplete_backups(&destination);
+    if !incomplete.is_empty() {
+        let folders: Vec<String> = incomplete.iter().map(|p| p.display().to_string()).collect();
+        status::notify_localized("DriveGuard", "incomplete_backups_found",
+                                 &[&folders.len().to_string(), &folders.join("\n")], true);
+    }
+    
+    // Snapshot the changes before this run becomes the latest backup
+    let diff = if schedule.diff_report {
+        Some(engine.diff_against_previous(&source_paths, &destination_path))
+    } else {
+        None
+    };
+    
+    // Keep the system from sleeping mid-copy (per thread, released when dropped)
+    let _keep_awake = crate::power::KeepAwake::new();
+    let backup_folder = engine.run_backup(&source_paths, &destination_path)?;
+    
+    // Save logs
+    engine.save_logs(&backup_folder).ok();
+    
+    if let Some(diff) = diff {
+        log::info!("Changes since last backup: {}", diff.summary());
+        diff.save(&backup_folder).ok();
+    }
+    
+    // Only a backup without failures may replace older ones
+    if let Some(policy) = schedule.retention {
+        if schedule.shares_destination {
+            log::warn!("Not deleting old backups in {}: other schedules back up there too", destination_path);
+        } else if !engine.failed_files.is_empty() || !engine.failed_sources.is_empty() {
+            log::info!("Not deleting old backups in {}: this backup had failures", destination_path);
+        } else if let Err(e) = engine.prune_old_backups(&destination, policy) {
+            log::warn!("Failed to delete old backups: {}", e);
+        }
+    }
+    
+    if schedule.keep_uncompressed_backups > 0 && schedule.shares_destination {
+        log::warn!("Not compressing old backups in {}: other schedules back up there too", destination_path);
+    } else if schedule.keep_uncompressed_backups > 0 {
+        if let Err(e) = crate::backup::compress_old_backups(&destination, schedule.keep_uncompressed_backups) {
+            log::warn!("Failed to compress old backups: {}", e);
+        }
+    }
+    
+    if schedule.lifecycle.is_some() {
+        let mut states = DriveStates::load();
+        states.entry(drive_key, &schedule.id).record(mode, Utc::now());
+        states.save();
+    }
+    
+    Ok(engine.summary(&backup_folder))
+}
+
+/// Show what a schedule's first backup will copy where and ask for an explicit OK
+fn confirm_first_backup(schedule: &BackupSchedule) -> bool {
+    let sources = schedule.load_backup_list();
+    let removable = schedule.destination_drive_letter().is_some_and(DriveMonitor::is_removable);
+    log::info!("Estimating the size of the first backup of '{}'", schedule.name);
+    let mut engine = BackupEngine::new();
+    engine.skip_hidden = schedule.skips_hidden(removable);
+    engine.skip_system = schedule.skips_system(removable);
+    engine.excluded_paths = crate::config::own_paths();
+    engine.exclude = schedule.exclude_patterns().unwrap_or_default();
+    let (files, bytes) = engine.estimate_size(&sources);
+    let old_backups = match schedule.retention {
+        Some(RetentionPolicy::KeepLast(count)) => {
+            crate::localization::tf("first_backup_old_keep_last", &[&count.max(1).to_string()])
+        }
+        Some(RetentionPolicy::KeepDays(days)) => crate::localization::tf("first_backup_old_keep_days", &[&days.to_string()]),
+        None if schedule.keep_uncompressed_backups > 0 => {
+            crate::localization::tf("first_backup_old_compressed", &[&schedule.keep_uncompressed_backups.to_string()])
+        }
+        None => crate::localization::t("first_backup_old_kept"),
+    };
+    
+    let params = nwg::MessageParams {
+        title: "DriveGuard",
+        content: &crate::localization::tf("first_backup_prompt", &[
+            &schedule.name,
+            &sources.join("\n"),
+            &schedule.destination_description(),
+            &format_count(files),
+            &format_bytes(bytes),
+            &old_backups,
+        ]),
+        buttons: nwg::MessageButtons::OkCancel,
+        icons: nwg::MessageIcons::Question,
+    };
+    matches!(nwg::message(&params), nwg::MessageChoice::Ok)
+}
+
+/// Ask whether to create a missing destination folder; headless there is nobody to ask, so no
+fn confirm_create_destination(destination: &str) -> bool {
+    if status::is_headless() {
+        return false;
+    }
+    
+    let params = nwg::MessageParams {
+        title: "DriveGuard",
+        content: &crate::localization::tf("destination_missing_prompt", &[destination]),
+        buttons: nwg::MessageButtons::YesNo,
+        icons: nwg::MessageIcons::Question,
+    };
+    matches!(nwg::message(&params), nwg::MessageChoice::Yes)
+}
+
+/// Only eject a removable drive, and only when it is where the backup went
+fn should_eject(schedule: &BackupSchedule, letter: char) -> bool {
+    let is_destination = if schedule.rotates_destinations() {
+        DriveMonitor::scan_drives(&[])
+            .iter()
+            .any(|d| d.letter == letter && drive_monitor::rotation_destination(schedule, d).is_some())
+    } else {
+        schedule.destination_drive_letter() == Some(letter)
+    };
+    if !is_destination || !DriveMonitor::is_removable(letter) {
+        log::info!("Not ejecting drive {}: it is not the removable backup destination", letter);
+        return false;
+    }
+    
+    true
+}
+
+/// Count down in the tray tooltip instead of a window; the tray menu can start or cancel it
+fn run_tray_countdown(schedules: &[BackupSchedule], drive: &DriveInfo, config: &Arc<Mutex<AppConfig>>) {
+    let mut seconds = schedules.iter().map(|s| s.countdown_minutes).min().unwrap_or(0) * 60;
+    log::info!("Tray countdown for drive {}: {}s", drive.letter, seconds);
+    
+    // A click left over from an earlier countdown must not decide this one
+    status::take_countdown_action();
+    
+    loop {
+        status::set(AppStatus::CountdownPending { drive: drive.letter, seconds_remaining: seconds });
+        
+        match status::take_countdown_action() {
+            Some(CountdownAction::Cancel) => {
+                log::info!("Backup cancelled by user");
+                status::set(AppStatus::Idle);
+                return;
+            }
+            Some(CountdownAction::StartNow) => break,
+            None if seconds == 0 || status::is_shutting_down() => break,
+            None => {}
+        }
+        
+        thread::sleep(Duration::from_secs(1));
+        seconds -= 1;
+    }
+    
+    log::info!("Starting backup now!");
+    if schedules.iter().any(|s| s.notify_on_start) {
+        status::notify_localized("DriveGuard", "do_not_disconnect", &[], false);
+    }
+    
+    let result = run_schedules(schedules, &drive.state_key(), config, None);
+    result.notify();
+    if let Some(report) = result.failure_report() {
+        BackupReportWindow::show(report);
+    }
+    
+    if !result.errors.is_empty() {
+        return;
+    }
+    
+    if let Some(schedule) = schedules.iter().find(|s| s.eject_after_backup) {
+        if should_eject(schedule, drive.letter) {
+            let letter = drive.letter.to_string();
+            match DriveMonitor::eject_drive(drive.letter) {
+                Ok(()) => {
+                    status::notify_localized("DriveGuard", "drive_safe_to_remove", &[&letter], false);
+                }
+                Err(e) => {
+                    log::warn!("Failed to eject drive {}: {}", drive.letter, e);
+                    status::notify_localized("DriveGuard", "drive_eject_failed", &[&letter, &e], true);
+                }
+            }
+        }
+    }
+}
+
+/// Back up without any windows (no desktop session); results only go to the log
+fn run_headless(schedules: &[BackupSchedule], drive: &DriveInfo, config: &Arc<Mutex<AppConfig>>) {
+    log::info!("Headless mode: starting {} backup(s) for drive {} without countdown", schedules.len(), drive.letter);
+    
+    let BatchResult { message, errors, .. } = run_schedules(schedules, &drive.state_key(), config, None);
+    
+    if errors.is_empty() {
+        log::info!("Backup complete:\n{}", message.trim_end());
+    } else {
+        log::error!("Backup failed:\n{}", message.trim_end());
+        return;
+    }
+    
+    if let Some(schedule) = schedules.iter().find(|s| s.eject_after_backup) {
+        if should_eject(schedule, drive.letter) {
+            match DriveMonitor::eject_drive(drive.letter) {
+                Ok(()) => log::info!("Drive {} ejected, safe to remove", drive.letter),
+                Err(e) => log::warn!("Failed to eject drive {}: {}", drive.letter, e),
+            }
+        }
+    }
+}
@@ -1,13 +1,28 @@
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use chrono::Utc;
+use windows::Win32::Foundation::{CloseHandle, GENERIC_READ, GENERIC_WRITE, HANDLE};
 use windows::Win32::Storage::FileSystem::{
-    GetVolumeInformationW, GetLogicalDrives, GetDriveTypeW,
+    GetVolumeInformationW, GetLogicalDrives, GetDriveTypeW, CreateFileW, FlushFileBuffers,
+    FILE_FLAGS_AND_ATTRIBUTES, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
 };
-use windows::core::PWSTR;
-use crate::config::AppConfig;
+use windows::Win32::System::IO::DeviceIoControl;
+use windows::Win32::System::Ioctl::{
+    FSCTL_DISMOUNT_VOLUME, FSCTL_LOCK_VOLUME, IOCTL_STORAGE_EJECT_MEDIA, IOCTL_STORAGE_MEDIA_REMOVAL,
+    IOCTL_STORAGE_QUERY_PROPERTY, PREVENT_MEDIA_REMOVAL, PropertyStandardQuery, STORAGE_DEVICE_DESCRIPTOR,
+    STORAGE_PROPERTY_QUERY, StorageDeviceProperty,
+};
+use windows::core::{PCWSTR, PWSTR};
+use crate::config::{self, AppConfig, BackupSchedule, DriveMatchKind, RotationDestination};
+use crate::status;
 
 const DRIVE_ID_FILE: &str = ".driveGuardID";
+const DRIVE_REMOVABLE: u32 = 2;
+const EJECT_LOCK_RETRIES: u32 = 5;
 
 #[derive(Debug, Clone)]
 pub struct DriveInfo {
@@ -15,61 +30,398 @@ pub struct DriveInfo {
     pub serial: Option<u32>,
     pub has_id_file: bool,
     pub id_content: Option<String>,
+    pub file_system: Option<String>, // "NTFS", "FAT32", "exFAT", ...
+    pub label: Option<String>, // Volume label, if set
+    pub hardware_serial: Option<String>, // Reported by the device itself; None if it reports none
+}
+
+/// Fields read with GetVolumeInformationW
+#[derive(Debug, Default)]
+struct VolumeInfo {
+    serial: Option<u32>,
+    label: Option<String>,
+    file_system: Option<String>,
+}
+
+impl DriveInfo {
+    pub fn is_fat32(&self) -> bool {
+        self.file_system.as_deref().is_some_and(is_fat32)
+    }
+    
+    /// Stable identity for per-drive state: volume serial, else ID file content, else letter
+    pub fn state_key(&self) -> String {
+        if let Some(serial) = self.serial {
+            format!("serial:{}", serial)
+        } else if let Some(ref id) = self.id_content {
+            format!("id:{}", id.trim())
+        } else {
+            format!("letter:{}", self.letter)
+        }
+    }
+}
+
+/// Outcome of checking a schedule's drive criteria against a drive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchResult {
+    MatchedBySerial(u32),
+    SerialMismatch { expected: Vec<String>, found: u32 },
+    NoSerial { expected: Vec<String> }, // The schedule wants a serial but the drive reports none
+    MatchedByHardwareSerial(String),
+    HardwareSerialMismatch { expected: Vec<String>, found: String },
+    NoHardwareSerial { expected: Vec<String> },
+    MatchedByLabel(String),
+    LabelMismatch { pattern: String, found: Option<String> },
+    MatchedByIdFile(Option<String>), // ID file content, if readable
+    NoIdFile,
+    NoCriteria, // No serials, no label pattern and drive_id_file is off
+}
+
+impl MatchResult {
+    pub fn is_match(&self) -> bool {
+        matches!(self, MatchResult::MatchedBySerial(_) | MatchResult::MatchedByHardwareSerial(_)
+                     | MatchResult::MatchedByLabel(_) | MatchResult::MatchedByIdFile(_))
+    }
+}
+
+impl std::fmt::Display for MatchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatchResult::MatchedBySerial(serial) => write!(f, "serial {} matches", config::format_volume_serial(*serial)),
+            MatchResult::SerialMismatch { expected, found } => write!(f, "serial {} ({}) is not one of {}",
+                                                                      config::format_volume_serial(*found), found, expected.join(", ")),
+            MatchResult::NoSerial { expected } => write!(f, "drive has no serial number (expected {})", expected.join(", ")),
+            MatchResult::MatchedByHardwareSerial(serial) => write!(f, "hardware serial {} matches", serial),
+            MatchResult::HardwareSerialMismatch { expected, found } => write!(f, "hardware serial {} is not one of {}",
+                                                                              found, expected.join(", ")),
+            MatchResult::NoHardwareSerial { expected } => write!(f, "device reports no hardware serial (expected {})",
+                                                                 expected.join(", ")),
+            MatchResult::MatchedByLabel(label) => write!(f, "label \"{}\" matches", label),
+            MatchResult::LabelMismatch { pattern, found: Some(label) } => write!(f, "label \"{}\" doesn't match \"{}\"", label, pattern),
+            MatchResult::LabelMismatch { pattern, found: None } => write!(f, "drive has no label (expected \"{}\")", pattern),
+            MatchResult::MatchedByIdFile(content) => write!(f, "{} file found (content: \"{}\")",
+                                                            DRIVE_ID_FILE, content.as_deref().map(str::trim).unwrap_or_default()),
+            MatchResult::NoIdFile => write!(f, "no {} file on the drive", DRIVE_ID_FILE),
+            MatchResult::NoCriteria => write!(f, "schedule has no drive serials or label and drive_id_file is off"),
+        }
+    }
+}
+
+/// Evaluate a schedule's drive criteria against a drive. Any of the schedule's serials (volume or
+/// hardware, per `match_by`) or a matching label accepts the drive; only when neither is configured
+/// does `drive_id_file` require the ID file to be present. `match_by = "id_file"` only looks at the file.
+/// Ignores `enabled` and the triggers; callers decide whether those matter.
+pub fn drive_matches(schedule: &BackupSchedule, info: &DriveInfo) -> MatchResult {
+    let serials = schedule.accepted_serials();
+    let label_pattern = schedule.drive_label.as_deref().map(str::trim).filter(|p| !p.is_empty());
+    
+    let serial_result = if serials.is_empty() {
+        None
+    } else {
+        match schedule.match_by {
+            DriveMatchKind::VolumeSerial => Some(match info.serial {
+                Some(serial) if serials.iter().any(|s| config::parse_volume_serial(s) == Some(serial)) => return MatchResult::MatchedBySerial(serial),
+                Some(serial) => MatchResult::SerialMismatch { expected: serials, found: serial },
+                None => MatchResult::NoSerial { expected: serials },
+            }),
+            DriveMatchKind::HardwareSerial => Some(match info.hardware_serial.as_deref() {
+                Some(serial) if serials.iter().any(|s| s.eq_ignore_ascii_case(serial)) => {
+                    return MatchResult::MatchedByHardwareSerial(serial.to_string());
+                }
+                Some(serial) => MatchResult::HardwareSerialMismatch { expected: serials, found: serial.to_string() },
+                None => MatchResult::NoHardwareSerial { expected: serials },
+            }),
+            DriveMatchKind::IdFile => None,
+        }
+    };
+    
+    if schedule.match_by == DriveMatchKind::IdFile {
+        return if info.has_id_file { MatchResult::MatchedByIdFile(info.id_content.clone()) } else { MatchResult::NoIdFile };
+    }
+    
+    if let Some(pattern) = label_pattern {
+        match info.label.as_deref() {
+            Some(label) if label_matches(pattern, label) => return MatchResult::MatchedByLabel(label.to_string()),
+            // With serials configured too, their mismatch is the more useful reason
+            found => return serial_result.unwrap_or_else(|| MatchResult::LabelMismatch {
+                pattern: pattern.to_string(),
+                found: found.map(str::to_string),
+            }),
+        }
+    }
+    
+    match serial_result {
+        Some(result) => result,
+        None if !schedule.drive_id_file => MatchResult::NoCriteria,
+        None if info.has_id_file => MatchResult::MatchedByIdFile(info.id_content.clone()),
+        None => MatchResult::NoIdFile,
+    }
+}
+
+/// Case-insensitive label match where `*` stands for any run of characters (e.g. "BACKUP*")
+pub fn label_matches(pattern: &str, label: &str) -> bool {
+    crate::backup::wildcard_matches(pattern, label)
+}
+
+/// The entry of a rotating schedule's `destinations` that `info` is, if any
+pub fn rotation_destination<'a>(schedule: &'a BackupSchedule, info: &DriveInfo) -> Option<&'a RotationDestination> {
+    schedule.destinations.iter().find(|destination| {
+        let by_serial = destination.drive_serial.as_deref()
+            .and_then(config::parse_volume_serial)
+            .is_some_and(|serial| info.serial == Some(serial));
+        let by_label = destination.drive_label.as_deref()
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .is_some_and(|pattern| info.label.as_deref().is_some_and(|label| label_matches(pattern, label)));
+        by_serial || by_label
+    })
+}
+
+/// Point a rotating schedule at whichever of its destination drives is connected, preferring
+/// the drive that triggered the backup (`drive_key`); returns the schedule and the chosen drive
+pub fn select_destination(schedule: &BackupSchedule, drives: &[DriveInfo], drive_key: &str)
+    -> Result<(BackupSchedule, DriveInfo), String>
+{
+    let connected: Vec<(&DriveInfo, &RotationDestination)> = drives
+        .iter()
+        .filter_map(|drive| rotation_destination(schedule, drive).map(|destination| (drive, destination)))
+        .collect();
+    let (drive, destination) = connected
+        .iter()
+        .find(|(drive, _)| drive.state_key() == drive_key)
+        .or_else(|| connected.first())
+        .ok_or_else(|| format!("None of the destination drives of '{}' is connected ({})",
+                               schedule.name, schedule.destination_description()))?;
+    
+    let path = format!("{}:\\{}", drive.letter, destination.folder.trim().trim_start_matches(['\\', '/']));
+    log::info!("'{}' rotates between {} drives, backing up to {} on drive {}",
+               schedule.name, schedule.destinations.len(), path, drive.letter);
+    Ok((schedule.with_destination(path), (*drive).clone()))
+}
+
+/// Human-readable table of every schedule checked against every drive, for the
+/// "Test drive match" tray action and `--test-drive-match`
+pub fn drive_match_report(drives: &[DriveInfo], config: &AppConfig) -> String {
+    if drives.is_empty() {
+        return "No drives connected.\n".to_string();
+    }
+    if config.schedules.is_empty() {
+        return "No schedules configured.\n".to_string();
+    }
+    
+    let mut report = String::new();
+    for info in drives {
+        report.push_str(&format!(
+            "Drive {}: {}({}, {}, serial {}, hardware serial {}, ID file: {})\n",
+            info.letter,
+            info.label.as_deref().map(|l| format!("\"{}\" ", l)).unwrap_or_default(),
+            if DriveMonitor::is_removable(info.letter) { "removable" } else { "fixed" },
+            info.file_system.as_deref().unwrap_or("unknown file system"),
+            info.serial.map_or("none".to_string(), |s| format!("{} / {}", config::format_volume_serial(s), s)),
+            info.hardware_serial.as_deref().unwrap_or("none"),
+            if info.has_id_file { "yes" } else { "no" },
+        ));
+        
+        for schedule in &config.schedules {
+            let result = drive_matches(schedule, info);
+            let mut notes = Vec::new();
+            if !schedule.enabled {
+                notes.push("schedule disabled");
+            }
+            if !schedule.trigger_on_connect {
+                notes.push("not triggered on connect");
+            }
+            
+            report.push_str(&format!(
+                "  {} {}: {}{}\n",
+                if result.is_match() { "MATCH   " } else { "NO MATCH" },
+                schedule.name,
+                result,
+                if notes.is_empty() { String::new() } else { format!(" [{}]", notes.join(", ")) },
+            ));
+        }
+        report.push('\n');
+    }
+    
+    report
+}
+
+/// Null-terminated UTF-16 buffer to a string; `None` when empty
+fn wide_to_string(buffer: &[u16]) -> Option<String> {
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    if len == 0 {
+        None
+    } else {
+        Some(String::from_utf16_lossy(&buffer[..len]))
+    }
+}
+
+/// NUL-terminated ASCII string at `offset` in a STORAGE_DEVICE_DESCRIPTOR buffer, trimmed;
+/// `None` for offset 0 (not reported) or a blank string
+fn descriptor_string(buffer: &[u8], offset: usize) -> Option<String> {
+    if offset == 0 || offset >= buffer.len() {
+        return None;
+    }
+    let bytes = &buffer[offset..];
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    let text = String::from_utf8_lossy(&bytes[..len]).trim().to_string();
+    if text.is_empty() { None } else { Some(text) }
+}
+
+/// FAT32 can't store files of 4 GB or more
+/// The cached info for a drive letter if it still holds the same volume (same serial),
+/// with label and file system refreshed; `None` means the drive has to be read again
+fn reuse_cached(cached: Option<&DriveInfo>, volume: &VolumeInfo) -> Option<DriveInfo> {
+    let cached = cached?;
+    if volume.serial.is_none() || cached.serial != volume.serial {
+        return None;
+    }
+    
+    Some(DriveInfo {
+        label: volume.label.clone(),
+        file_system: volume.file_system.clone(),
+        ..cached.clone()
+    })
+}
+
+pub fn is_fat32(file_system: &str) -> bool {
+    file_system.eq_ignore_ascii_case("FAT32")
 }
 
-#[derive(Default)]
 pub struct DriveMonitor {
-    connected_drives: HashMap<char, DriveInfo>,
+    connected_drives: HashMap<String, DriveInfo>, // Keyed by DriveInfo::state_key(), not by letter
+    letter_ids: HashMap<char, String>, // Current drive letter -> drive key
+    config: Arc<Mutex<AppConfig>>, // For countdowns to record what they did; checks get a copy instead
 }
 
 impl DriveMonitor {
-    pub fn new() -> Self {
+    pub fn new(config: Arc<Mutex<AppConfig>>) -> Self {
         Self {
             connected_drives: HashMap::new(),
+            letter_ids: HashMap::new(),
+            config,
         }
     }
     
     pub fn check_drives(&mut self, config: &AppConfig) {
-        let current_drives = Self::get_all_drives();
+        let ignored = config.general.ignored_drive_letters();
+        let current_drives = Self::key_by_id(Self::get_all_drives(&self.drives_by_letter(), &ignored));
         
-        // Check for newly connected drives
-        for (letter, info) in &current_drives {
-            if !self.connected_drives.contains_key(letter) {
-                log::info!("Drive {} connected", letter);
-                self.on_drive_connected(*letter, info, config);
+        // Check for newly connected drives; the same drive under a new letter is not new
+        for (key, info) in &current_drives {
+            match self.connected_drives.get(key) {
+                None => {
+                    if self.letter_ids.get(&info.letter).is_some_and(|old| old != key) {
+                        log::info!("Drive letter {} now belongs to a different drive", info.letter);
+                    }
+                    log::info!("Drive {} connected ({})", info.letter, key);
+                    self.on_drive_connected(info.letter, info, config);
+                }
+                Some(previous) if previous.letter != info.letter => {
+                    log::info!("Drive {} re-lettered to {} ({})", previous.letter, info.letter, key);
+                }
+                Some(_) => {}
             }
         }
         
-        // Check for disconnected drives
-        let disconnected: Vec<char> = self.connected_drives
-            .keys()
-            .filter(|k| !current_drives.contains_key(k))
-            .copied()
-            .collect();
+        // Offer snoozed backups again once the snooze is over, if the drive is still connected
+        for drive_key in status::take_expired_snoozes(Utc::now()) {
+            if let Some(info) = current_drives.values().find(|d| d.state_key() == drive_key) {
+                log::info!("Snooze for drive {} is over", info.letter);
+                self.on_drive_connected(info.letter, info, config);
+            }
+        }
         
-        for letter in disconnected {
-            log::info!("Drive {} disconnected", letter);
-            self.connected_drives.remove(&letter);
+        // Check for disconnected drives
+        for (key, info) in &self.connected_drives {
+            if !current_drives.contains_key(key) {
+                log::info!("Drive {} disconnected ({})", info.letter, key);
+            }
         }
         
-        self.connected_drives = current_drives;
+        self.set_connected(current_drives);
+    }
+    
+    /// Drives currently connected, as last seen by the monitor, in letter order
+    pub fn connected_drives(&self) -> Vec<DriveInfo> {
+        let mut drives: Vec<DriveInfo> = self.connected_drives.values().cloned().collect();
+        drives.sort_by_key(|d| d.letter);
+        drives
+    }
+    
+    /// Look up connected drives right now, without monitor state (for the command line)
+    pub fn scan_drives(ignored: &[char]) -> Vec<DriveInfo> {
+        let mut drives: Vec<DriveInfo> = Self::get_all_drives(&HashMap::new(), ignored).into_values().collect();
+        drives.sort_by_key(|d| d.letter);
+        drives
     }
     
     // Check all currently connected drives on startup
     pub fn check_all_drives_on_startup(&mut self, config: &AppConfig) {
-        let current_drives = Self::get_all_drives();
+        let ignored = config.general.ignored_drive_letters();
+        let current_drives = Self::key_by_id(Self::get_all_drives(&HashMap::new(), &ignored));
         
-        for (letter, info) in &current_drives {
-            log::info!("Checking existing drive {} on startup", letter);
-            self.on_drive_connected(*letter, info, config);
+        for info in current_drives.values() {
+            log::info!("Checking existing drive {} on startup", info.letter);
+            self.on_drive_connected(info.letter, info, config);
         }
         
-        self.connected_drives = current_drives;
+        self.set_connected(current_drives);
+    }
+    
+    /// Last seen info per drive letter, reused by the next scan
+    fn drives_by_letter(&self) -> HashMap<char, DriveInfo> {
+        self.connected_drives.values().map(|info| (info.letter, info.clone())).collect()
+    }
+    
+    fn set_connected(&mut self, drives: HashMap<String, DriveInfo>) {
+        self.letter_ids = drives.iter().map(|(key, info)| (info.letter, key.clone())).collect();
+        self.connected_drives = drives;
+    }
+    
+    /// Re-key drives from letter to stable identity
+    fn key_by_id(drives: HashMap<char, DriveInfo>) -> HashMap<String, DriveInfo> {
+        let mut by_id = HashMap::new();
+        
+        // Letter order so a duplicate identity always lands on the same drive
+        let mut drives: Vec<(char, DriveInfo)> = drives.into_iter().collect();
+        drives.sort_by_key(|(letter, _)| *letter);
+        
+        for (letter, info) in drives {
+            let mut key = info.state_key();
+            if by_id.contains_key(&key) {
+                // Two volumes claiming the same identity (e.g. a copied ID file); keep them apart
+                log::warn!("Drive {} has the same identity as another drive ({})", letter, key);
+                key = format!("{}#{}", key, letter);
+            }
+            by_id.insert(key, info);
+        }
+        
+        by_id
     }
     
     fn on_drive_connected(&self, letter: char, info: &DriveInfo, config: &AppConfig) {
         log::info!("Checking drive {} against {} schedules", letter, config.schedules.len());
         
+        // Collect every due schedule so they share one countdown window
+        let due_schedules = Self::due_for_drive(info, config);
+        
+        if !due_schedules.is_empty() && !status::backups_enabled() {
+            log::info!("==> {} schedule(s) due for drive {}, but backups are turned off", due_schedules.len(), letter);
+        } else if !due_schedules.is_empty() {
+            log::info!("==> {} schedule(s) due for drive {}, CALLING CountdownWindow::show", due_schedules.len(), letter);
+            if info.is_fat32() {
+                log::warn!("Drive {} is formatted as FAT32; files over 4 GB can't be backed up to it", letter);
+            }
+            crate::countdown_window::CountdownWindow::show(due_schedules, info.clone(), config.general.countdown_style,
+                                                           self.config.clone());
+            log::info!("==> CountdownWindow::show returned");
+        }
+    }
+    
+    /// Enabled connect-triggered schedules that match `info`, are due and aren't snoozed
+    fn due_for_drive(info: &DriveInfo, config: &AppConfig) -> Vec<BackupSchedule> {
+        let mut due_schedules = Vec::new();
+        
         // Check if any schedule matches this drive
         for schedule in &config.schedules {
             log::info!("Checking schedule '{}' (enabled: {}, trigger_on_connect: {})", 
@@ -80,73 +432,40 @@ impl DriveMonitor {
                 continue;
             }
             
-            let matches = if let Some(ref target_serial) = schedule.drive_serial {
-                if !target_serial.is_empty() {
-                    // Check by serial number
-                    log::info!("  Checking by serial number: target='{}', drive={:?}", target_serial, info.serial);
-                    if let Some(drive_serial) = info.serial {
-                        let matches = target_serial == &drive_serial.to_string();
-                        log::info!("  Serial match result: {}", matches);
-                        matches
-                    } else {
-                        log::info!("  Drive has no serial number");
-                        false
-                    }
-                } else {
-                    log::info!("  Serial is empty, checking ID file instead");
-                    schedule.drive_id_file && info.has_id_file
+            let result = drive_matches(schedule, info);
+            if result.is_match() {
+                log::info!("✓ Drive matches schedule '{}': {}", schedule.name, result);
+                if status::is_snoozed(&info.state_key(), &schedule.id, Utc::now()) {
+                    log::info!("  Schedule '{}' is snoozed for this drive", schedule.name);
+                } else if Self::is_backup_due(schedule) {
+                    due_schedules.push(schedule.clone());
                 }
-            } else if schedule.drive_id_file {
-                // Check by ID file
-                log::info!("  Checking by ID file: has_id_file={}", info.has_id_file);
-                info.has_id_file
-            } else {
-                log::info!("  No matching criteria configured");
-                false
-            };
-            
-            if matches {
-                log::info!("✓ Drive matches schedule '{}'", schedule.name);
-                self.check_and_trigger_backup(schedule, letter);
             } else {
-                log::info!("✗ Drive does NOT match schedule '{}'", schedule.name);
+                log::info!("✗ Drive does NOT match schedule '{}': {}", schedule.name, result);
             }
         }
+        
+        due_schedules
     }
     
-    fn check_and_trigger_backup(&self, schedule: &crate::config::BackupSchedule, drive_letter: char) {
-        use chrono::{DateTime, Utc, Duration};
+    fn is_backup_due(schedule: &crate::config::BackupSchedule) -> bool {
+        log::info!("==> Checking whether schedule '{}' is due", schedule.name);
         
-        log::info!("==> check_and_trigger_backup CALLED for drive {} and schedule '{}'", drive_letter, schedule.name);
-        
-        let now = Utc::now();
-        let should_backup = if let Some(ref last_backup_str) = schedule.last_backup {
-            if !last_backup_str.is_empty() {
-                if let Ok(last_backup) = DateTime::parse_from_rfc3339(last_backup_str) {
-                    let elapsed = now.signed_duration_since(last_backup);
-                    elapsed >= Duration::days(schedule.interval_days as i64)
-                } else {
-                    true
-                }
-            } else {
-                true // Empty string means never backed up
-            }
-        } else {
-            true // None means never backed up
-        };
+        let should_backup = schedule.is_due(chrono::Utc::now());
         
         log::info!("==> Should backup: {}", should_backup);
         
-        if should_backup {
-            log::info!("==> Backup is due for schedule '{}', CALLING CountdownWindow::show", schedule.name);
-            crate::countdown_window::CountdownWindow::show(schedule.clone(), drive_letter);
-            log::info!("==> CountdownWindow::show returned");
-        } else {
-            log::info!("Backup not due yet for schedule '{}'", schedule.name);
+        if !should_backup {
+            log::info!("Backup not due yet for schedule '{}' (next: {})", schedule.name,
+                       schedule.next_due().map(|due| due.to_rfc3339()).unwrap_or_default());
         }
+        
+        should_backup
     }
     
-    fn get_all_drives() -> HashMap<char, DriveInfo> {
+    /// Scan drive letters except `ignored`; a drive found in `cached` with the same serial keeps its ID file
+    /// info instead of re-reading it, so polling doesn't keep sleeping drives awake
+    fn get_all_drives(cached: &HashMap<char, DriveInfo>, ignored: &[char]) -> HashMap<char, DriveInfo> {
         let mut drives = HashMap::new();
         
         unsafe {
@@ -155,6 +474,9 @@ impl DriveMonitor {
             for i in 0..26 {
                 if (bitmask & (1 << i)) != 0 {
                     let letter = (b'A' + i) as char;
+                    if ignored.contains(&letter) {
+                        continue;
+                    }
                     let drive_path = format!("{}:\\", letter);
                     
                     // Check if it's a removable or fixed drive
@@ -166,17 +488,34 @@ impl DriveMonitor {
                     
                     // 2 = removable, 3 = fixed
                     if drive_type == 2 || drive_type == 3 {
-                        let serial = Self::get_volume_serial(&drive_path);
+                        // Empty card reader slots and drives without media have no volume to read
+                        let volume = match Self::get_volume_info(&drive_path) {
+                            Some(volume) => volume,
+                            None => {
+                                log::debug!("Drive {} has no readable volume (no media?), skipping", letter);
+                                continue;
+                            }
+                        };
+                        if let Some(info) = reuse_cached(cached.get(&letter), &volume) {
+                            drives.insert(letter, info);
+                            continue;
+                        }
+                        
                         let (has_id_file, id_content) = Self::check_id_file(&drive_path);
+                        let hardware_serial = Self::get_hardware_serial(letter);
                         
-                        log::info!("Drive {} - Serial: {:?}, Has ID file: {}, ID content: {:?}", 
-                                  letter, serial, has_id_file, id_content);
+                        log::info!("Drive {} - Serial: {}, Hardware serial: {:?}, Label: {:?}, File system: {:?}, Has ID file: {}, ID content: {:?}", 
+                                  letter, volume.serial.map_or("none".to_string(), config::format_volume_serial), hardware_serial,
+                                  volume.label, volume.file_system, has_id_file, id_content);
                         
                         drives.insert(letter, DriveInfo {
                             letter,
-                            serial,
+                            serial: volume.serial,
                             has_id_file,
                             id_content,
+                            file_system: volume.file_system,
+                            label: volume.label,
+                            hardware_serial,
                         });
                     }
                 }
@@ -186,7 +525,8 @@ impl DriveMonitor {
         drives
     }
     
-    fn get_volume_serial(drive_path: &str) -> Option<u32> {
+    /// Volume serial number, label and file system name
+    fn get_volume_info(drive_path: &str) -> Option<VolumeInfo> {
         unsafe {
             let mut path_wide: Vec<u16> = drive_path.encode_utf16().collect();
             path_wide.push(0);
@@ -194,24 +534,82 @@ impl DriveMonitor {
             let mut serial: u32 = 0;
             let mut max_component_len: u32 = 0;
             let mut file_system_flags: u32 = 0;
+            let mut volume_name = [0u16; 261];
+            let mut file_system_name = [0u16; 32];
             
             let result = GetVolumeInformationW(
                 PWSTR(path_wide.as_mut_ptr()),
-                None,
+                Some(&mut volume_name),
                 Some(&mut serial),
                 Some(&mut max_component_len),
                 Some(&mut file_system_flags),
+                Some(&mut file_system_name),
+            );
+            
+            result.ok().map(|_| VolumeInfo {
+                serial: Some(serial),
+                label: wide_to_string(&volume_name),
+                file_system: wide_to_string(&file_system_name),
+            })
+        }
+    }
+    
+    /// Serial number the device reports through IOCTL_STORAGE_QUERY_PROPERTY. Unlike the volume
+    /// serial it survives reformatting; some cheap USB sticks report none, or the same one for all.
+    fn get_hardware_serial(letter: char) -> Option<String> {
+        let mut volume_wide: Vec<u16> = format!("\\\\.\\{}:", letter).encode_utf16().collect();
+        volume_wide.push(0);
+        
+        unsafe {
+            // Querying properties needs no access rights, so this works without admin
+            let handle = CreateFileW(
+                PCWSTR(volume_wide.as_ptr()),
+                0,
+                FILE_SHARE_READ | FILE_SHARE_WRITE,
+                None,
+                OPEN_EXISTING,
+                FILE_FLAGS_AND_ATTRIBUTES(0),
+                HANDLE::default(),
+            )
+            .ok()?;
+            
+            let query = STORAGE_PROPERTY_QUERY {
+                PropertyId: StorageDeviceProperty,
+                QueryType: PropertyStandardQuery,
+                AdditionalParameters: [0],
+            };
+            let mut buffer = [0u8; 1024];
+            let mut bytes_returned: u32 = 0;
+            let result = DeviceIoControl(
+                handle,
+                IOCTL_STORAGE_QUERY_PROPERTY,
+                Some(&query as *const _ as *const core::ffi::c_void),
+                std::mem::size_of::<STORAGE_PROPERTY_QUERY>() as u32,
+                Some(buffer.as_mut_ptr() as *mut core::ffi::c_void),
+                buffer.len() as u32,
+                Some(&mut bytes_returned),
                 None,
             );
+            CloseHandle(handle).ok();
             
-            if result.is_ok() {
-                Some(serial)
-            } else {
-                None
+            if let Err(e) = result {
+                log::debug!("Drive {} doesn't report a hardware serial: {}", letter, e);
+                return None;
             }
+            let returned = (bytes_returned as usize).min(buffer.len());
+            if returned < std::mem::size_of::<STORAGE_DEVICE_DESCRIPTOR>() {
+                return None;
+            }
+            let descriptor = std::ptr::read_unaligned(buffer.as_ptr() as *const STORAGE_DEVICE_DESCRIPTOR);
+            descriptor_string(&buffer[..returned], descriptor.SerialNumberOffset as usize)
         }
     }
     
+    /// File system of the volume mounted at `letter`, e.g. "NTFS" or "FAT32"
+    pub fn file_system(letter: char) -> Option<String> {
+        Self::get_volume_info(&format!("{}:\\", letter)).and_then(|volume| volume.file_system)
+    }
+    
     fn check_id_file(drive_path: &str) -> (bool, Option<String>) {
         let id_file_path = format!("{}{}", drive_path, DRIVE_ID_FILE);
         
@@ -227,4 +625,293 @@ impl DriveMonitor {
         let id_file_path = format!("{}{}", drive_path, DRIVE_ID_FILE);
         fs::write(&id_file_path, id)
     }
-}
\ No newline at end of file
+    
+    pub fn is_removable(letter: char) -> bool {
+        let mut path_wide: Vec<u16> = format!("{}:\\", letter).encode_utf16().collect();
+        path_wide.push(0);
+        unsafe { GetDriveTypeW(PCWSTR(path_wide.as_ptr())) == DRIVE_REMOVABLE }
+    }
+    
+    /// Flush, lock, dismount and eject a removable volume so it can be unplugged safely.
+    /// Fails without ejecting if another process still has files open on the drive.
+    pub fn eject_drive(letter: char) -> Result<(), String> {
+        if !Self::is_removable(letter) {
+            return Err(format!("Drive {}: is not removable", letter));
+        }
+        
+        let mut volume_wide: Vec<u16> = format!("\\\\.\\{}:", letter).encode_utf16().collect();
+        volume_wide.push(0);
+        
+        unsafe {
+            let handle = CreateFileW(
+                PCWSTR(volume_wide.as_ptr()),
+                GENERIC_READ.0 | GENERIC_WRITE.0,
+                FILE_SHARE_READ | FILE_SHARE_WRITE,
+                None,
+                OPEN_EXISTING,
+                FILE_FLAGS_AND_ATTRIBUTES(0),
+                HANDLE::default(),
+            )
+            .map_err(|e| format!("Failed to open volume {}: {}", letter, e))?;
+            
+            let result = Self::eject_volume_handle(handle, letter);
+            CloseHandle(handle).ok();
+            result
+        }
+    }
+    
+    unsafe fn eject_volume_handle(handle: HANDLE, letter: char) -> Result<(), String> {
+        let mut bytes_returned: u32 = 0;
+        
+        FlushFileBuffers(handle)
+            .map_err(|e| format!("Failed to flush drive {}: {}", letter, e))?;
+        
+        // Locking fails while files are open; give other processes a moment to let go
+        let mut locked = false;
+        for attempt in 1..=EJECT_LOCK_RETRIES {
+            if DeviceIoControl(handle, FSCTL_LOCK_VOLUME, None, 0, None, 0, Some(&mut bytes_returned), None).is_ok() {
+                locked = true;
+                break;
+            }
+            log::info!("Drive {} is busy, retrying lock ({}/{})", letter, attempt, EJECT_LOCK_RETRIES);
+            thread::sleep(Duration::from_millis(500));
+        }
+        
+        if !locked {
+            return Err(format!("Drive {}: is still in use (files are open), close them and eject manually", letter));
+        }
+        
+        DeviceIoControl(handle, FSCTL_DISMOUNT_VOLUME, None, 0, None, 0, Some(&mut bytes_returned), None)
+            .map_err(|e| format!("Failed to dismount drive {}: {}", letter, e))?;
+        
+        let allow_removal = PREVENT_MEDIA_REMOVAL { PreventMediaRemoval: false.into() };
+        DeviceIoControl(
+            handle,
+            IOCTL_STORAGE_MEDIA_REMOVAL,
+            Some(&allow_removal as *const _ as *const core::ffi::c_void),
+            std::mem::size_of::<PREVENT_MEDIA_REMOVAL>() as u32,
+            None,
+            0,
+            Some(&mut bytes_returned),
+            None,
+        )
+        .map_err(|e| format!("Failed to allow media removal on drive {}: {}", letter, e))?;
+        
+        DeviceIoControl(handle, IOCTL_STORAGE_EJECT_MEDIA, None, 0, None, 0, Some(&mut bytes_returned), None)
+            .map_err(|e| format!("Failed to eject drive {}: {}", letter, e))?;
+        
+        log::info!("Drive {} ejected", letter);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn schedule(drive_serial: Option<&str>, drive_id_file: bool) -> BackupSchedule {
+        BackupSchedule {
+            drive_serial: drive_serial.map(str::to_string),
+            drive_id_file,
+            ..BackupSchedule::new("Test".to_string())
+        }
+    }
+    
+    fn drive(serial: Option<u32>, id_content: Option<&str>) -> DriveInfo {
+        DriveInfo {
+            letter: 'E',
+            serial,
+            has_id_file: id_content.is_some(),
+            id_content: id_content.map(str::to_string),
+            file_system: None,
+            label: None,
+            hardware_serial: None,
+        }
+    }
+    
+    #[test]
+    fn test_recent_backup_is_not_triggered_again() {
+        let mut config = AppConfig::default();
+        config.schedules.push(schedule(Some("1234"), false));
+        let connected = drive(Some(1234), None);
+        
+        assert_eq!(DriveMonitor::due_for_drive(&connected, &config).len(), 1);
+        
+        // What update_last_backup records once the countdown's backup has succeeded
+        config.schedules[0].last_backup = Some(Utc::now().to_rfc3339());
+        assert!(DriveMonitor::due_for_drive(&connected, &config).is_empty());
+    }
+    
+    #[test]
+    fn test_match_by() {
+        let reformatted = DriveInfo { hardware_serial: Some("AA0012345678".to_string()), ..drive(Some(5678), Some("my-usb")) };
+        
+        let by_hardware = BackupSchedule { match_by: DriveMatchKind::HardwareSerial, ..schedule(Some("aa0012345678"), false) };
+        assert_eq!(drive_matches(&by_hardware, &reformatted), MatchResult::MatchedByHardwareSerial("AA0012345678".to_string()));
+        assert_eq!(drive_matches(&by_hardware, &drive(Some(5678), None)),
+                   MatchResult::NoHardwareSerial { expected: vec!["aa0012345678".to_string()] });
+        
+        // The volume serial of the reformatted drive no longer matches; that stays the default
+        assert!(!drive_matches(&schedule(Some("1234"), false), &reformatted).is_match());
+        
+        // id_file ignores serials
+        let by_id_file = BackupSchedule { match_by: DriveMatchKind::IdFile, ..schedule(Some("1234"), false) };
+        assert!(drive_matches(&by_id_file, &reformatted).is_match());
+        assert_eq!(drive_matches(&by_id_file, &drive(Some(1234), None)), MatchResult::NoIdFile);
+        
+        let mut buffer = vec![0u8; 64];
+        buffer[40..52].copy_from_slice(b"  1234ABCD  ");
+        assert_eq!(descriptor_string(&buffer, 40).as_deref(), Some("1234ABCD"));
+        assert_eq!(descriptor_string(&buffer, 0), None);
+        assert_eq!(descriptor_string(&buffer, 60), None);
+    }
+    
+    #[test]
+    fn test_drive_matches() {
+        let serial_drive = drive(Some(1234), None);
+        let id_drive = drive(None, Some("my-usb\r\n"));
+        let both_drive = drive(Some(1234), Some("my-usb"));
+        let bare_drive = drive(None, None);
+        
+        // Serial set: decides alone, with or without drive_id_file
+        for id_file in [false, true] {
+            assert_eq!(drive_matches(&schedule(Some("1234"), id_file), &serial_drive), MatchResult::MatchedBySerial(1234));
+            assert_eq!(drive_matches(&schedule(Some("1234"), id_file), &both_drive), MatchResult::MatchedBySerial(1234));
+            assert_eq!(drive_matches(&schedule(Some("9999"), id_file), &both_drive),
+                       MatchResult::SerialMismatch { expected: vec!["9999".to_string()], found: 1234 });
+            assert_eq!(drive_matches(&schedule(Some("1234"), id_file), &id_drive),
+                       MatchResult::NoSerial { expected: vec!["1234".to_string()] });
+            assert_eq!(drive_matches(&schedule(Some("1234"), id_file), &bare_drive),
+                       MatchResult::NoSerial { expected: vec!["1234".to_string()] });
+        }
+        
+        // Empty or missing serial falls back to the ID file
+        for serial in [None, Some(""), Some("  ")] {
+            assert_eq!(drive_matches(&schedule(serial, true), &id_drive),
+                       MatchResult::MatchedByIdFile(Some("my-usb\r\n".to_string())));
+            assert_eq!(drive_matches(&schedule(serial, true), &both_drive),
+                       MatchResult::MatchedByIdFile(Some("my-usb".to_string())));
+            assert_eq!(drive_matches(&schedule(serial, true), &serial_drive), MatchResult::NoIdFile);
+            assert_eq!(drive_matches(&schedule(serial, true), &bare_drive), MatchResult::NoIdFile);
+            
+            for info in [&serial_drive, &id_drive, &both_drive, &bare_drive] {
+                assert_eq!(drive_matches(&schedule(serial, false), info), MatchResult::NoCriteria);
+            }
+        }
+        
+        assert!(MatchResult::MatchedBySerial(1).is_match());
+        assert!(MatchResult::MatchedByIdFile(None).is_match());
+        assert!(!MatchResult::NoCriteria.is_match());
+        assert_eq!(MatchResult::MatchedByIdFile(Some(" my-usb\n".to_string())).to_string(),
+                   ".driveGuardID file found (content: \"my-usb\")");
+    }
+    
+    #[test]
+    fn test_multiple_serials_and_label() {
+        let rotation = BackupSchedule {
+            drive_serial: Some("1111".to_string()),
+            drive_serials: vec!["2222".to_string(), " 3333 ".to_string(), "1111".to_string()],
+            ..schedule(None, true)
+        };
+        assert_eq!(rotation.accepted_serials(), vec!["1111", "2222", "3333"]);
+        
+        assert_eq!(drive_matches(&rotation, &drive(Some(1111), None)), MatchResult::MatchedBySerial(1111));
+        assert_eq!(drive_matches(&rotation, &drive(Some(3333), None)), MatchResult::MatchedBySerial(3333));
+        assert!(!drive_matches(&rotation, &drive(Some(4444), Some("id"))).is_match());
+        
+        // Only the list, no single serial
+        let list_only = BackupSchedule { drive_serials: vec!["2222".to_string()], ..schedule(None, false) };
+        assert_eq!(drive_matches(&list_only, &drive(Some(2222), None)), MatchResult::MatchedBySerial(2222));
+        
+        // Label wildcard, alone and as an alternative to serials
+        let labelled = |label: &str| DriveInfo { label: Some(label.to_string()), ..drive(Some(9999), None) };
+        let by_label = BackupSchedule { drive_label: Some("Backup*".to_string()), ..schedule(None, false) };
+        assert_eq!(drive_matches(&by_label, &labelled("BACKUP-A")), MatchResult::MatchedByLabel("BACKUP-A".to_string()));
+        assert_eq!(drive_matches(&by_label, &labelled("Photos")), MatchResult::LabelMismatch {
+            pattern: "Backup*".to_string(),
+            found: Some("Photos".to_string()),
+        });
+        assert_eq!(drive_matches(&by_label, &drive(Some(9999), None)), MatchResult::LabelMismatch {
+            pattern: "Backup*".to_string(),
+            found: None,
+        });
+        
+        let both = BackupSchedule { drive_label: Some("BACKUP*".to_string()), ..rotation.clone() };
+        assert!(drive_matches(&both, &labelled("backup-b")).is_match());
+        assert!(drive_matches(&both, &DriveInfo { label: Some("Other".to_string()), ..drive(Some(2222), None) }).is_match());
+        assert!(matches!(drive_matches(&both, &labelled("Other")), MatchResult::SerialMismatch { .. }));
+        
+        assert!(label_matches("BACKUP", "backup"));
+        assert!(!label_matches("BACKUP", "BACKUP2"));
+        assert!(label_matches("*", ""));
+        assert!(label_matches("off*site*", "OFFSITE"));
+        assert!(label_matches("*-A", "BACKUP-A"));
+        assert!(label_matches("B*K*P", "BACKUP"));
+        assert!(!label_matches("AB*BA", "ABA"));
+    }
+    
+    #[test]
+    fn test_select_destination() {
+        let rotating = BackupSchedule {
+            destinations: vec![
+                RotationDestination { drive_serial: Some("A1B2-C3D4".to_string()), folder: "Backups\\Laptop".to_string(), ..Default::default() },
+                RotationDestination { drive_label: Some("OFFSITE*".to_string()), folder: "\\Laptop".to_string(), ..Default::default() },
+            ],
+            ..schedule(None, false)
+        };
+        let home = DriveInfo { letter: 'F', ..drive(Some(0xA1B2_C3D4), None) };
+        let offsite = DriveInfo { letter: 'G', label: Some("offsite-2".to_string()), ..drive(Some(42), None) };
+        let other = DriveInfo { letter: 'H', ..drive(Some(7), None) };
+        
+        let (chosen, chosen_drive) = select_destination(&rotating, &[other.clone(), offsite.clone()], &other.state_key()).unwrap();
+        assert_eq!(chosen.destination_path, "G:\\Laptop");
+        assert!(!chosen.rotates_destinations());
+        assert_eq!(chosen_drive.letter, 'G');
+        
+        // The drive that triggered the backup wins when several are connected
+        let (chosen, _) = select_destination(&rotating, &[home.clone(), offsite.clone()], &offsite.state_key()).unwrap();
+        assert_eq!(chosen.destination_path, "G:\\Laptop");
+        let (chosen, _) = select_destination(&rotating, &[home.clone(), offsite], &other.state_key()).unwrap();
+        assert_eq!(chosen.destination_path, "F:\\Backups\\Laptop");
+        
+        assert!(select_destination(&rotating, &[other], "serial:7").unwrap_err().contains("None of the destination drives"));
+    }
+    
+    #[test]
+    fn test_serial_formats() {
+        let info = drive(Some(0xA1B2_C3D4), None);
+        for configured in ["2712847316", "A1B2-C3D4", "a1b2-c3d4", " 0xA1B2C3D4 "] {
+            assert_eq!(drive_matches(&schedule(Some(configured), false), &info), MatchResult::MatchedBySerial(0xA1B2_C3D4));
+        }
+        for configured in ["A1B2C3D4", "A1B2-C3D5", "A1B-2C3D4", "+2712847316", "serial"] {
+            assert!(!drive_matches(&schedule(Some(configured), false), &info).is_match(), "{}", configured);
+        }
+        
+        assert_eq!(config::format_volume_serial(0xA1B2_C3D4), "A1B2-C3D4");
+        assert_eq!(config::format_volume_serial(1234), "0000-04D2");
+        assert_eq!(config::parse_volume_serial("0000-04D2"), Some(1234));
+        assert_eq!(config::parse_volume_serial("4294967296"), None);
+        assert_eq!(MatchResult::MatchedBySerial(1234).to_string(), "serial 0000-04D2 matches");
+    }
+    
+    #[test]
+    fn test_reuse_cached() {
+        let cached = DriveInfo { label: Some("OLD".to_string()), ..drive(Some(1234), Some("my-usb")) };
+        let volume = |serial: Option<u32>| VolumeInfo {
+            serial,
+            label: Some("NEW".to_string()),
+            file_system: Some("NTFS".to_string()),
+        };
+        
+        // Same volume: ID file info kept, volume fields refreshed
+        let info = reuse_cached(Some(&cached), &volume(Some(1234))).unwrap();
+        assert_eq!(info.id_content.as_deref(), Some("my-usb"));
+        assert_eq!(info.label.as_deref(), Some("NEW"));
+        assert_eq!(info.file_system.as_deref(), Some("NTFS"));
+        
+        // Another volume under the same letter, an unreadable one, or a letter seen for the first time
+        assert!(reuse_cached(Some(&cached), &volume(Some(5678))).is_none());
+        assert!(reuse_cached(Some(&cached), &volume(None)).is_none());
+        assert!(reuse_cached(None, &volume(Some(1234))).is_none());
+    }
+}
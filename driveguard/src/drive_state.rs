@@ -0,0 +1,142 @@
+// Per-drive backup history
+// Remembers when each schedule last did a full/incremental backup to a given drive,
+// so the first connect of a new drive gets a full backup and later ones go incremental
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use crate::backup::BackupMode;
+use crate::config::LifecyclePolicy;
+
+const DRIVE_STATE_FILE: &str = "drive_state.json";
+
+/// Backup history of one schedule on one drive
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DriveBackupState {
+    pub last_full_backup: Option<String>, // ISO 8601 format
+    pub last_incremental_backup: Option<String>, // ISO 8601 format
+    pub incrementals_since_full: u32,
+}
+
+impl DriveBackupState {
+    /// Full backup on first sight of the drive or once the incremental chain is too long/old
+    pub fn choose_mode(&self, policy: &LifecyclePolicy, now: DateTime<Utc>) -> BackupMode {
+        let last_full = match self.last_full_backup.as_deref().and_then(|s| DateTime::parse_from_rfc3339(s).ok()) {
+            Some(t) => t,
+            None => return BackupMode::Full,
+        };
+        
+        if policy.full_every_runs > 0 && self.incrementals_since_full >= policy.full_every_runs {
+            return BackupMode::Full;
+        }
+        
+        if policy.full_every_days > 0
+            && now.signed_duration_since(last_full) >= Duration::days(policy.full_every_days as i64)
+        {
+            return BackupMode::Full;
+        }
+        
+        BackupMode::Incremental
+    }
+    
+    pub fn record(&mut self, mode: BackupMode, now: DateTime<Utc>) {
+        if mode == BackupMode::Incremental {
+            self.last_incremental_backup = Some(now.to_rfc3339());
+            self.incrementals_since_full += 1;
+        } else {
+            self.last_full_backup = Some(now.to_rfc3339());
+            self.incrementals_since_full = 0;
+        }
+    }
+}
+
+/// All per-drive state, keyed by "<drive key>/<schedule id>"
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DriveStates {
+    pub drives: BTreeMap<String, DriveBackupState>,
+}
+
+impl DriveStates {
+    pub fn load() -> Self {
+        if !Path::new(DRIVE_STATE_FILE).exists() {
+            return Self::default();
+        }
+        
+        match fs::read_to_string(DRIVE_STATE_FILE).map(|c| serde_json::from_str(&c)) {
+            Ok(Ok(states)) => states,
+            Ok(Err(e)) => {
+                log::warn!("Ignoring unreadable {}: {}", DRIVE_STATE_FILE, e);
+                Self::default()
+            }
+            Err(e) => {
+                log::warn!("Failed to read {}: {}", DRIVE_STATE_FILE, e);
+                Self::default()
+            }
+        }
+    }
+    
+    pub fn save(&self) {
+        match serde_json::to_string_pretty(self) {
+            Ok(content) => {
+                if let Err(e) = fs::write(DRIVE_STATE_FILE, content) {
+                    log::warn!("Failed to write {}: {}", DRIVE_STATE_FILE, e);
+                }
+            }
+            Err(e) => log::warn!("Failed to serialize drive state: {}", e),
+        }
+    }
+    
+    pub fn get(&self, drive_key: &str, schedule_id: &str) -> DriveBackupState {
+        self.drives.get(&state_key(drive_key, schedule_id)).cloned().unwrap_or_default()
+    }
+    
+    pub fn entry(&mut self, drive_key: &str, schedule_id: &str) -> &mut DriveBackupState {
+        self.drives.entry(state_key(drive_key, schedule_id)).or_default()
+    }
+}
+
+fn state_key(drive_key: &str, schedule_id: &str) -> String {
+    format!("{}/{}", drive_key, schedule_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn policy(full_every_runs: u32, full_every_days: u32) -> LifecyclePolicy {
+        LifecyclePolicy { full_every_runs, full_every_days }
+    }
+    
+    #[test]
+    fn test_lifecycle_mode_selection() {
+        let now = Utc::now();
+        let lifecycle = policy(3, 30);
+        
+        // First connect of a drive is always a full backup
+        let mut state = DriveBackupState::default();
+        assert_eq!(state.choose_mode(&lifecycle, now), BackupMode::Full);
+        
+        state.record(BackupMode::Full, now);
+        assert_eq!(state.choose_mode(&lifecycle, now), BackupMode::Incremental);
+        
+        // Chain length is bounded by full_every_runs
+        for _ in 0..3 {
+            state.record(BackupMode::Incremental, now);
+        }
+        assert_eq!(state.incrementals_since_full, 3);
+        assert_eq!(state.choose_mode(&lifecycle, now), BackupMode::Full);
+        
+        state.record(BackupMode::Full, now);
+        assert_eq!(state.incrementals_since_full, 0);
+        
+        // ...and its age by full_every_days
+        assert_eq!(state.choose_mode(&lifecycle, now + Duration::days(29)), BackupMode::Incremental);
+        assert_eq!(state.choose_mode(&lifecycle, now + Duration::days(30)), BackupMode::Full);
+        
+        // Zero disables a limit
+        state.incrementals_since_full = 100;
+        assert_eq!(state.choose_mode(&policy(0, 0), now + Duration::days(365)), BackupMode::Incremental);
+    }
+}
@@ -7,6 +7,7 @@ use std::path::PathBuf;
 pub struct Localization {
     translations: HashMap<String, Value>,
     current_locale: String,
+    log_locale: String, // For log lines and other support-facing text
 }
 
 impl Localization {
@@ -14,6 +15,7 @@ impl Localization {
         let mut loc = Self {
             translations: HashMap::new(),
             current_locale: locale.to_string(),
+            log_locale: "en".to_string(),
         };
         
         loc.load_all_translations();
@@ -115,21 +117,27 @@ impl Localization {
         }
     }
     
-    pub fn get(&self, key: &str) -> String {
-        // Try current locale
-        if let Some(locale_obj) = self.translations.get(&self.current_locale) {
-            if let Some(value) = locale_obj.get(key) {
-                if let Some(s) = value.as_str() {
-                    return s.to_string();
-                }
-            }
+    pub fn set_log_locale(&mut self, locale: &str) {
+        if self.translations.contains_key(locale) {
+            self.log_locale = locale.to_string();
+        } else {
+            log::warn!("Log locale '{}' not found, using English", locale);
+            self.log_locale = "en".to_string();
         }
-        
-        // Fallback to English
-        if let Some(locale_obj) = self.translations.get("en") {
-            if let Some(value) = locale_obj.get(key) {
-                if let Some(s) = value.as_str() {
-                    return s.to_string();
+    }
+    
+    pub fn get(&self, key: &str) -> String {
+        self.get_in(&self.current_locale, key)
+    }
+    
+    /// Look up `key` in a specific locale instead of the current one, falling back to English
+    pub fn get_in(&self, locale: &str, key: &str) -> String {
+        for locale in [locale, "en"] {
+            if let Some(locale_obj) = self.translations.get(locale) {
+                if let Some(value) = locale_obj.get(key) {
+                    if let Some(s) = value.as_str() {
+                        return s.to_string();
+                    }
                 }
             }
         }
@@ -138,14 +146,58 @@ impl Localization {
     }
     
     pub fn get_formatted(&self, key: &str, args: &[&str]) -> String {
-        let mut text = self.get(key);
+        format_placeholders(&self.get(key), args, &[])
+    }
+    
+    pub fn get_named(&self, key: &str, args: &[(&str, &str)]) -> String {
+        format_placeholders(&self.get(key), &[], args)
+    }
+    
+    pub fn get_formatted_in(&self, locale: &str, key: &str, args: &[&str]) -> String {
+        format_placeholders(&self.get_in(locale, key), args, &[])
+    }
+}
+
+/// Substitute `{0}`-style positional and `{name}`-style named placeholders in one pass,
+/// so translations can reorder them and substituted values are never re-scanned.
+/// `{{` and `}}` produce literal braces; unknown placeholders (including `{}`) are kept as-is.
+pub fn format_placeholders(template: &str, positional: &[&str], named: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    
+    while let Some(pos) = rest.find(['{', '}']) {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
         
-        for (i, arg) in args.iter().enumerate() {
-            text = text.replace(&format!("{{{}}}", i), arg);
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
         }
         
-        text
+        if tail.starts_with('{') {
+            if let Some(end) = tail.find('}') {
+                let name = &tail[1..end];
+                let value = match name.parse::<usize>() {
+                    Ok(index) => positional.get(index).copied(),
+                    Err(_) => named.iter().find(|(n, _)| *n == name).map(|(_, v)| *v),
+                };
+                
+                if let Some(value) = value {
+                    out.push_str(value);
+                    rest = &tail[end + 1..];
+                    continue;
+                }
+            }
+        }
+        
+        // Not a placeholder we know; keep the brace literally
+        out.push_str(&tail[..1]);
+        rest = &tail[1..];
     }
+    
+    out.push_str(rest);
+    out
 }
 
 // Global localization instance
@@ -161,6 +213,65 @@ pub fn tf(key: &str, args: &[&str]) -> String {
     LOC.lock().unwrap().get_formatted(key, args)
 }
 
+/// Translate with named placeholders, e.g. `tn("update_version_info", &[("version", "0.2.0")])`
+pub fn tn(key: &str, args: &[(&str, &str)]) -> String {
+    LOC.lock().unwrap().get_named(key, args)
+}
+
+/// Translate into the log language (`log_language`, English by default) for log lines
+pub fn tf_log(key: &str, args: &[&str]) -> String {
+    let loc = LOC.lock().unwrap();
+    loc.get_formatted_in(&loc.log_locale, key, args)
+}
+
 pub fn set_locale(locale: &str) {
     LOC.lock().unwrap().set_locale(locale);
-}
\ No newline at end of file
+}
+
+pub fn set_log_locale(locale: &str) {
+    LOC.lock().unwrap().set_log_locale(locale);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_format_placeholders() {
+        // Positional, including a translation that reorders them
+        assert_eq!(format_placeholders("Version {0} is available (you have {1})", &["0.2.0", "0.1.0"], &[]),
+                   "Version 0.2.0 is available (you have 0.1.0)");
+        assert_eq!(format_placeholders("У вас {1}, доступна {0}", &["0.2.0", "0.1.0"], &[]),
+                   "У вас 0.1.0, доступна 0.2.0");
+        
+        // Named, reordered
+        let named = [("version", "0.2.0"), ("count", "3")];
+        assert_eq!(format_placeholders("{count} files for v{version}", &[], &named), "3 files for v0.2.0");
+        assert_eq!(format_placeholders("v{version}: {count}", &[], &named), "v0.2.0: 3");
+        
+        // Literal braces
+        assert_eq!(format_placeholders("Empty set: {}", &["x"], &[]), "Empty set: {}");
+        assert_eq!(format_placeholders("{{0}} is {0}", &["zero"], &[]), "{0} is zero");
+        assert_eq!(format_placeholders("unbalanced { and }", &[], &[]), "unbalanced { and }");
+        
+        // Unknown placeholders stay, and values aren't substituted again
+        assert_eq!(format_placeholders("{0} {2} {missing}", &["{1}", "one"], &[]), "{1} {2} {missing}");
+    }
+    
+    #[test]
+    fn test_get_in_other_locale() {
+        let mut loc = Localization::new("uk");
+        loc.set_log_locale("en");
+        
+        assert_eq!(loc.get("button_cancel"), "Скасувати");
+        assert_eq!(loc.get_in(&loc.log_locale, "button_cancel"), "Cancel");
+        assert_eq!(loc.get_in("en", "button_cancel"), "Cancel");
+        
+        // Unknown locales and keys fall back to English, then to a marker
+        assert_eq!(loc.get_in("xx", "button_cancel"), "Cancel");
+        assert_eq!(loc.get_in("uk", "no_such_key"), "[Missing: no_such_key]");
+        
+        loc.set_log_locale("xx");
+        assert_eq!(loc.log_locale, "en");
+    }
+}
@@ -0,0 +1,50 @@
+// Log output
+// Goes to the console normally; switched to a file when there's no desktop to show a console on
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+
+pub const LOG_FILE: &str = "driveguard.log";
+
+lazy_static! {
+    static ref LOG_TARGET: Mutex<Option<File>> = Mutex::new(None);
+}
+
+/// Writes to the log file once one is set, stderr before that
+struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match LOG_TARGET.lock().unwrap().as_mut() {
+            Some(file) => file.write(buf),
+            None => io::stderr().write(buf),
+        }
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        match LOG_TARGET.lock().unwrap().as_mut() {
+            Some(file) => file.flush(),
+            None => io::stderr().flush(),
+        }
+    }
+}
+
+pub fn init() {
+    env_logger::Builder::from_default_env()
+        .filter_level(log::LevelFilter::Info)
+        .target(env_logger::Target::Pipe(Box::new(LogWriter)))
+        .init();
+}
+
+/// Append all further log output to `LOG_FILE`
+pub fn log_to_file() -> io::Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(LOG_FILE)?;
+    
+    *LOG_TARGET.lock().unwrap() = Some(file);
+    Ok(())
+}
@@ -13,63 +13,289 @@ mod countdown_window;
 mod update_checker;
 mod update_notification;
 mod version;
+mod status;
+mod power;
+mod drive_state;
+mod logging;
+mod service;
+mod backup_report;
+mod progress_bar;
+mod backup_history;
+mod stats_window;
+mod schedule_export;
 
+use std::io::Write;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use native_windows_gui as nwg;
 use crate::config::AppConfig;
 use crate::drive_monitor::DriveMonitor;
 use crate::ui::TrayApp;
 
 fn main() {
+    // Answered before anything else starts, so they work in any environment
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("--version") => {
+            println!("DriveGuard {} ({})", version::VERSION, version::CODENAME);
+            return;
+        }
+        Some("--help") | Some("-h") => {
+            print_help();
+            return;
+        }
+        _ => {}
+    }
+    
     // Initialize logging to console
-    env_logger::Builder::from_default_env()
-        .filter_level(log::LevelFilter::Info)
-        .init();
+    logging::init();
+    
+    // Windows service, diagnostic, backup and restore commands
+    match args.get(1).map(String::as_str) {
+        Some("--install-service") => exit_with(service::install()),
+        Some("--uninstall-service") => exit_with(service::uninstall()),
+        Some("--run-as-service") => exit_with(service::run()),
+        Some("--test-drive-match") => {
+            let config = AppConfig::load_or_create();
+            print!("{}", drive_monitor::drive_match_report(&DriveMonitor::scan_drives(&config.general.ignored_drive_letters()), &config));
+            exit_with(Ok(()));
+        }
+        Some("--list-backups") => exit_with(list_backups_cli(&args[2..])),
+        Some("--restore") => exit_with(restore_cli(&args[2..])),
+        Some("--run-backup") => exit_with(run_backup_cli(&args[2..])),
+        Some("--export-schedules") => exit_with(export_schedules_cli(&args[2..])),
+        Some("--import-schedules") => exit_with(import_schedules_cli(&args[2..])),
+        _ => {}
+    }
     
     log::info!("DriveGuard v0.1.0 starting...");
     
-    // Initialize NWG
-    nwg::init().expect("Failed to init Native Windows GUI");
+    // Let the user know if an update was applied but couldn't restart us
+    if let Some(version) = driveguard_shared::update_state::take_no_restart_marker() {
+        log::info!("Update to v{} was applied before this launch", version);
+    }
+    
+    // Initialize NWG (fails without an interactive desktop, e.g. Server Core or session 0)
+    let gui_available = match nwg::init() {
+        Ok(()) => true,
+        Err(e) => {
+            log::error!("Failed to init Native Windows GUI: {:?}", e);
+            false
+        }
+    };
+    
+    let config = load_config();
+    verify_applied_update(&config);
+    
+    // Initialize drive monitor
+    let drive_monitor = Arc::new(Mutex::new(DriveMonitor::new(config.clone())));
+    
+    // Create and build the tray application
+    let app = if gui_available {
+        match TrayApp::build_ui(config.clone(), drive_monitor.clone()) {
+            Ok(app) => Some(app),
+            Err(e) => {
+                log::error!("Failed to build tray UI: {:?}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
     
-    // Load or create default configuration
+    if app.is_none() {
+        enter_headless_mode();
+    }
+    
+    // Let the user know if applying the last update failed (e.g. administrator permission was declined)
+    if let Some(reason) = driveguard_shared::update_state::take_apply_failed_marker() {
+        log::warn!("Applying the last update failed: {}", reason);
+        status::notify(&crate::localization::t("update_failed"), &reason, true);
+    }
+    
+    if app.is_some() {
+        ask_telemetry_consent(config.clone());
+    }
+    
+    start_background_threads(config, drive_monitor);
+    
+    // Run the message loop
+    if app.is_some() {
+        nwg::dispatch_thread_events();
+    } else {
+        // Nothing to dispatch; the monitor threads do all the work
+        loop {
+            thread::sleep(Duration::from_secs(3600));
+        }
+    }
+}
+
+/// Keep monitoring drives and running backups without tray or windows
+fn enter_headless_mode() {
+    status::set_headless();
+    
+    match logging::log_to_file() {
+        Ok(()) => log::warn!("No desktop available, running in headless monitoring-only mode"),
+        Err(e) => log::warn!("No desktop available, running headless (failed to open {}: {})", logging::LOG_FILE, e),
+    }
+    log::warn!("Connect-triggered backups start without countdown; update notifications are only logged");
+}
+
+/// Load or create the configuration and apply its languages.
+///
+/// The tray, drive monitor, scheduler and update threads all share this mutex. Hold it only to
+/// copy data out or write results back, never across drive scans, backups, network requests or
+/// dialogs; otherwise one slow operation stalls every other thread.
+fn load_config() -> Arc<Mutex<AppConfig>> {
     let config = Arc::new(Mutex::new(AppConfig::load_or_create()));
     
-    // Set language from config
+    // Set languages from config
     if let Ok(cfg) = config.lock() {
-        crate::localization::set_locale(&cfg.general.language);
-        log::info!("Language set to: {}", cfg.general.language);
+        crate::localization::set_locale(&cfg.general.ui_language);
+        crate::localization::set_log_locale(&cfg.general.log_language);
+        log::info!("Language set to: {} (log: {})", cfg.general.ui_language, cfg.general.log_language);
+        
+        status::set_backups_enabled(cfg.general.backups_enabled);
+        if !cfg.general.backups_enabled {
+            log::warn!("Backups are turned off (backups_enabled = false in settings.toml)");
+        }
     }
     
-    // Initialize drive monitor
-    let drive_monitor = Arc::new(Mutex::new(DriveMonitor::new()));
+    config
+}
+
+/// A just-applied update has to keep running for this long to count as working
+const UPDATE_HEALTHY_AFTER: Duration = Duration::from_secs(120);
+
+/// After an update, count launches of the new version until it has run for UPDATE_HEALTHY_AFTER.
+/// If it keeps ending before that (crash on startup, broken build), have the updater put the previous
+/// version back and never offer this one again. A rollback is only tried once.
+fn verify_applied_update(config: &Arc<Mutex<AppConfig>>) {
+    use driveguard_shared::update_state::{self, MAX_UPDATE_LAUNCHES};
     
-    // Create and build the tray application
-    let app = TrayApp::build_ui(config.clone(), drive_monitor.clone())
-        .expect("Failed to build UI");
+    let mut pending = match update_state::read_pending_update() {
+        Some(pending) => pending,
+        None => return,
+    };
     
-    // Check all drives on startup
-    log::info!("Checking all connected drives on startup...");
-    if let Ok(mut monitor) = drive_monitor.lock() {
-        if let Ok(cfg) = config.lock() {
-            monitor.check_all_drives_on_startup(&cfg);
+    if pending.version != version::VERSION {
+        // The update never took effect, or this is the version that was restored
+        log::info!("Update to v{} is not the running version (v{}), forgetting it", pending.version, version::VERSION);
+        update_state::clear_pending_update();
+        return;
+    }
+    if pending.rollback_started {
+        log::error!("Rolling back the update to v{} failed earlier; keeping this version", pending.version);
+        update_state::clear_pending_update();
+        return;
+    }
+    
+    if pending.launches >= MAX_UPDATE_LAUNCHES {
+        log::error!("v{} stopped {} times before running {} seconds, rolling back to v{}",
+                    pending.version, pending.launches, UPDATE_HEALTHY_AFTER.as_secs(), pending.previous_version);
+        
+        // Next to this executable, whatever the working directory; the updater works in that folder
+        let updater = match std::env::current_exe() {
+            Ok(exe) => exe.with_file_name("updater.exe"),
+            Err(e) => {
+                log::error!("Can't locate updater.exe for the rollback: {}", e);
+                return;
+            }
+        };
+        let install_dir = updater.parent().map(Path::to_path_buf).unwrap_or_default();
+        
+        // Only give up this run once the updater is really on its way
+        match std::process::Command::new(&updater)
+            .arg("--rollback")
+            .arg(&pending.previous_version)
+            .current_dir(&install_dir)
+            .spawn() {
+            Ok(_) => {
+                pending.rollback_started = true;
+                if let Err(e) = update_state::write_pending_update(&pending) {
+                    log::warn!("Failed to write update marker: {}", e);
+                }
+                if let Ok(mut cfg) = config.lock() {
+                    cfg.skip_version(&pending.version);
+                }
+                std::process::exit(0); // The updater replaces the executable and restarts it
+            }
+            Err(e) => {
+                log::error!("Failed to start {} for rollback: {}", updater.display(), e);
+                return;
+            }
         }
     }
     
+    pending.launches += 1;
+    log::info!("Running updated v{} (launch {} of {} allowed before rollback)",
+               pending.version, pending.launches, MAX_UPDATE_LAUNCHES);
+    if let Err(e) = update_state::write_pending_update(&pending) {
+        log::warn!("Failed to write update marker: {}", e);
+    }
+    
+    thread::spawn(|| {
+        thread::sleep(UPDATE_HEALTHY_AFTER);
+        log::info!("Update verified, it has been running for {} seconds", UPDATE_HEALTHY_AFTER.as_secs());
+        update_state::clear_pending_update();
+    });
+}
+
+/// Ask once whether update checks may include an anonymous usage report; only when an endpoint is configured
+fn ask_telemetry_consent(config: Arc<Mutex<AppConfig>>) {
+    let url = match config.lock() {
+        Ok(cfg) => match cfg.general.update_settings {
+            Some(ref settings) if settings.needs_telemetry_consent() => settings.telemetry_url.clone(),
+            _ => return,
+        },
+        Err(_) => return,
+    };
+    
+    // Don't hold up the tray while the question is open
+    thread::spawn(move || {
+        let params = nwg::MessageParams {
+            title: "DriveGuard",
+            content: &crate::localization::tf("telemetry_consent_prompt", &[&url]),
+            buttons: nwg::MessageButtons::YesNo,
+            icons: nwg::MessageIcons::Question,
+        };
+        let consent = matches!(nwg::message(&params), nwg::MessageChoice::Yes);
+        log::info!("Anonymous usage reports {}", if consent { "allowed" } else { "declined" });
+        
+        if let Ok(mut cfg) = config.lock() {
+            cfg.set_telemetry_consent(consent);
+        }
+    });
+}
+
+/// Drive monitoring, scheduled backup and update check loops (shared by the tray app and the service)
+fn start_background_threads(config: Arc<Mutex<AppConfig>>, drive_monitor: Arc<Mutex<DriveMonitor>>) {
     // Start drive monitoring thread
     let config_clone = config.clone();
     let drive_monitor_clone = drive_monitor.clone();
     thread::spawn(move || {
+        // Check all drives on startup; here rather than before spawning, since reading every
+        // volume can take a while and the caller (the tray or the service manager) is waiting
+        log::info!("Checking all connected drives on startup...");
+        let startup_config = config_clone.lock().map(|cfg| cfg.clone());
+        if let (Ok(mut monitor), Ok(cfg)) = (drive_monitor_clone.lock(), startup_config) {
+            monitor.check_all_drives_on_startup(&cfg);
+        }
+        
         loop {
-            // Check for drive connections/disconnections
-            if let Ok(mut monitor) = drive_monitor_clone.lock() {
-                if let Ok(cfg) = config_clone.lock() {
-                    monitor.check_drives(&cfg);
-                }
+            let mut poll_interval = Duration::from_secs(2);
+            
+            // Check for drive connections/disconnections; reading the volumes can be slow, so
+            // work on a copy of the config instead of holding its lock
+            let cfg = config_clone.lock().map(|cfg| cfg.clone());
+            if let (Ok(mut monitor), Ok(cfg)) = (drive_monitor_clone.lock(), cfg) {
+                monitor.check_drives(&cfg);
+                poll_interval = cfg.general.monitor_poll_interval();
             }
             
-            thread::sleep(Duration::from_secs(2));
+            thread::sleep(poll_interval);
         }
     });
     
@@ -77,12 +303,54 @@ fn main() {
     let config_clone2 = config.clone();
     thread::spawn(move || {
         loop {
-            // Check if any scheduled backups need to run
+            let mut poll_interval = Duration::from_secs(60);
+            let mut due = Vec::new();
+            
+            // Check if any scheduled backups need to run; act on them after releasing the lock
             if let Ok(cfg) = config_clone2.lock() {
-                cfg.check_scheduled_backups();
+                due = cfg.due_scheduled_backups(chrono::Utc::now());
+                poll_interval = cfg.general.schedule_poll_interval();
             }
             
-            thread::sleep(Duration::from_secs(60));
+            for schedule in due {
+                if !status::backups_enabled() {
+                    log::info!("Schedule '{}' is due for backup, but backups are turned off", schedule.name);
+                    continue;
+                }
+                log::info!("Schedule '{}' is due for backup", schedule.name);
+                // TODO: Trigger backup countdown window
+            }
+            
+            thread::sleep(poll_interval);
+        }
+    });
+    
+    // Estimate whether a backup is needed at all, for the tray tooltip; throttled by change_check_minutes
+    let config_clone4 = config.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_secs(60)); // Stay out of the way of startup
+        
+        loop {
+            let (interval, schedules) = match config_clone4.lock() {
+                Ok(cfg) => (cfg.general.change_check_interval(), cfg.schedules.clone()),
+                Err(_) => return,
+            };
+            let interval = match interval {
+                Some(interval) => interval,
+                None => return,
+            };
+            
+            if !status::is_backup_active() {
+                let pending: Vec<&str> = schedules
+                    .iter()
+                    .filter(|s| s.enabled && s.has_pending_changes())
+                    .map(|s| s.name.as_str())
+                    .collect();
+                log::debug!("Schedules with changes not backed up yet: {:?}", pending);
+                status::set_pending_changes(Some(!pending.is_empty()));
+            }
+            
+            thread::sleep(interval);
         }
     });
     
@@ -92,22 +360,234 @@ fn main() {
     thread::spawn(move || {
         thread::sleep(Duration::from_secs(5)); // Wait 5 seconds after startup
         
-        if let Ok(cfg) = config_clone3.lock() {
-            let checker = update_checker::UpdateChecker::new(&cfg);
-            
-            if checker.should_check_now() {
-                if let Some(update_info) = checker.check_for_updates() {
-                    if !checker.is_version_skipped(&update_info.version) {
-                        log::info!("Update available: v{}", update_info.version);
-                        update_notification::UpdateNotificationWindow::show(update_info, config_clone3.clone());
-                    } else {
-                        log::info!("Update v{} available but skipped by user", update_info.version);
-                    }
-                }
+        // Only copy the settings under the lock; the check itself runs the updater over the
+        // network and must not stall the drive and schedule threads
+        let mut checker = match config_clone3.lock() {
+            Ok(cfg) => update_checker::UpdateChecker::new(&cfg),
+            Err(_) => return,
+        };
+        if !checker.should_check_now() {
+            return;
+        }
+        
+        let update = checker.check_for_updates();
+        if status::is_shutting_down() {
+            return;
+        }
+        if let Ok(mut cfg) = config_clone3.lock() {
+            cfg.record_update_check(checker.source_status());
+        }
+        
+        if let Some(update_info) = update {
+            if !checker.is_version_skipped(&update_info.version) {
+                log::info!("Update available: v{}", update_info.version);
+                status::set(status::AppStatus::UpdateAvailable(update_info.version.clone()));
+                update_notification::UpdateNotificationWindow::show(update_info, config_clone3.clone());
+            } else {
+                log::info!("Update v{} available but skipped by user", update_info.version);
             }
         }
     });
+}
+
+/// `--list-backups <destination_path>`: print the backups a schedule has made there
+fn list_backups_cli(args: &[String]) -> Result<(), String> {
+    let destination = args.first().ok_or("Usage: driveguard.exe --list-backups <destination_path>")?;
+    let backups = backup::list_backups(Path::new(destination));
     
-    // Run the message loop
-    nwg::dispatch_thread_events();
-}
\ No newline at end of file
+    if backups.is_empty() {
+        println!("No backups found in {}", destination);
+    }
+    for folder in backups.iter().rev() {
+        println!("{}", folder.display());
+    }
+    Ok(())
+}
+
+/// `--restore <backup_folder> <target_folder> [--yes]`: copy a backup back, asking before
+/// overwriting existing files when warn_before_delete is on (unless --yes is given)
+fn restore_cli(args: &[String]) -> Result<(), String> {
+    let usage = "Usage: driveguard.exe --restore <backup_folder> <target_folder> [--yes]";
+    let backup_folder = Path::new(args.first().ok_or(usage)?);
+    let target = Path::new(args.get(1).ok_or(usage)?);
+    let assume_yes = args.iter().any(|a| a == "--yes");
+    
+    let config = AppConfig::load_or_create();
+    let conflicts = backup::restore_conflicts(backup_folder, target);
+    let mut overwrite = true;
+    
+    if !conflicts.is_empty() && config.general.warn_before_delete && !assume_yes {
+        println!("{} file(s) already exist in {} and would be overwritten, e.g.:", conflicts.len(), target.display());
+        for path in conflicts.iter().take(10) {
+            println!("  {}", path.display());
+        }
+        print!("Overwrite them? [y/N] (N restores only missing files): ");
+        std::io::stdout().flush().ok();
+        
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer).map_err(|e| format!("Failed to read answer: {}", e))?;
+        overwrite = answer.trim().eq_ignore_ascii_case("y");
+    }
+    
+    let mut engine = backup::BackupEngine::new();
+    let mut last_print: Option<Instant> = None;
+    engine.on_progress = Some(Box::new(move |progress: &backup::BackupProgress| {
+        if last_print.is_some_and(|t| t.elapsed() < Duration::from_secs(1)) {
+            return;
+        }
+        last_print = Some(Instant::now());
+        println!("  {} files processed", progress.processed_files);
+    }));
+    
+    println!("Restoring {} to {}...", backup_folder.display(), target.display());
+    engine.restore(backup_folder, target, overwrite)?;
+    
+    println!("Restored: {}, kept existing: {}, failed: {}",
+             engine.copied_files, engine.skipped_files, engine.failed_files.len());
+    for (path, error) in &engine.failed_files {
+        println!("  {} - Failed! ({})", path, error);
+    }
+    
+    if engine.failed_files.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} file(s) could not be restored", engine.failed_files.len()))
+    }
+}
+
+/// `--run-backup <schedule id or name> [--quiet]`: run one schedule now from the console with a
+/// live progress bar, or with --quiet only the summary. Exits with 0 on success, 1 if the backup
+/// failed and 2 if it finished but some files couldn't be copied.
+fn run_backup_cli(args: &[String]) -> Result<(), String> {
+    let usage = "Usage: driveguard.exe --run-backup <schedule id or name> [--quiet]";
+    let wanted = args.iter().find(|a| !a.starts_with("--")).ok_or(usage)?;
+    let quiet = args.iter().any(|a| a == "--quiet");
+    
+    // Nobody to answer prompts or see balloons; they go to the log instead
+    status::set_headless();
+    let config = load_config();
+    let schedule = config.lock().unwrap().schedules.iter()
+        .find(|s| s.id == *wanted || s.name.eq_ignore_ascii_case(wanted))
+        .cloned()
+        .ok_or_else(|| format!("No schedule with id or name '{}'", wanted))?;
+    
+    // Lifecycle policies keep their history per destination drive
+    let drive_key = DriveMonitor::scan_drives(&[])
+        .into_iter()
+        .find(|d| Some(d.letter) == schedule.destination_drive_letter())
+        .map(|d| d.state_key())
+        .unwrap_or_else(|| format!("path:{}", schedule.destination().display()));
+    
+    let started = Instant::now();
+    let mut last_draw: Option<Instant> = None;
+    let on_progress = Box::new(move |progress: &backup::BackupProgress| {
+        if quiet || last_draw.is_some_and(|t| t.elapsed() < Duration::from_millis(250)) {
+            return;
+        }
+        last_draw = Some(Instant::now());
+        print!("\r{:<90}", progress_bar::render(progress, started.elapsed()));
+        std::io::stdout().flush().ok();
+    });
+    
+    if !quiet {
+        println!("Backing up '{}' to {}...", schedule.name, schedule.destination_description());
+    }
+    let settings = config.lock().unwrap().general.clone();
+    let result = countdown_window::run_schedule_backup(&schedule, &drive_key, !quiet, &settings, None, on_progress);
+    if !quiet {
+        println!();
+    }
+    let summary = result?;
+    
+    println!("Backup '{}' saved to {} in {}", schedule.name, summary.folder,
+             progress_bar::format_duration(started.elapsed()));
+    println!("Files: {} copied, {} unchanged, {} failed (of {})",
+             summary.copied_files, summary.skipped_files, summary.failed_files.len(), summary.total_files);
+    if !summary.missing_sources.is_empty() {
+        println!("Missing sources: {}", summary.missing_sources.join(", "));
+    }
+    for (source, e) in &summary.failed_sources {
+        println!("Could not back up {}: {}", source, e);
+    }
+    
+    if !summary.failed_sources.is_empty() && summary.failed_files.is_empty() {
+        std::process::exit(2);
+    }
+    if !summary.failed_files.is_empty() {
+        if let Some(error_log) = summary.error_log {
+            println!("Failed files are listed in {}", error_log);
+        }
+        std::process::exit(2);
+    }
+    Ok(())
+}
+
+/// `--export-schedules <file> [schedule id or name...]`: write schedules and their backup lists
+/// to one file (all schedules unless some are named)
+fn export_schedules_cli(args: &[String]) -> Result<(), String> {
+    let file = args.first().ok_or("Usage: driveguard.exe --export-schedules <file> [schedule id or name...]")?;
+    let config = AppConfig::load_or_create();
+    let export = schedule_export::ScheduleExport::from_config(&config, &args[1..])?;
+    export.save(Path::new(file))?;
+    
+    println!("Exported {} schedule(s) to {}", export.schedules.len(), file);
+    Ok(())
+}
+
+/// `--import-schedules <file> [--map-serial OLD=NEW...]`: add exported schedules to settings.toml,
+/// optionally pointing them at this machine's backup drives
+fn import_schedules_cli(args: &[String]) -> Result<(), String> {
+    let usage = "Usage: driveguard.exe --import-schedules <file> [--map-serial OLD=NEW...]";
+    let file = args.first().filter(|a| !a.starts_with("--")).ok_or(usage)?;
+    
+    let mut serial_map = Vec::new();
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        if arg != "--map-serial" {
+            return Err(format!("Unknown option '{}'\n{}", arg, usage));
+        }
+        let (old, new) = rest.next().and_then(|m| m.split_once('=')).ok_or(usage)?;
+        serial_map.push((old.trim().to_string(), new.trim().to_string()));
+    }
+    
+    let export = schedule_export::ScheduleExport::load(Path::new(file))?;
+    let mut config = AppConfig::load_or_create();
+    let imported = export.merge_into(&mut config, &serial_map);
+    
+    std::fs::create_dir_all("schedules").map_err(|e| format!("Failed to create schedules folder: {}", e))?;
+    for exported in &imported {
+        exported.schedule.save_backup_list(&exported.backup_list);
+        println!("Imported '{}' as {} ({} backup path(s))",
+                 exported.schedule.name, exported.schedule.id, exported.backup_list.len());
+    }
+    config.validate();
+    config.save();
+    Ok(())
+}
+
+fn print_help() {
+    println!("DriveGuard {} - Automatic USB Drive Backup Tool", version::VERSION);
+    println!("Without a command DriveGuard runs in the system tray (headless when there is no desktop).");
+    println!("Usage:");
+    println!("  driveguard.exe --run-backup <schedule id or name> [--quiet]  Run a schedule's backup now");
+    println!("  driveguard.exe --list-backups <destination_path>  List the backups in a destination");
+    println!("  driveguard.exe --restore <backup_folder> <target_folder> [--yes]  Copy a backup back");
+    println!("  driveguard.exe --export-schedules <file> [schedule id or name...]  Save schedules and backup lists");
+    println!("  driveguard.exe --import-schedules <file> [--map-serial OLD=NEW...]  Add exported schedules");
+    println!("  driveguard.exe --test-drive-match  Show which connected drives match which schedules");
+    println!("  driveguard.exe --install-service  Register DriveGuard as a Windows service");
+    println!("  driveguard.exe --uninstall-service  Remove the Windows service");
+    println!("  driveguard.exe --version  Print the version");
+    println!("  driveguard.exe --help  Print this help");
+}
+
+fn exit_with(result: Result<(), String>) -> ! {
+    match result {
+        Ok(()) => std::process::exit(0),
+        Err(e) => {
+            log::error!("{}", e);
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
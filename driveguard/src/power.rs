@@ -0,0 +1,99 @@
+// Power management helpers
+// Keeps the machine awake while a backup is copying and pauses copying across suspend/resume
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+// WM_POWERBROADCAST event codes
+const PBT_APMSUSPEND: usize = 4;
+const PBT_APMRESUMEAUTOMATIC: usize = 18;
+
+// Set between a suspend notification and the matching resume
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_suspended() -> bool {
+    SUSPENDED.load(Ordering::SeqCst)
+}
+
+/// Handle a WM_POWERBROADCAST notification
+pub fn on_power_broadcast(event: usize) {
+    match event {
+        PBT_APMSUSPEND => {
+            log::info!("System is suspending, pausing backups");
+            SUSPENDED.store(true, Ordering::SeqCst);
+        }
+        PBT_APMRESUMEAUTOMATIC => {
+            log::info!("System resumed, continuing backups");
+            SUSPENDED.store(false, Ordering::SeqCst);
+        }
+        _ => {}
+    }
+}
+
+/// Block the calling (backup) thread until the system has resumed
+pub fn wait_while_suspended() {
+    if !is_suspended() {
+        return;
+    }
+    
+    log::info!("Backup paused while the system is suspended");
+    while is_suspended() {
+        thread::sleep(Duration::from_millis(500));
+    }
+    log::info!("Backup resumed");
+}
+
+/// Time since the last keyboard or mouse input in this session
+pub fn user_idle_time() -> Duration {
+    #[cfg(windows)]
+    unsafe {
+        use windows::Win32::System::SystemInformation::GetTickCount;
+        use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+        
+        let mut info = LASTINPUTINFO { cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32, dwTime: 0 };
+        if GetLastInputInfo(&mut info).as_bool() {
+            return Duration::from_millis(GetTickCount().wrapping_sub(info.dwTime) as u64);
+        }
+    }
+    Duration::ZERO
+}
+
+/// Prevents the system from sleeping while alive. Must be dropped on the thread that created it
+pub struct KeepAwake;
+
+impl KeepAwake {
+    pub fn new() -> Self {
+        #[cfg(windows)]
+        unsafe {
+            use windows::Win32::System::Power::{SetThreadExecutionState, ES_CONTINUOUS, ES_SYSTEM_REQUIRED};
+            SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED);
+        }
+        Self
+    }
+}
+
+impl Drop for KeepAwake {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        unsafe {
+            use windows::Win32::System::Power::{SetThreadExecutionState, ES_CONTINUOUS};
+            SetThreadExecutionState(ES_CONTINUOUS);
+        }
+    }
+}
+
+/// Ask Windows to send suspend/resume WM_POWERBROADCAST messages to `hwnd`.
+/// Needed for message-only windows, which don't receive the broadcast otherwise
+#[cfg(windows)]
+pub fn register_suspend_resume(hwnd: *mut core::ffi::c_void) {
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::System::Power::RegisterSuspendResumeNotification;
+    use windows::Win32::UI::WindowsAndMessaging::DEVICE_NOTIFY_WINDOW_HANDLE;
+    
+    unsafe {
+        if let Err(e) = RegisterSuspendResumeNotification(HANDLE(hwnd), DEVICE_NOTIFY_WINDOW_HANDLE) {
+            log::warn!("Failed to register for suspend/resume notifications: {}", e);
+        }
+    }
+}
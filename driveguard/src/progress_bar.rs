@@ -0,0 +1,160 @@
+// Text progress bar for console backups (`--run-backup`)
+
+use std::time::Duration;
+use crate::backup::BackupProgress;
+
+const BAR_WIDTH: usize = 24;
+
+/// One line like `[#########---------------]  38%  120/315 files (38%)  1.2 GB/3.1 GB  ETA 2m 05s`;
+/// the bar follows bytes, the file share is shown separately since the two can differ a lot.
+/// Without pre-counted totals only the running counts are shown.
+pub fn render(progress: &BackupProgress, elapsed: Duration) -> String {
+    let counts = format!("{} files  {}", progress.processed_files, format_bytes(progress.processed_bytes));
+    
+    // Bytes say more about remaining time than file counts, when known
+    let percent = match progress.bytes_percent().or_else(|| progress.files_percent()) {
+        Some(percent) => percent,
+        None => return counts,
+    };
+    
+    let filled = (percent / 100.0 * BAR_WIDTH as f64).round() as usize;
+    let mut line = format!(
+        "[{}{}] {:>3}%  {}/{} files",
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        percent.floor() as u32,
+        progress.processed_files,
+        progress.expected_files,
+    );
+    if let (Some(files), Some(_)) = (progress.files_percent(), progress.bytes_percent()) {
+        line.push_str(&format!(" ({}%)", files.floor() as u32));
+    }
+    line.push_str(&format!("  {}/{}", format_bytes(progress.processed_bytes), format_bytes(progress.expected_bytes)));
+    
+    if let Some(eta) = progress.eta.filter(|_| percent < 100.0).or_else(|| average_eta(percent / 100.0, elapsed)) {
+        line.push_str(&format!("  ETA {}", format_duration(eta)));
+    }
+    line
+}
+
+/// Remaining time at the average rate so far, until the engine has a smoothed estimate
+fn average_eta(fraction: f64, elapsed: Duration) -> Option<Duration> {
+    if fraction <= 0.0 || fraction >= 1.0 || elapsed < Duration::from_secs(1) {
+        return None;
+    }
+    Some(elapsed.mul_f64((1.0 - fraction) / fraction))
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// `9,800`
+pub fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let groups: Vec<&str> = digits
+        .as_bytes()
+        .rchunks(3)
+        .rev()
+        .map(|group| std::str::from_utf8(group).unwrap_or_default())
+        .collect();
+    groups.join(",")
+}
+
+/// Shorten a long path for a balloon by dropping folders after the drive, keeping the end:
+/// `D:\…\Backups\2025-11-19T12-00-00`
+pub fn shorten_path(path: &str, max_chars: usize) -> String {
+    if path.chars().count() <= max_chars {
+        return path.to_string();
+    }
+    
+    let parts: Vec<&str> = path.split('\\').collect();
+    let mut tail: Vec<&str> = Vec::new();
+    let mut length = parts[0].chars().count() + 2; // "D:" + "\…"
+    for part in parts[1..].iter().rev() {
+        let part_length = part.chars().count() + 1;
+        if length + part_length > max_chars && !tail.is_empty() {
+            break;
+        }
+        length += part_length;
+        tail.insert(0, part);
+    }
+    
+    if tail.len() == parts.len() - 1 {
+        // A single very long folder name; cut it instead
+        let keep: String = path.chars().rev().take(max_chars.saturating_sub(1)).collect();
+        return format!("…{}", keep.chars().rev().collect::<String>());
+    }
+    format!("{}\\…\\{}", parts[0], tail.join("\\"))
+}
+
+/// `45s`, `2m 05s` or `1h 02m`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_render() {
+        let progress = BackupProgress {
+            processed_files: 50,
+            total_files: 50,
+            processed_bytes: 512 * 1024 * 1024,
+            expected_files: 100,
+            expected_bytes: 2 * 1024 * 1024 * 1024,
+            eta: None,
+        };
+        assert_eq!(render(&progress, Duration::from_secs(30)),
+                   "[######------------------]  25%  50/100 files (50%)  512.0 MB/2.0 GB  ETA 1m 30s");
+        
+        // The engine's smoothed estimate wins over the average so far
+        let smoothed = BackupProgress { eta: Some(Duration::from_secs(45)), ..progress };
+        assert!(render(&smoothed, Duration::from_secs(30)).ends_with("ETA 45s"));
+        
+        // No totals: counts only
+        let unknown = BackupProgress { expected_files: 0, expected_bytes: 0, ..progress };
+        assert_eq!(render(&unknown, Duration::from_secs(30)), "50 files  512.0 MB");
+        
+        // More than expected (files grew) stays at 100% without an ETA
+        let over = BackupProgress { processed_bytes: 3 * 1024 * 1024 * 1024, ..progress };
+        assert!(render(&over, Duration::from_secs(30)).starts_with(&format!("[{}] 100%", "#".repeat(BAR_WIDTH))));
+        assert!(!render(&over, Duration::from_secs(30)).contains("ETA"));
+        
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1h 02m");
+        assert_eq!(format_count(9800), "9,800");
+        assert_eq!(format_count(1234567), "1,234,567");
+        assert_eq!(format_count(42), "42");
+    }
+    
+    #[test]
+    fn test_shorten_path() {
+        assert_eq!(shorten_path("D:\\Backups\\2025-11-19T12-00-00", 60), "D:\\Backups\\2025-11-19T12-00-00");
+        assert_eq!(shorten_path("D:\\Users\\me\\Work\\Backups\\2025-11-19T12-00-00", 35),
+                   "D:\\…\\Backups\\2025-11-19T12-00-00");
+        let long = format!("D:\\{}", "x".repeat(50));
+        assert_eq!(shorten_path(&long, 20).chars().count(), 20);
+    }
+}
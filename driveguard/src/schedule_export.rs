@@ -0,0 +1,151 @@
+// Schedule export and import
+// Schedules and their backup lists travel in one JSON file, so a standard backup policy can be
+// set up on other machines without recreating it by hand
+
+use std::fs;
+use std::path::Path;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use crate::config::{AppConfig, BackupSchedule};
+
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleExport {
+    pub format_version: u32,
+    pub exported: String, // ISO 8601 format
+    pub schedules: Vec<ExportedSchedule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedSchedule {
+    pub schedule: BackupSchedule,
+    pub backup_list: Vec<String>,
+}
+
+impl ScheduleExport {
+    /// The schedules whose id or name is in `wanted` (all of them if it's empty)
+    pub fn from_config(config: &AppConfig, wanted: &[String]) -> Result<Self, String> {
+        for name in wanted {
+            if !config.schedules.iter().any(|s| matches(s, name)) {
+                return Err(format!("No schedule with id or name '{}'", name));
+            }
+        }
+        
+        let schedules = config.schedules
+            .iter()
+            .filter(|s| wanted.is_empty() || wanted.iter().any(|name| matches(s, name)))
+            .map(|s| ExportedSchedule { schedule: s.clone(), backup_list: s.load_backup_list() })
+            .collect();
+        
+        Ok(Self {
+            format_version: FORMAT_VERSION,
+            exported: Utc::now().to_rfc3339(),
+            schedules,
+        })
+    }
+    
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let export: Self = serde_json::from_str(&content)
+            .map_err(|e| format!("{} is not a DriveGuard schedule export: {}", path.display(), e))?;
+        
+        if export.format_version > FORMAT_VERSION {
+            return Err(format!("{} was exported by a newer DriveGuard (format {}), please update first",
+                               path.display(), export.format_version));
+        }
+        Ok(export)
+    }
+    
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize schedules: {}", e))?;
+        fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+    
+    /// Add the exported schedules to `config` as new ones: fresh ids, no backup history yet, and
+    /// drive serials replaced according to `serial_map` (old, new). Names that are already taken
+    /// get " (imported)" appended. Returns the schedules as added, for writing their backup lists.
+    pub fn merge_into(self, config: &mut AppConfig, serial_map: &[(String, String)]) -> Vec<ExportedSchedule> {
+        let mut next_id = Utc::now().timestamp();
+        let mut imported = Vec::new();
+        
+        for mut exported in self.schedules {
+            let schedule = &mut exported.schedule;
+            
+            while config.schedules.iter().any(|s| s.id == format!("schedule_{}", next_id)) {
+                next_id += 1;
+            }
+            schedule.id = format!("schedule_{}", next_id);
+            next_id += 1;
+            
+            if config.schedules.iter().any(|s| s.name.eq_ignore_ascii_case(&schedule.name)) {
+                schedule.name = format!("{} (imported)", schedule.name);
+            }
+            
+            schedule.last_backup = None;
+            schedule.first_backup_confirmed = false; // New machine, new sources and destination to check
+            schedule.drive_serial = schedule.drive_serial.take().map(|serial| remap(serial, serial_map));
+            schedule.drive_serials = schedule.drive_serials.drain(..).map(|serial| remap(serial, serial_map)).collect();
+            
+            config.schedules.push(exported.schedule.clone());
+            imported.push(exported);
+        }
+        imported
+    }
+}
+
+fn matches(schedule: &BackupSchedule, wanted: &str) -> bool {
+    schedule.id == wanted || schedule.name.eq_ignore_ascii_case(wanted)
+}
+
+fn remap(serial: String, serial_map: &[(String, String)]) -> String {
+    serial_map
+        .iter()
+        .find(|(old, _)| old.eq_ignore_ascii_case(serial.trim()))
+        .map(|(_, new)| new.clone())
+        .unwrap_or(serial)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_merge_into_config() {
+        let mut weekly = BackupSchedule::new("Weekly".to_string());
+        weekly.drive_serial = Some("1111".to_string());
+        weekly.drive_serials = vec!["2222".to_string()];
+        weekly.last_backup = Some("2025-11-19T12:00:00Z".to_string());
+        let mut daily = BackupSchedule::new("Daily".to_string());
+        daily.id = "schedule_1".to_string();
+        
+        let export = ScheduleExport {
+            format_version: FORMAT_VERSION,
+            exported: Utc::now().to_rfc3339(),
+            schedules: vec![
+                ExportedSchedule { schedule: weekly.clone(), backup_list: vec!["C:\\Data".to_string()] },
+                ExportedSchedule { schedule: daily, backup_list: Vec::new() },
+            ],
+        };
+        
+        // Importing into the machine it came from must not clash with the originals
+        let mut config = AppConfig::default();
+        config.schedules.push(weekly.clone());
+        let imported = export.merge_into(&mut config, &[("1111".to_string(), "9999".to_string())]);
+        
+        assert_eq!(config.schedules.len(), 3);
+        assert_eq!(imported.len(), 2);
+        let ids: Vec<&String> = config.schedules.iter().map(|s| &s.id).collect();
+        assert!(ids.iter().enumerate().all(|(i, id)| !ids[i + 1..].contains(id)));
+        
+        let copy = &imported[0].schedule;
+        assert_eq!(copy.name, "Weekly (imported)");
+        assert_eq!(copy.last_backup, None);
+        assert_eq!(copy.drive_serial.as_deref(), Some("9999"));
+        assert_eq!(copy.drive_serials, vec!["2222".to_string()]);
+        assert_eq!(imported[0].backup_list, vec!["C:\\Data".to_string()]);
+        assert_eq!(imported[1].schedule.name, "Daily");
+    }
+}
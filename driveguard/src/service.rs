@@ -0,0 +1,187 @@
+// Windows service support
+// Runs the same drive monitor and schedule loops as the tray app, without any UI,
+// so backups happen even when nobody is logged in
+
+use std::ffi::OsString;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+        ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle},
+    service_dispatcher,
+    service_manager::{ServiceManager, ServiceManagerAccess},
+};
+use crate::drive_monitor::DriveMonitor;
+use crate::status::{self, AppStatus};
+
+const SERVICE_NAME: &str = "DriveGuard";
+const SERVICE_DISPLAY_NAME: &str = "DriveGuard Backup Service";
+const SERVICE_DESCRIPTION: &str = "Backs up files to USB drives when they are connected or on a schedule";
+const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
+
+// How long a stop request waits for a running backup to finish; at system shutdown Windows
+// only waits about 20 seconds for services, so there the backup gets less
+const STOP_BACKUP_TIMEOUT: Duration = Duration::from_secs(600);
+const SHUTDOWN_BACKUP_TIMEOUT: Duration = Duration::from_secs(15);
+const PENDING_WAIT_HINT: Duration = Duration::from_secs(10);
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Register DriveGuard with the service control manager (needs admin rights)
+pub fn install() -> Result<(), String> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    ).map_err(|e| format!("Failed to open service manager: {}", e))?;
+    
+    let executable_path = std::env::current_exe()
+        .map_err(|e| format!("Failed to locate driveguard.exe: {}", e))?;
+    
+    let info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from(SERVICE_DISPLAY_NAME),
+        service_type: SERVICE_TYPE,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path,
+        launch_arguments: vec![OsString::from("--run-as-service")],
+        dependencies: vec![],
+        account_name: None, // LocalSystem
+        account_password: None,
+    };
+    
+    let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG)
+        .map_err(|e| format!("Failed to install service: {}", e))?;
+    service.set_description(SERVICE_DESCRIPTION)
+        .map_err(|e| format!("Failed to set service description: {}", e))?;
+    
+    log::info!("Service '{}' installed", SERVICE_NAME);
+    println!("DriveGuard service installed. Start it with: sc start {}", SERVICE_NAME);
+    Ok(())
+}
+
+/// Stop (if running) and remove the service
+pub fn uninstall() -> Result<(), String> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .map_err(|e| format!("Failed to open service manager: {}", e))?;
+    
+    let service = manager.open_service(
+        SERVICE_NAME,
+        ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+    ).map_err(|e| format!("Failed to open service: {}", e))?;
+    
+    // Marked for deletion now, removed once stopped and all handles are closed
+    service.delete().map_err(|e| format!("Failed to delete service: {}", e))?;
+    
+    let stopped = service.query_status()
+        .map(|s| s.current_state == ServiceState::Stopped)
+        .unwrap_or(false);
+    if !stopped {
+        if let Err(e) = service.stop() {
+            log::warn!("Failed to stop service: {}", e);
+        }
+    }
+    
+    log::info!("Service '{}' uninstalled", SERVICE_NAME);
+    println!("DriveGuard service removed.");
+    Ok(())
+}
+
+/// Entry point when started by the service control manager
+pub fn run() -> Result<(), String> {
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+        .map_err(|e| format!("Failed to start service dispatcher (only the service manager can use --run-as-service): {}", e))
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        log::error!("Service failed: {}", e);
+    }
+}
+
+fn run_service() -> Result<(), String> {
+    let (shutdown_tx, shutdown_rx) = mpsc::channel();
+    
+    let event_handler = move |control_event| -> ServiceControlHandlerResult {
+        match control_event {
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                shutdown_tx.send(control_event).ok();
+                ServiceControlHandlerResult::NoError
+            }
+            _ => ServiceControlHandlerResult::NotImplemented,
+        }
+    };
+    
+    let status_handle = service_control_handler::register(SERVICE_NAME, event_handler)
+        .map_err(|e| format!("Failed to register service control handler: {}", e))?;
+    set_state(&status_handle, ServiceState::StartPending, ServiceControlAccept::empty(), 1)?;
+    
+    // Services start in System32; settings.toml and friends live next to the exe
+    if let Some(dir) = std::env::current_exe().ok().and_then(|p| p.parent().map(|d| d.to_path_buf())) {
+        std::env::set_current_dir(&dir).ok();
+    }
+    
+    status::set_headless();
+    if let Err(e) = crate::logging::log_to_file() {
+        log::warn!("Failed to open {}: {}", crate::logging::LOG_FILE, e);
+    }
+    log::info!("DriveGuard v{} starting as a service", crate::version::VERSION);
+    
+    let config = crate::load_config();
+    let drive_monitor = Arc::new(Mutex::new(DriveMonitor::new(config.clone())));
+    set_state(&status_handle, ServiceState::StartPending, ServiceControlAccept::empty(), 2)?;
+    
+    // Returns right away; the startup drive scan runs on the monitor thread
+    crate::start_background_threads(config, drive_monitor);
+    
+    set_state(&status_handle, ServiceState::Running, ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN, 0)?;
+    
+    // Wait for stop or shutdown
+    let control = shutdown_rx.recv().unwrap_or(ServiceControl::Stop);
+    log::info!("Service stop requested");
+    status::request_shutdown();
+    set_state(&status_handle, ServiceState::StopPending, ServiceControlAccept::empty(), 1)?;
+    
+    // Let a backup that's already copying finish instead of leaving it half done, telling the
+    // service manager every second that the stop is still progressing
+    let timeout = if control == ServiceControl::Shutdown { SHUTDOWN_BACKUP_TIMEOUT } else { STOP_BACKUP_TIMEOUT };
+    let started = Instant::now();
+    let mut checkpoint = 2;
+    while matches!(status::get(), AppStatus::BackingUp { .. }) && started.elapsed() < timeout {
+        std::thread::sleep(Duration::from_secs(1));
+        set_state(&status_handle, ServiceState::StopPending, ServiceControlAccept::empty(), checkpoint)?;
+        checkpoint += 1;
+    }
+    
+    if matches!(status::get(), AppStatus::BackingUp { .. }) {
+        log::warn!("Backup still running after {:?}, stopping anyway", timeout);
+    }
+    
+    log::info!("Service stopped");
+    set_state(&status_handle, ServiceState::Stopped, ServiceControlAccept::empty(), 0)
+}
+
+fn set_state(
+    handle: &ServiceStatusHandle,
+    state: ServiceState,
+    controls_accepted: ServiceControlAccept,
+    checkpoint: u32,
+) -> Result<(), String> {
+    handle.set_service_status(ServiceStatus {
+        service_type: SERVICE_TYPE,
+        current_state: state,
+        controls_accepted,
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint,
+        wait_hint: match state {
+            ServiceState::StartPending | ServiceState::StopPending => PENDING_WAIT_HINT,
+            _ => Duration::default(),
+        },
+        process_id: None,
+    }).map_err(|e| format!("Failed to set service status: {}", e))
+}
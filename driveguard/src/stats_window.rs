@@ -0,0 +1,139 @@
+use native_windows_gui as nwg;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+use chrono::Utc;
+use crate::backup_history::{BackupHistory, ScheduleStats};
+use crate::config::BackupSchedule;
+use crate::localization::{t, tf};
+use crate::progress_bar::{format_bytes, format_duration};
+
+/// Per-schedule backup statistics from history.json
+pub struct StatsWindow {
+    window: nwg::Window,
+    
+    #[allow(dead_code)] // Never read, but the text box only stays on screen while the struct owns it
+    text_stats: nwg::TextBox,
+    btn_close: nwg::Button,
+    
+    handler: RefCell<Option<nwg::EventHandler>>,
+}
+
+impl StatsWindow {
+    pub fn show(schedules: Vec<BackupSchedule>) {
+        thread::spawn(move || {
+            if let Err(e) = nwg::init() {
+                log::error!("Failed to init NWG in statistics thread: {:?}", e);
+                return;
+            }
+            
+            let text = stats_text(&schedules, &BackupHistory::load());
+            if let Err(e) = Self::build_and_run(&text) {
+                log::error!("Failed to build statistics window: {:?}", e);
+            }
+        });
+    }
+    
+    fn build_and_run(text: &str) -> Result<(), nwg::NwgError> {
+        let mut window = Default::default();
+        nwg::Window::builder()
+            .size((500, 420))
+            .position((300, 300))
+            .title(&t("stats_title"))
+            .flags(nwg::WindowFlags::WINDOW | nwg::WindowFlags::VISIBLE)
+            .build(&mut window)?;
+        
+        let mut text_stats = Default::default();
+        nwg::TextBox::builder()
+            .text(text)
+            .readonly(true)
+            .flags(nwg::TextBoxFlags::VISIBLE | nwg::TextBoxFlags::VSCROLL | nwg::TextBoxFlags::AUTOVSCROLL)
+            .parent(&window)
+            .position((20, 20))
+            .size((460, 320))
+            .build(&mut text_stats)?;
+        
+        let mut btn_close = Default::default();
+        nwg::Button::builder()
+            .text(&t("button_close"))
+            .parent(&window)
+            .position((340, 360))
+            .size((140, 40))
+            .build(&mut btn_close)?;
+        
+        let app = Rc::new(StatsWindow {
+            window,
+            text_stats,
+            btn_close,
+            handler: RefCell::new(None),
+        });
+        
+        let app_clone = app.clone();
+        let handler = nwg::full_bind_event_handler(&app.window.handle, move |evt, _evt_data, handle| {
+            use nwg::Event;
+            
+            if handle == app_clone.btn_close {
+                if let Event::OnButtonClick = evt {
+                    nwg::stop_thread_dispatch();
+                }
+            } else if handle == app_clone.window {
+                if let Event::OnWindowClose = evt {
+                    nwg::stop_thread_dispatch();
+                }
+            }
+        });
+        
+        *app.handler.borrow_mut() = Some(handler);
+        
+        nwg::dispatch_thread_events();
+        Ok(())
+    }
+}
+
+impl Drop for StatsWindow {
+    fn drop(&mut self) {
+        let handler = self.handler.borrow();
+        if let Some(h) = handler.as_ref() {
+            nwg::unbind_event_handler(h);
+        }
+    }
+}
+
+/// One block per schedule (edit controls need \r\n line breaks)
+fn stats_text(schedules: &[BackupSchedule], history: &BackupHistory) -> String {
+    if schedules.is_empty() {
+        return t("stats_no_schedules");
+    }
+    
+    let now = Utc::now();
+    let mut text = String::new();
+    for schedule in schedules {
+        text.push_str(&format!("{}\r\n", schedule.name));
+        
+        let stats = history.stats(&schedule.id, now);
+        if stats.runs == 0 {
+            text.push_str(&format!("  {}\r\n\r\n", t("stats_no_runs")));
+            continue;
+        }
+        
+        for line in stats_lines(&stats) {
+            text.push_str(&format!("  {}\r\n", line));
+        }
+        text.push_str("\r\n");
+    }
+    text
+}
+
+fn stats_lines(stats: &ScheduleStats) -> Vec<String> {
+    let seconds = |s: u64| format_duration(Duration::from_secs(s));
+    
+    let mut lines = vec![tf("stats_runs", &[&stats.runs.to_string()])];
+    lines.push(tf("stats_average_duration", &[&seconds(stats.average_seconds)]));
+    if let Some(last) = stats.last_seconds {
+        lines.push(tf("stats_last_duration", &[&seconds(last)]));
+    }
+    lines.push(tf("stats_average_copied", &[&stats.average_files.to_string(), &format_bytes(stats.average_bytes)]));
+    lines.push(tf("stats_recent_failures", &[&stats.recent_failures.to_string(), &stats.recent_runs.to_string()]));
+    lines
+}
@@ -0,0 +1,238 @@
+// Shared application status
+// Background threads publish what DriveGuard is doing; the tray polls it for the tooltip
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use crate::localization::{t, tf, tf_log, tn};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppStatus {
+    Idle,
+    CountdownPending { drive: char, seconds_remaining: u64 }, // Tray-style countdown before a backup
+    BackingUp { processed: usize, total: usize, eta: Option<std::time::Duration> },
+    BackupFailed(String), // Error message shown when the tray is clicked
+    UpdateAvailable(String), // Version
+}
+
+impl AppStatus {
+    pub fn tooltip(&self) -> String {
+        if !backups_enabled() && !self.is_active() {
+            return t("tray_status_backups_off");
+        }
+        
+        match self {
+            AppStatus::Idle => match pending_changes() {
+                Some(true) => t("tray_status_changes_pending"),
+                Some(false) => t("tray_status_up_to_date"),
+                None => t("tray_status_idle"),
+            },
+            AppStatus::CountdownPending { drive, seconds_remaining } => tn("tray_status_countdown", &[
+                ("drive", &drive.to_string()),
+                ("time", &format!("{}:{:02}", seconds_remaining / 60, seconds_remaining % 60)),
+            ]),
+            AppStatus::BackingUp { processed, total, eta: Some(eta) } => tf("tray_status_backing_up_eta", &[
+                &processed.to_string(),
+                &total.to_string(),
+                &crate::progress_bar::format_duration(*eta),
+            ]),
+            AppStatus::BackingUp { processed, total, eta: None } => {
+                tf("tray_status_backing_up", &[&processed.to_string(), &total.to_string()])
+            }
+            AppStatus::BackupFailed(_) => t("tray_status_failed"),
+            AppStatus::UpdateAvailable(version) => tf("tray_status_update", &[version]),
+        }
+    }
+    
+    /// Icon the tray shows next to this status's tooltip
+    pub fn tray_icon(&self) -> TrayIcon {
+        if !backups_enabled() && !self.is_active() {
+            return TrayIcon::BackupsOff;
+        }
+        
+        match self {
+            AppStatus::Idle => TrayIcon::Idle,
+            AppStatus::CountdownPending { .. } | AppStatus::BackingUp { .. } => TrayIcon::BackingUp,
+            AppStatus::BackupFailed(_) => TrayIcon::Error,
+            AppStatus::UpdateAvailable(_) => TrayIcon::UpdateAvailable,
+        }
+    }
+    
+    /// A backup is running or about to start
+    fn is_active(&self) -> bool {
+        matches!(self, AppStatus::BackingUp { .. } | AppStatus::CountdownPending { .. })
+    }
+}
+
+/// The tray icon variants; all but `Idle` are the normal icon with a badge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayIcon {
+    Idle,
+    BackingUp,
+    Error,
+    UpdateAvailable,
+    BackupsOff,
+}
+
+// Set when there's no desktop to show windows on (Server Core, session 0)
+static HEADLESS: AtomicBool = AtomicBool::new(false);
+
+// Set when the service is stopping; no new backups are started after this
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+// Set while backups are turned off (backups_enabled = false or the tray toggle); nothing is triggered
+static BACKUPS_OFF: AtomicBool = AtomicBool::new(false);
+
+/// What the user picked from the tray menu during a tray countdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountdownAction {
+    StartNow,
+    Cancel,
+}
+
+/// Balloon message for the tray to show on its next status refresh
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub title: String,
+    pub text: String,
+    pub is_error: bool,
+}
+
+lazy_static! {
+    static ref STATUS: Mutex<AppStatus> = Mutex::new(AppStatus::Idle);
+    static ref COUNTDOWN_ACTION: Mutex<Option<CountdownAction>> = Mutex::new(None);
+    static ref NOTIFICATIONS: Mutex<Vec<Notification>> = Mutex::new(Vec::new());
+    // Connect-triggered backups put off from the countdown window: (drive key, schedule id) -> until
+    static ref SNOOZES: Mutex<HashMap<(String, String), DateTime<Utc>>> = Mutex::new(HashMap::new());
+    // Downloaded update installed when DriveGuard is closed (restart_after_update = "manual")
+    static ref UPDATE_ON_EXIT: Mutex<Option<String>> = Mutex::new(None);
+    // Whether the sources changed since their last backup; None until checked or when the check is off
+    static ref PENDING_CHANGES: Mutex<Option<bool>> = Mutex::new(None);
+}
+
+pub fn set(status: AppStatus) {
+    *STATUS.lock().unwrap() = status;
+}
+
+pub fn get() -> AppStatus {
+    STATUS.lock().unwrap().clone()
+}
+
+pub fn request_countdown_action(action: CountdownAction) {
+    *COUNTDOWN_ACTION.lock().unwrap() = Some(action);
+}
+
+pub fn take_countdown_action() -> Option<CountdownAction> {
+    COUNTDOWN_ACTION.lock().unwrap().take()
+}
+
+/// Queue a tray balloon; only logged when headless since there is no tray to show it
+pub fn notify(title: &str, text: &str, is_error: bool) {
+    if is_headless() {
+        log::info!("{}: {}", title, text);
+        return;
+    }
+    
+    NOTIFICATIONS.lock().unwrap().push(Notification {
+        title: title.to_string(),
+        text: text.to_string(),
+        is_error,
+    });
+}
+
+/// Like `notify` for a translated message: the balloon uses the UI language, the log line
+/// the log language, so driveguard.log stays readable in bug reports
+pub fn notify_localized(title: &str, key: &str, args: &[&str], is_error: bool) {
+    if is_error {
+        log::warn!("{}: {}", title, tf_log(key, args));
+    } else {
+        log::info!("{}: {}", title, tf_log(key, args));
+    }
+    
+    if !is_headless() {
+        NOTIFICATIONS.lock().unwrap().push(Notification {
+            title: title.to_string(),
+            text: tf(key, args),
+            is_error,
+        });
+    }
+}
+
+/// Don't offer `schedule_id`'s backup to the drive again before `until` (kept in memory only)
+pub fn snooze(drive_key: &str, schedule_id: &str, until: DateTime<Utc>) {
+    SNOOZES.lock().unwrap().insert((drive_key.to_string(), schedule_id.to_string()), until);
+}
+
+pub fn is_snoozed(drive_key: &str, schedule_id: &str, now: DateTime<Utc>) -> bool {
+    SNOOZES.lock().unwrap()
+        .get(&(drive_key.to_string(), schedule_id.to_string()))
+        .is_some_and(|until| now < *until)
+}
+
+/// Remove snoozes that have run out and return the drives they were for, so a drive that is
+/// still connected can be offered its backup again
+pub fn take_expired_snoozes(now: DateTime<Utc>) -> Vec<String> {
+    let mut snoozes = SNOOZES.lock().unwrap();
+    let mut drive_keys: Vec<String> = snoozes.iter()
+        .filter(|(_, until)| now >= **until)
+        .map(|((drive_key, _), _)| drive_key.clone())
+        .collect();
+    snoozes.retain(|_, until| now < *until);
+    
+    drive_keys.sort();
+    drive_keys.dedup();
+    drive_keys
+}
+
+/// A backup is running or about to start, so DriveGuard must not restart now
+pub fn is_backup_active() -> bool {
+    get().is_active()
+}
+
+pub fn set_backups_enabled(enabled: bool) {
+    BACKUPS_OFF.store(!enabled, Ordering::SeqCst);
+}
+
+/// False while the master switch is off; drives and schedules are still checked, but not backed up
+pub fn backups_enabled() -> bool {
+    !BACKUPS_OFF.load(Ordering::SeqCst)
+}
+
+pub fn set_pending_changes(pending: Option<bool>) {
+    *PENDING_CHANGES.lock().unwrap() = pending;
+}
+
+pub fn pending_changes() -> Option<bool> {
+    *PENDING_CHANGES.lock().unwrap()
+}
+
+pub fn set_update_on_exit(version: &str) {
+    *UPDATE_ON_EXIT.lock().unwrap() = Some(version.to_string());
+}
+
+pub fn take_update_on_exit() -> Option<String> {
+    UPDATE_ON_EXIT.lock().unwrap().take()
+}
+
+pub fn take_notifications() -> Vec<Notification> {
+    std::mem::take(&mut *NOTIFICATIONS.lock().unwrap())
+}
+
+pub fn set_headless() {
+    HEADLESS.store(true, Ordering::SeqCst);
+}
+
+/// True when running monitoring-only, without tray or windows
+pub fn is_headless() -> bool {
+    HEADLESS.load(Ordering::SeqCst)
+}
+
+pub fn request_shutdown() {
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+}
+
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::SeqCst)
+}
@@ -1,26 +1,53 @@
 use native_windows_gui as nwg;
 use std::sync::{Arc, Mutex};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::thread;
+use std::time::Duration;
+use chrono::{DateTime, Local};
+use driveguard_shared::manifest::UpdateSettings;
 use crate::config::AppConfig;
 use crate::drive_monitor::DriveMonitor;
+use crate::stats_window::StatsWindow;
+use crate::status::{self, AppStatus, TrayIcon};
+use crate::update_checker::UpdateChecker;
+use crate::update_notification::UpdateNotificationWindow;
+
+const WM_POWERBROADCAST: u32 = 0x0218;
+const POWER_HANDLER_ID: usize = 0x10000;
 
 pub struct TrayApp {
     window: nwg::MessageWindow,
     icon: nwg::Icon,
+    // Status icons; None if one failed to load, the normal icon stands in for it
+    backing_up_icon: Option<nwg::Icon>,
+    error_icon: Option<nwg::Icon>,
+    update_icon: Option<nwg::Icon>,
+    backups_off_icon: Option<nwg::Icon>,
     tray: nwg::TrayNotification,
     tray_menu: nwg::Menu,
     menu_title: nwg::MenuItem,
     menu_sep1: nwg::MenuSeparator,
+    menu_backups_enabled: nwg::MenuItem,
+    menu_countdown_start: nwg::MenuItem,
+    menu_countdown_cancel: nwg::MenuItem,
     menu_settings: nwg::MenuItem,
     menu_schedules: nwg::MenuItem,
+    menu_statistics: nwg::MenuItem,
+    menu_test_match: nwg::MenuItem,
+    menu_check_updates: nwg::MenuItem,
     menu_about: nwg::MenuItem,
     menu_sep2: nwg::MenuSeparator,
     menu_exit: nwg::MenuItem,
     
+    status_timer: nwg::AnimationTimer,
+    last_tooltip: RefCell<String>,
+    last_icon: Cell<TrayIcon>,
+    
     config: Arc<Mutex<AppConfig>>,
     drive_monitor: Arc<Mutex<DriveMonitor>>,
     
     handler: RefCell<Option<nwg::EventHandler>>,
+    power_handler: RefCell<Option<nwg::RawEventHandler>>,
 }
 
 impl TrayApp {
@@ -43,13 +70,17 @@ impl TrayApp {
                     .build(&mut icon)
                     .expect("Failed to create icon");
             });
+        let backing_up_icon = load_status_icon(include_bytes!("../assets/icon_backing_up.ico"));
+        let error_icon = load_status_icon(include_bytes!("../assets/icon_error.ico"));
+        let update_icon = load_status_icon(include_bytes!("../assets/icon_update.ico"));
+        let backups_off_icon = load_status_icon(include_bytes!("../assets/icon_backups_off.ico"));
         
         // Create tray
         let mut tray = Default::default();
         nwg::TrayNotification::builder()
             .parent(&window)
             .icon(Some(&icon))
-            .tip(Some("DriveGuard - Automatic Backup"))
+            .tip(Some(&AppStatus::Idle.tooltip()))
             .build(&mut tray)?;
         
         // Create menu
@@ -71,6 +102,29 @@ impl TrayApp {
             .parent(&tray_menu)
             .build(&mut menu_sep1)?;
         
+        // Master switch, checked while backups are on
+        let mut menu_backups_enabled = Default::default();
+        nwg::MenuItem::builder()
+            .text(&crate::localization::t("menu_backups_enabled"))
+            .parent(&tray_menu)
+            .check(status::backups_enabled())
+            .build(&mut menu_backups_enabled)?;
+        
+        // Only enabled while a tray-style countdown is running
+        let mut menu_countdown_start = Default::default();
+        nwg::MenuItem::builder()
+            .text(&crate::localization::t("menu_start_backup_now"))
+            .parent(&tray_menu)
+            .disabled(true)
+            .build(&mut menu_countdown_start)?;
+        
+        let mut menu_countdown_cancel = Default::default();
+        nwg::MenuItem::builder()
+            .text(&crate::localization::t("menu_cancel_backup"))
+            .parent(&tray_menu)
+            .disabled(true)
+            .build(&mut menu_countdown_cancel)?;
+        
         let mut menu_settings = Default::default();
         nwg::MenuItem::builder()
             .text("Settings")
@@ -83,6 +137,24 @@ impl TrayApp {
             .parent(&tray_menu)
             .build(&mut menu_schedules)?;
         
+        let mut menu_statistics = Default::default();
+        nwg::MenuItem::builder()
+            .text(&crate::localization::t("menu_statistics"))
+            .parent(&tray_menu)
+            .build(&mut menu_statistics)?;
+        
+        let mut menu_test_match = Default::default();
+        nwg::MenuItem::builder()
+            .text("Test Drive Match")
+            .parent(&tray_menu)
+            .build(&mut menu_test_match)?;
+        
+        let mut menu_check_updates = Default::default();
+        nwg::MenuItem::builder()
+            .text("Check for Updates")
+            .parent(&tray_menu)
+            .build(&mut menu_check_updates)?;
+        
         let mut menu_about = Default::default();
         nwg::MenuItem::builder()
             .text("About")
@@ -100,21 +172,42 @@ impl TrayApp {
             .parent(&tray_menu)
             .build(&mut menu_exit)?;
         
+        // Poll the shared status to keep the tooltip and icon current
+        let mut status_timer = Default::default();
+        nwg::AnimationTimer::builder()
+            .parent(&window)
+            .interval(Duration::from_secs(1))
+            .build(&mut status_timer)?;
+        
         let app = Arc::new(TrayApp {
             window,
             icon,
+            backing_up_icon,
+            error_icon,
+            update_icon,
+            backups_off_icon,
             tray,
             tray_menu,
             menu_title,
             menu_sep1,
+            menu_backups_enabled,
+            menu_countdown_start,
+            menu_countdown_cancel,
             menu_settings,
             menu_schedules,
+            menu_statistics,
+            menu_test_match,
+            menu_check_updates,
             menu_about,
             menu_sep2,
             menu_exit,
+            status_timer,
+            last_tooltip: RefCell::new(String::new()),
+            last_icon: Cell::new(TrayIcon::Idle),
             config,
             drive_monitor,
             handler: RefCell::new(None),
+            power_handler: RefCell::new(None),
         });
         
         // Setup event handlers
@@ -129,11 +222,31 @@ impl TrayApp {
                         app_clone.tray_menu.popup(x, y);
                     }
                     Event::OnMousePress(nwg::MousePressEvent::MousePressLeftUp) => {
-                        let (x, y) = nwg::GlobalCursor::position();
-                        app_clone.tray_menu.popup(x, y);
+                        if let AppStatus::BackupFailed(error) = status::get() {
+                            app_clone.show_backup_failure(&error);
+                        } else {
+                            let (x, y) = nwg::GlobalCursor::position();
+                            app_clone.tray_menu.popup(x, y);
+                        }
                     }
                     _ => {}
                 }
+            } else if handle == app_clone.status_timer {
+                if let Event::OnTimerTick = evt {
+                    app_clone.refresh_status();
+                }
+            } else if handle == app_clone.menu_backups_enabled {
+                if let Event::OnMenuItemSelected = evt {
+                    app_clone.toggle_backups();
+                }
+            } else if handle == app_clone.menu_countdown_start {
+                if let Event::OnMenuItemSelected = evt {
+                    status::request_countdown_action(status::CountdownAction::StartNow);
+                }
+            } else if handle == app_clone.menu_countdown_cancel {
+                if let Event::OnMenuItemSelected = evt {
+                    status::request_countdown_action(status::CountdownAction::Cancel);
+                }
             } else if handle == app_clone.menu_settings {
                 if let Event::OnMenuItemSelected = evt {
                     app_clone.show_settings();
@@ -142,12 +255,25 @@ impl TrayApp {
                 if let Event::OnMenuItemSelected = evt {
                     app_clone.show_schedules();
                 }
+            } else if handle == app_clone.menu_statistics {
+                if let Event::OnMenuItemSelected = evt {
+                    app_clone.show_statistics();
+                }
+            } else if handle == app_clone.menu_test_match {
+                if let Event::OnMenuItemSelected = evt {
+                    app_clone.show_drive_match();
+                }
+            } else if handle == app_clone.menu_check_updates {
+                if let Event::OnMenuItemSelected = evt {
+                    app_clone.check_for_updates_now();
+                }
             } else if handle == app_clone.menu_about {
                 if let Event::OnMenuItemSelected = evt {
                     app_clone.show_about();
                 }
             } else if handle == app_clone.menu_exit {
                 if let Event::OnMenuItemSelected = evt {
+                    crate::update_checker::UpdateChecker::apply_update_on_exit();
                     nwg::stop_thread_dispatch();
                 }
             }
@@ -155,52 +281,216 @@ impl TrayApp {
         
         *app.handler.borrow_mut() = Some(handler);
         
+        // Pause backups across suspend/resume
+        if let Some(hwnd) = app.window.handle.hwnd() {
+            crate::power::register_suspend_resume(hwnd as *mut core::ffi::c_void);
+        }
+        let power_handler = nwg::bind_raw_event_handler(&app.window.handle, POWER_HANDLER_ID, |_hwnd, msg, w, _l| {
+            if msg == WM_POWERBROADCAST {
+                crate::power::on_power_broadcast(w);
+            }
+            None
+        });
+        match power_handler {
+            Ok(h) => *app.power_handler.borrow_mut() = Some(h),
+            Err(e) => log::warn!("Failed to bind power event handler: {:?}", e),
+        }
+        
+        app.status_timer.start();
+        
+        // Point out config problems that need the user's attention
+        let warnings = app.config.lock().map(|cfg| cfg.shared_destination_warnings()).unwrap_or_default();
+        if !warnings.is_empty() {
+            let flags = nwg::TrayNotificationFlags::WARNING_ICON | nwg::TrayNotificationFlags::LARGE_ICON;
+            app.tray.show(&warnings.join("\n\n"), Some("DriveGuard: shared backup destination"), Some(flags), None);
+        }
+        
         Ok(app)
     }
     
+    fn refresh_status(&self) {
+        let current = status::get();
+        let tooltip = current.tooltip();
+        let mut last = self.last_tooltip.borrow_mut();
+        
+        if *last != tooltip {
+            self.tray.set_tip(&tooltip);
+            *last = tooltip;
+            
+            // Same status as the tooltip, so the two always agree
+            let icon = current.tray_icon();
+            if self.last_icon.replace(icon) != icon {
+                self.tray.set_icon(self.status_icon(icon));
+            }
+            
+            let counting_down = matches!(current, AppStatus::CountdownPending { .. });
+            self.menu_countdown_start.set_enabled(counting_down);
+            self.menu_countdown_cancel.set_enabled(counting_down);
+        }
+        
+        for notification in status::take_notifications() {
+            let icon = if notification.is_error {
+                nwg::TrayNotificationFlags::ERROR_ICON
+            } else {
+                nwg::TrayNotificationFlags::INFO_ICON
+            };
+            self.tray.show(&notification.text, Some(&notification.title), Some(icon | nwg::TrayNotificationFlags::LARGE_ICON), None);
+        }
+    }
+    
+    fn status_icon(&self, icon: TrayIcon) -> &nwg::Icon {
+        let badged = match icon {
+            TrayIcon::Idle => None,
+            TrayIcon::BackingUp => self.backing_up_icon.as_ref(),
+            TrayIcon::Error => self.error_icon.as_ref(),
+            TrayIcon::UpdateAvailable => self.update_icon.as_ref(),
+            TrayIcon::BackupsOff => self.backups_off_icon.as_ref(),
+        };
+        badged.unwrap_or(&self.icon)
+    }
+    
+    /// Turn all backups off or back on; drives connected meanwhile are offered their backup the next time
+    fn toggle_backups(&self) {
+        let enabled = !status::backups_enabled();
+        status::set_backups_enabled(enabled);
+        self.menu_backups_enabled.set_checked(enabled);
+        log::info!("Backups turned {} from the tray", if enabled { "on" } else { "off" });
+        
+        if let Ok(mut cfg) = self.config.lock() {
+            cfg.remember_backups_enabled(enabled);
+        }
+        self.refresh_status();
+    }
+    
+    fn show_backup_failure(&self, error: &str) {
+        nwg::modal_error_message(&self.window, "Backup Failed", &format!("Backup failed:\n\n{}", error));
+        
+        // The user has seen the error, go back to idle
+        status::set(AppStatus::Idle);
+        self.refresh_status();
+    }
+    
+    // Dialogs stay open for as long as the user likes; build their text first and release the
+    // config lock before showing them, or drive monitoring stalls behind it
     fn show_settings(&self) {
-        if let Ok(cfg) = self.config.lock() {
-            let msg = format!(
+        let msg = match self.config.lock() {
+            Ok(cfg) => format!(
                 "Current Settings:\n\n\
-                Language: {}\n\
+                Language: {} (log: {})\n\
                 Min Free Space: {} GB\n\
                 Warn Before Delete: {}\n\
                 Active Schedules: {}\n\n\
+                Update Sources:\n{}\n\
                 Edit 'settings.toml' to change settings.",
-                cfg.general.language,
+                cfg.general.ui_language,
+                cfg.general.log_language,
                 cfg.general.min_free_space_gb,
                 cfg.general.warn_before_delete,
-                cfg.schedules.len()
+                cfg.schedules.len(),
+                describe_update_sources(&cfg.general.update_settings.clone().unwrap_or_default())
+            ),
+            Err(_) => return,
+        };
+        
+        nwg::modal_info_message(&self.window, "Settings", &msg);
+    }
+    
+    fn show_schedules(&self) {
+        let schedules = match self.config.lock() {
+            Ok(cfg) => cfg.schedules.clone(),
+            Err(_) => return,
+        };
+        
+        if schedules.is_empty() {
+            nwg::modal_info_message(
+                &self.window,
+                "Schedules",
+                "No schedules configured yet.\n\nAdd a schedule in settings.toml to get started!"
             );
+        } else {
+            let mut msg = String::from("Configured Schedules:\n\n");
+            for schedule in &schedules {
+                msg.push_str(&format!(
+                    "• {} ({})\n  Runs: {}\n  Trigger on connect: {}\n  Destination: {}\n\n",
+                    schedule.name,
+                    if schedule.enabled { "Enabled" } else { "Disabled" },
+                    schedule.timing_description(),
+                    schedule.trigger_on_connect,
+                    schedule.destination_description()
+                ));
+            }
             
-            nwg::modal_info_message(&self.window, "Settings", &msg);
+            nwg::modal_info_message(&self.window, "Schedules", &msg);
         }
     }
     
-    fn show_schedules(&self) {
-        if let Ok(cfg) = self.config.lock() {
-            if cfg.schedules.is_empty() {
-                nwg::modal_info_message(
-                    &self.window,
-                    "Schedules",
-                    "No schedules configured yet.\n\nAdd a schedule in settings.toml to get started!"
-                );
-            } else {
-                let mut msg = String::from("Configured Schedules:\n\n");
-                for schedule in &cfg.schedules {
-                    msg.push_str(&format!(
-                        "• {} ({})\n  Interval: {} days\n  Trigger on connect: {}\n  Destination: {}\n\n",
-                        schedule.name,
-                        if schedule.enabled { "Enabled" } else { "Disabled" },
-                        schedule.interval_days,
-                        schedule.trigger_on_connect,
-                        schedule.destination_path
-                    ));
+    /// Durations, sizes and failures of past backups, per schedule
+    fn show_statistics(&self) {
+        if let Ok(schedules) = self.config.lock().map(|cfg| cfg.schedules.clone()) {
+            StatsWindow::show(schedules);
+        }
+    }
+    
+    /// Explain which connected drives each schedule would (not) back up to
+    fn show_drive_match(&self) {
+        let drives = match self.drive_monitor.lock() {
+            Ok(monitor) => monitor.connected_drives(),
+            Err(_) => return,
+        };
+        
+        let report = match self.config.lock() {
+            Ok(cfg) => crate::drive_monitor::drive_match_report(&drives, &cfg),
+            Err(_) => return,
+        };
+        nwg::modal_info_message(&self.window, "Test Drive Match", &report);
+    }
+    
+    /// Run the update checker right away, ignoring the check frequency
+    fn check_for_updates_now(&self) {
+        let config = self.config.clone();
+        
+        // The check runs the updater process, keep it off the UI thread
+        thread::spawn(move || {
+            let mut checker = match config.lock() {
+                Ok(cfg) => UpdateChecker::new(&cfg),
+                Err(_) => return,
+            };
+            
+            let update = checker.check_for_updates();
+            if let Ok(mut cfg) = config.lock() {
+                cfg.record_update_check(checker.source_status());
+            }
+            
+            match update {
+                Some(update_info) if checker.is_version_skipped(&update_info.version) => {
+                    let params = nwg::MessageParams {
+                        title: "DriveGuard Updates",
+                        content: &format!("Version {} is available but you chose to skip it.\n\nShow it anyway?",
+                                          update_info.version),
+                        buttons: nwg::MessageButtons::YesNo,
+                        icons: nwg::MessageIcons::Question,
+                    };
+                    
+                    if matches!(nwg::message(&params), nwg::MessageChoice::Yes) {
+                        if let Ok(mut cfg) = config.lock() {
+                            cfg.unskip_version(&update_info.version);
+                        }
+                        status::set(AppStatus::UpdateAvailable(update_info.version.clone()));
+                        UpdateNotificationWindow::show(update_info, config.clone());
+                    }
+                }
+                Some(update_info) => {
+                    status::set(AppStatus::UpdateAvailable(update_info.version.clone()));
+                    UpdateNotificationWindow::show(update_info, config.clone());
+                }
+                None => {
+                    nwg::simple_message(
+                        "DriveGuard Updates",
+                        &format!("You're up to date (v{}).", crate::update_checker::get_current_version()),
+                    );
                 }
-                
-                nwg::modal_info_message(&self.window, "Schedules", &msg);
             }
-        }
+        });
     }
     
     fn show_about(&self) {
@@ -224,5 +514,42 @@ impl Drop for TrayApp {
         if let Some(h) = handler.as_ref() {
             nwg::unbind_event_handler(h);
         }
+        
+        if let Some(h) = self.power_handler.borrow_mut().take() {
+            nwg::unbind_raw_event_handler(&h).ok();
+        }
+    }
+}
+
+/// Build an embedded tray status icon; None if Windows rejects it
+fn load_status_icon(data: &'static [u8]) -> Option<nwg::Icon> {
+    let mut icon = Default::default();
+    match nwg::Icon::builder().source_bin(Some(data)).build(&mut icon) {
+        Ok(()) => Some(icon),
+        Err(e) => {
+            log::warn!("Failed to load a tray status icon, using the normal one: {:?}", e);
+            None
+        }
     }
-}
\ No newline at end of file
+}
+
+/// Each update source with when it last answered and its last error, so "no updates" can be
+/// told apart from "the server can't be reached"
+fn describe_update_sources(settings: &UpdateSettings) -> String {
+    let when = |time: &Option<String>| match time.as_deref().map(DateTime::parse_from_rfc3339) {
+        Some(Ok(time)) => time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(),
+        _ => "never".to_string(),
+    };
+    
+    let mut text = String::new();
+    for source in &settings.sources {
+        let status = settings.source_status.get(&source.url).cloned().unwrap_or_default();
+        text.push_str(&format!("• {}{} ({})\n  Last reached: {}\n",
+                               source.name, if source.enabled { "" } else { " [disabled]" }, source.url,
+                               when(&status.last_success)));
+        if let Some(error) = status.last_error {
+            text.push_str(&format!("  Last error ({}): {}\n", when(&status.last_failure), error));
+        }
+    }
+    text
+}
@@ -1,9 +1,15 @@
+use std::collections::BTreeMap;
 use std::process::Command;
 use std::thread;
 use std::time::Duration;
 use chrono::{DateTime, Utc};
-use driveguard_shared::manifest::UpdateSettings;
+use driveguard_shared::manifest::{
+    RestartPolicy, SourceStatus, UpdateSettings, UpdateSource, Version, UPDATE_HEADERS_ENV, UPDATE_SETTINGS_ENV,
+    UPDATE_SOURCE_URL_ENV,
+};
+use driveguard_shared::updater_output::{exit_code, UpdaterOutput, JSON_FLAG};
 use crate::config::AppConfig;
+use crate::status;
 
 // Get version from version.rs module
 pub fn get_current_version() -> &'static str {
@@ -47,34 +53,76 @@ impl UpdateChecker {
         true
     }
     
-    pub fn check_for_updates(&self) -> Option<UpdateInfo> {
+    /// Ask every enabled source for a newer release, noting in `source_status` which of them answered
+    pub fn check_for_updates(&mut self) -> Option<UpdateInfo> {
         log::info!("Checking for updates...");
         
         // Sort sources by priority
         let mut sources = self.settings.sources.clone();
         sources.sort_by_key(|s| s.priority);
         
-        // Try each source in order
-        for source in sources.iter().filter(|s| s.enabled) {
-            log::info!("Trying update source: {} ({})", source.name, source.url);
-            
-            match self.check_source(&source.url) {
-                Ok(info) => {
-                    log::info!("Found update from {}: v{}", source.name, info.version);
-                    return Some(info);
-                }
-                Err(e) => {
-                    log::warn!("Failed to check {}: {}", source.name, e);
-                    continue;
-                }
-            }
+        // Ask every source; the newest release wins, priority only breaks ties
+        let update = newest_update(&sources, get_current_version(), |source| self.check_source(source));
+        if update.is_none() {
+            log::info!("No updates available from any source");
         }
+        update
+    }
+    
+    /// Outcome of the last contact with each source, for `AppConfig::record_update_check`
+    pub fn source_status(&self) -> &BTreeMap<String, SourceStatus> {
+        &self.settings.source_status
+    }
+    
+    fn check_source(&mut self, source: &UpdateSource) -> Result<UpdateInfo, String> {
+        let output = self.run_check(source);
         
-        log::info!("No updates available from any source");
-        None
+        // Any answer from the manifest counts as contact; only errors mean the source is unhealthy
+        let error = match output {
+            Ok(UpdaterOutput::Error { ref message }) => Some(message.clone()),
+            Ok(_) => None,
+            Err(ref e) => Some(e.clone()),
+        };
+        let status = self.settings.source_status.entry(source.url.clone()).or_default();
+        record_contact(status, error, Utc::now());
+        
+        match output? {
+            // Skipped versions are still returned; callers decide whether to show them
+            UpdaterOutput::UpdateAvailable(release) | UpdaterOutput::UpdateSkipped(release) => {
+                Ok(UpdateInfo {
+                    version: release.version,
+                    url: release.url,
+                    checksum: release.checksum,
+                    size_bytes: release.size_bytes,
+                    breaking_changes: release.breaking_changes,
+                    release_date: release.release_date,
+                    changelog_url: release.changelog_url,
+                    min_compatible_version: release.min_compatible_version,
+                    source: source.clone(),
+                })
+            }
+            UpdaterOutput::TestVersionNotAllowed { version } => {
+                log::info!("Skipping test version {} (test versions disabled)", version);
+                Err("Test version not allowed".to_string())
+            }
+            UpdaterOutput::Incompatible { version, min_compatible_version } => {
+                log::warn!("Update {} requires at least v{} to install", version, min_compatible_version);
+                Err(format!("Update {} is not compatible with this version (requires {})",
+                            version, min_compatible_version))
+            }
+            UpdaterOutput::ArchitectureMismatch { version, available, installed } => {
+                log::warn!("Update {} is built for {} but this installation is {}", version, available, installed);
+                Err(format!("Update {} is only available for {}; download the {} build from {}",
+                            version, available, installed, source.url))
+            }
+            UpdaterOutput::UpToDate => Err("Already up to date".to_string()),
+            UpdaterOutput::Error { message } => Err(message),
+            other => Err(format!("Unexpected updater output: {:?}", other)),
+        }
     }
     
-    fn check_source(&self, manifest_url: &str) -> Result<UpdateInfo, String> {
+    /// Run `updater.exe --check` against `source` and parse what it reports
+    fn run_check(&self, source: &UpdateSource) -> Result<UpdaterOutput, String> {
         // Try to find updater.exe in multiple locations
         let updater_paths = vec![
             "updater.exe",
@@ -90,8 +138,12 @@ impl UpdateChecker {
             // Call updater to check for updates
             match Command::new(updater_path)
                 .arg("--check")
-                .arg(manifest_url)
+                .arg(&source.url)
                 .arg(get_current_version())
+                .arg(JSON_FLAG)
+                .env(UPDATE_HEADERS_ENV, source_headers_env(source))
+                .env(UPDATE_SOURCE_URL_ENV, &source.url)
+                .env(UPDATE_SETTINGS_ENV, self.settings_env())
                 .output()
             {
                 Ok(output) => {
@@ -105,51 +157,8 @@ impl UpdateChecker {
                     }
                     
                     // Parse updater output
-                    for line in stdout.lines() {
-                        if line.starts_with("UPDATE_AVAILABLE:") {
-                            let version = line.strip_prefix("UPDATE_AVAILABLE:").unwrap().to_string();
-                            
-                            // Check if it's a test version and if user allows them
-                            let is_test_version = version.contains('r');
-                            if is_test_version && !self.settings.allow_test_versions {
-                                log::info!("Skipping test version {} (test versions disabled)", version);
-                                return Err("Test version not allowed".to_string());
-                            }
-                            
-                            // Parse additional info from following lines
-                            let mut url = String::new();
-                            let mut checksum = String::new();
-                            let mut size = 0u64;
-                            let mut breaking = false;
-                            
-                            for info_line in stdout.lines() {
-                                if info_line.starts_with("URL:") {
-                                    url = info_line.strip_prefix("URL:").unwrap().to_string();
-                                } else if info_line.starts_with("CHECKSUM:") {
-                                    checksum = info_line.strip_prefix("CHECKSUM:").unwrap().to_string();
-                                } else if info_line.starts_with("SIZE:") {
-                                    size = info_line.strip_prefix("SIZE:").unwrap().parse().unwrap_or(0);
-                                } else if info_line.starts_with("BREAKING:") {
-                                    breaking = info_line.strip_prefix("BREAKING:").unwrap() == "true";
-                                }
-                            }
-                            
-                            return Ok(UpdateInfo {
-                                version,
-                                url,
-                                checksum,
-                                size_bytes: size,
-                                breaking_changes: breaking,
-                            });
-                        } else if line == "UP_TO_DATE" {
-                            return Err("Already up to date".to_string());
-                        }
-                    }
-                    
-                    if !stdout.is_empty() {
-                        log::error!("Updater output did not contain expected markers. Got {} bytes", stdout.len());
-                    }
-                    return Err("Failed to parse updater output".to_string());
+                    return UpdaterOutput::parse(&stdout)
+                        .map_err(|e| format!("{} ({})", e, describe_exit_status(output.status)));
                 }
                 Err(e) => {
                     last_error = format!("{}", e);
@@ -165,6 +174,16 @@ impl UpdateChecker {
         Err("Failed to find or execute updater".to_string())
     }
     
+    /// Serialize the update policy for the updater process (sources and their credentials left out)
+    fn settings_env(&self) -> String {
+        let policy = UpdateSettings {
+            sources: Vec::new(),
+            source_status: BTreeMap::new(),
+            ..self.settings.clone()
+        };
+        serde_json::to_string(&policy).unwrap_or_default()
+    }
+    
     pub fn download_update(&self, info: &UpdateInfo) -> Result<String, String> {
         log::info!("Downloading update v{}...", info.version);
         
@@ -173,37 +192,74 @@ impl UpdateChecker {
             .arg(&info.version)
             .arg(&info.url)
             .arg(&info.checksum)
+            .arg(JSON_FLAG)
+            .env(UPDATE_HEADERS_ENV, source_headers_env(&info.source))
+            .env(UPDATE_SOURCE_URL_ENV, &info.source.url)
+            .env(UPDATE_SETTINGS_ENV, self.settings_env())
             .output()
             .map_err(|e| format!("Failed to run updater: {}", e))?;
         
         let stdout = String::from_utf8_lossy(&output.stdout);
         
-        for line in stdout.lines() {
-            if line.starts_with("DOWNLOAD_COMPLETE:") {
-                let path = line.strip_prefix("DOWNLOAD_COMPLETE:").unwrap().to_string();
+        match UpdaterOutput::parse(&stdout) {
+            Ok(UpdaterOutput::DownloadComplete { path }) => {
                 log::info!("Download complete: {}", path);
-                return Ok(path);
+                Ok(path)
+            }
+            Ok(UpdaterOutput::Error { message }) => Err(format!("Download failed: {}", message)),
+            Ok(other) => Err(format!("Download failed: unexpected updater output {:?}", other)),
+            Err(e) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Err(format!("Download failed: {} ({}; {})", e, describe_exit_status(output.status), stderr.trim()))
             }
         }
-        
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!("Download failed: {}", stderr))
     }
     
     pub fn apply_update(&self, version: &str) -> Result<(), String> {
-        log::info!("Applying update v{}...", version);
+        if let Err(e) = ensure_newer(version, get_current_version()) {
+            log::warn!("Refusing to apply update: {}", e);
+            return Err(format!("Refusing to apply update: {}", e));
+        }
         
-        // Start updater to apply update
-        Command::new("updater.exe")
-            .arg("--apply")
-            .arg(version)
-            .arg(get_current_version())
-            .spawn()
-            .map_err(|e| format!("Failed to start updater: {}", e))?;
+        let policy = self.settings.restart_after_update;
+        if policy == RestartPolicy::Manual {
+            log::info!("Update v{} will be installed when DriveGuard is closed", version);
+            status::set_update_on_exit(version);
+            status::notify_localized("DriveGuard", "update_restart_to_finish", &[version], false);
+            return Ok(());
+        }
         
-        // Exit DriveGuard so updater can replace the executable
-        log::info!("Exiting to apply update...");
-        std::process::exit(0);
+        let wait_for_idle = policy == RestartPolicy::WhenIdle;
+        let idle_after = Duration::from_secs(self.settings.wait_after_interaction_minutes * 60);
+        if !status::is_backup_active() && (!wait_for_idle || crate::power::user_idle_time() >= idle_after) {
+            return start_updater(version, true);
+        }
+        
+        // Never restart in the middle of a backup; check again in a while
+        status::notify_localized("DriveGuard",
+                                 if wait_for_idle { "update_waiting_for_idle" } else { "update_waiting_for_backup" },
+                                 &[version], false);
+        let version = version.to_string();
+        thread::spawn(move || loop {
+            thread::sleep(APPLY_RETRY_INTERVAL);
+            if status::is_backup_active() || (wait_for_idle && crate::power::user_idle_time() < idle_after) {
+                continue;
+            }
+            if let Err(e) = start_updater(&version, true) {
+                log::error!("Failed to apply update v{}: {}", version, e);
+                return;
+            }
+        });
+        Ok(())
+    }
+    
+    /// Install the update chosen with restart_after_update = "manual" while DriveGuard exits
+    pub fn apply_update_on_exit() {
+        if let Some(version) = status::take_update_on_exit() {
+            if let Err(e) = start_updater(&version, false) {
+                log::error!("Failed to apply update v{}: {}", version, e);
+            }
+        }
     }
     
     pub fn update_last_interaction(&mut self) {
@@ -233,6 +289,37 @@ impl UpdateChecker {
     }
 }
 
+/// How often a deferred update checks whether DriveGuard may restart now
+const APPLY_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Start the updater to replace driveguard.exe. With `restart` it starts the new version and this
+/// process exits here; otherwise the caller is expected to exit on its own.
+fn start_updater(version: &str, restart: bool) -> Result<(), String> {
+    log::info!("Applying update v{}...", version);
+    
+    let mut command = Command::new("updater.exe");
+    command.arg("--apply").arg(version).arg(get_current_version());
+    if !restart {
+        command.arg("--no-restart");
+    }
+    command.spawn().map_err(|e| format!("Failed to start updater: {}", e))?;
+    
+    if restart {
+        // Exit DriveGuard so updater can replace the executable
+        log::info!("Exiting to apply update...");
+        std::process::exit(0);
+    }
+    Ok(())
+}
+
+/// The updater's exit status in words, for when its output couldn't be read
+fn describe_exit_status(status: std::process::ExitStatus) -> String {
+    match status.code() {
+        Some(code) => format!("updater exit code {}: {}", code, exit_code::describe(code)),
+        None => "updater was terminated".to_string(),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct UpdateInfo {
     pub version: String,
@@ -240,6 +327,79 @@ pub struct UpdateInfo {
     pub checksum: String,
     pub size_bytes: u64,
     pub breaking_changes: bool,
+    pub release_date: String, // As in the manifest; empty if the updater didn't report it
+    pub changelog_url: String,
+    pub min_compatible_version: String, // Oldest version that can update straight to this one
+    pub source: UpdateSource, // Where the update was found (needed for authenticated downloads)
+}
+
+/// Query all enabled `sources` (already sorted by priority) and return the newest update that is newer
+/// than `current`; between sources offering the same version the earlier one wins. A source offering the
+/// running version or an older one (misconfigured manifest, stale mirror) is passed over.
+fn newest_update(
+    sources: &[UpdateSource],
+    current: &str,
+    mut check: impl FnMut(&UpdateSource) -> Result<UpdateInfo, String>,
+) -> Option<UpdateInfo> {
+    let mut newest: Option<(Version, UpdateInfo)> = None;
+    
+    for source in sources.iter().filter(|s| s.enabled) {
+        log::info!("Trying update source: {} ({})", source.name, source.url);
+        
+        let info = match check(source) {
+            Ok(info) => info,
+            Err(e) => {
+                log::warn!("Failed to check {}: {}", source.name, e);
+                continue;
+            }
+        };
+        log::info!("{} reports v{}", source.name, info.version);
+        
+        if let Err(e) = ensure_newer(&info.version, current) {
+            log::warn!("Rejected update from {}: {}", source.name, e);
+            continue;
+        }
+        // ensure_newer has already parsed it
+        let Ok(version) = Version::parse(&info.version) else { continue };
+        let is_newest = match newest {
+            Some((ref best, _)) => version > *best,
+            None => true,
+        };
+        if is_newest {
+            newest = Some((version, info));
+        }
+    }
+    
+    let (_, info) = newest?;
+    log::info!("Found update from {}: v{}", info.source.name, info.version);
+    Some(info)
+}
+
+/// Note a successful contact with a source at `now`, or a failed one with its `error`
+fn record_contact(status: &mut SourceStatus, error: Option<String>, now: DateTime<Utc>) {
+    match error {
+        None => status.last_success = Some(now.to_rfc3339()),
+        Some(error) => {
+            status.last_failure = Some(now.to_rfc3339());
+            status.last_error = Some(error);
+        }
+    }
+}
+
+/// Only versions strictly greater than the running one may be offered or applied, whatever their source
+fn ensure_newer(version: &str, current: &str) -> Result<(), String> {
+    let offered = Version::parse(version).map_err(|e| format!("invalid update version {}: {}", version, e))?;
+    let running = Version::parse(current).map_err(|e| format!("invalid running version {}: {}", current, e))?;
+    
+    if offered <= running {
+        return Err(format!("v{} is not newer than the running v{}", version, current));
+    }
+    Ok(())
+}
+
+/// Serialize a source's extra request headers for the updater process
+fn source_headers_env(source: &UpdateSource) -> String {
+    serde_json::to_string(&source.request_headers()).unwrap_or_default()
 }
 
 pub fn start_update_checker_thread(config: std::sync::Arc<std::sync::Mutex<AppConfig>>) {
@@ -247,18 +407,120 @@ pub fn start_update_checker_thread(config: std::sync::Arc<std::sync::Mutex<AppCo
         loop {
             thread::sleep(Duration::from_secs(3600)); // Check every hour
             
-            if let Ok(cfg) = config.lock() {
-                let checker = UpdateChecker::new(&cfg);
+            // Don't hold the config lock across the network check
+            let mut checker = match config.lock() {
+                Ok(cfg) => UpdateChecker::new(&cfg),
+                Err(_) => return,
+            };
+            
+            if checker.should_check_now() {
+                let update = checker.check_for_updates();
+                if let Ok(mut cfg) = config.lock() {
+                    cfg.record_update_check(checker.source_status());
+                }
                 
-                if checker.should_check_now() {
-                    if let Some(update_info) = checker.check_for_updates() {
-                        log::info!("Update available: v{}", update_info.version);
-                        
-                        // TODO: Show notification to user
-                        // This will be integrated with the UI
-                    }
+                if let Some(update_info) = update {
+                    log::info!("Update available: v{}", update_info.version);
+                    
+                    // TODO: Show notification to user
+                    // This will be integrated with the UI
                 }
             }
         }
     });
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn source(name: &str, priority: u8) -> UpdateSource {
+        UpdateSource {
+            name: name.to_string(),
+            priority,
+            ..Default::default()
+        }
+    }
+    
+    fn offer(version: &str, source: &UpdateSource) -> Result<UpdateInfo, String> {
+        Ok(UpdateInfo {
+            version: version.to_string(),
+            url: String::new(),
+            checksum: String::new(),
+            size_bytes: 0,
+            breaking_changes: false,
+            release_date: String::new(),
+            changelog_url: String::new(),
+            min_compatible_version: String::new(),
+            source: source.clone(),
+        })
+    }
+    
+    #[test]
+    fn test_first_update_rejects_downgrades() {
+        let sources = vec![source("GitHub", 0), source("Mirror", 1)];
+        
+        // The preferred source can't be reached and the lower-priority mirror still serves an older release
+        let update = newest_update(&sources, "0.2.0", |s| match s.name.as_str() {
+            "GitHub" => Err("timed out".to_string()),
+            _ => offer("0.1.5", s),
+        });
+        assert!(update.is_none());
+        
+        // A stale "latest" on the preferred source doesn't hide a real update further down
+        let update = newest_update(&sources, "0.2.0", |s| match s.name.as_str() {
+            "GitHub" => offer("0.1.9", s),
+            _ => offer("0.3.0", s),
+        });
+        assert_eq!(update.unwrap().source.name, "Mirror");
+        
+        assert!(ensure_newer("0.2.0", "0.2.0").is_err());
+        assert!(ensure_newer("0.2.1", "0.2.0").is_ok());
+        assert!(ensure_newer("garbage", "0.2.0").is_err());
+    }
+    
+    #[test]
+    fn test_record_contact() {
+        let mut status = SourceStatus::default();
+        let monday = DateTime::parse_from_rfc3339("2025-11-17T09:00:00Z").unwrap().with_timezone(&Utc);
+        let tuesday = DateTime::parse_from_rfc3339("2025-11-18T09:00:00Z").unwrap().with_timezone(&Utc);
+        
+        record_contact(&mut status, None, monday);
+        record_contact(&mut status, Some("HTTP 503".to_string()), tuesday);
+        
+        // A failure doesn't erase when the source last worked
+        assert_eq!(status.last_success, Some(monday.to_rfc3339()));
+        assert_eq!(status.last_failure, Some(tuesday.to_rfc3339()));
+        assert_eq!(status.last_error.as_deref(), Some("HTTP 503"));
+    }
+    
+    #[test]
+    fn test_newest_update_across_sources() {
+        let sources = vec![source("GitHub", 0), source("Mirror", 1), source("Self-hosted", 2)];
+        let versions = |github: &'static str, mirror: &'static str, hosted: &'static str| {
+            move |s: &UpdateSource| match s.name.as_str() {
+                "GitHub" => offer(github, s),
+                "Mirror" => offer(mirror, s),
+                _ => offer(hosted, s),
+            }
+        };
+        
+        // A lower-priority source with a newer release wins
+        let update = newest_update(&sources, "0.1.0", versions("0.2.0", "0.3.0", "0.2.5")).unwrap();
+        assert_eq!((update.version.as_str(), update.source.name.as_str()), ("0.3.0", "Mirror"));
+        
+        // Same version on several sources: the highest priority one is used
+        let update = newest_update(&sources, "0.1.0", versions("0.2.0", "0.3.0", "0.3.0")).unwrap();
+        assert_eq!(update.source.name, "Mirror");
+        
+        // Versions are compared, not strings ("0.10.0" > "0.9.0")
+        let update = newest_update(&sources, "0.1.0", versions("0.9.0", "0.10.0", "0.1.0")).unwrap();
+        assert_eq!(update.version, "0.10.0");
+        
+        // Disabled sources aren't asked
+        let mut disabled = sources.clone();
+        disabled[1].enabled = false;
+        let update = newest_update(&disabled, "0.1.0", versions("0.2.0", "0.3.0", "0.2.5")).unwrap();
+        assert_eq!(update.version, "0.2.5");
+    }
+}
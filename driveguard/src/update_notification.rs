@@ -2,9 +2,26 @@ use native_windows_gui as nwg;
 use std::sync::{Arc, Mutex};
 use std::cell::RefCell;
 use std::thread;
+use lazy_static::lazy_static;
 use crate::update_checker::{UpdateInfo, UpdateChecker};
 use crate::config::AppConfig;
 
+lazy_static! {
+    // Version currently being offered; only one notification window is open at a time
+    static ref ACTIVE_PROMPT: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// Clears ACTIVE_PROMPT when the notification thread ends, however it ends
+struct ActivePromptGuard;
+
+impl Drop for ActivePromptGuard {
+    fn drop(&mut self) {
+        if let Ok(mut active) = ACTIVE_PROMPT.lock() {
+            *active = None;
+        }
+    }
+}
+
 pub struct UpdateNotificationWindow {
     window: nwg::Window,
     
@@ -25,7 +42,24 @@ pub struct UpdateNotificationWindow {
 
 impl UpdateNotificationWindow {
     pub fn show(update_info: UpdateInfo, config: Arc<Mutex<AppConfig>>) {
+        if crate::status::is_headless() {
+            log::info!("Update v{} available (no desktop to show the notification on)", update_info.version);
+            return;
+        }
+        
+        {
+            let mut active = ACTIVE_PROMPT.lock().unwrap();
+            if let Some(ref version) = *active {
+                log::info!("Update notification for v{} is already open, not showing v{}",
+                           version, update_info.version);
+                return;
+            }
+            *active = Some(update_info.version.clone());
+        }
+        
         thread::spawn(move || {
+            let _active_guard = ActivePromptGuard;
+            
             nwg::init().expect("Failed to init NWG");
             
             let update_info = Arc::new(Mutex::new(update_info));
@@ -69,15 +103,9 @@ impl UpdateNotificationWindow {
                 .build(&mut label_size)
                 .expect("Failed to build size label");
             
-            let breaking_text = if info.breaking_changes {
-                "\n⚠ This update contains breaking changes. Please review the changelog."
-            } else {
-                "\nThis is a compatible update and can be installed safely."
-            };
-            
             let mut label_info = Default::default();
             nwg::Label::builder()
-                .text(&format!("Changes:\n- Bug fixes and improvements\n- Enhanced performance{}\n\nTo disable automatic updates, go to Settings > Updates", breaking_text))
+                .text(&release_text(&info))
                 .parent(&window)
                 .position((20, 120))
                 .size((460, 100))
@@ -129,17 +157,19 @@ impl UpdateNotificationWindow {
             
             // Setup event handlers
             let app_clone = app.clone();
-            let handler = nwg::full_bind_event_handler(&app.window.handle, move |evt, _evt_data, handle| {
+            let handler = nwg::full_bind_event_handler(&app.window.handle, move |evt, evt_data, handle| {
                 use nwg::Event;
                 
-                if handle == app_clone.btn_update_now {
+                // Keys reach whichever control has the focus
+                if let Event::OnKeyPress = evt {
+                    app_clone.on_key_press(evt_data.on_key());
+                } else if handle == app_clone.btn_update_now {
                     if let Event::OnButtonClick = evt {
                         app_clone.start_update();
                     }
                 } else if handle == app_clone.btn_ask_later {
                     if let Event::OnButtonClick = evt {
-                        log::info!("Update postponed by user");
-                        nwg::stop_thread_dispatch();
+                        app_clone.ask_later();
                     }
                 } else if handle == app_clone.btn_skip_version {
                     if let Event::OnButtonClick = evt {
@@ -177,13 +207,13 @@ impl UpdateNotificationWindow {
                 log::info!("Download complete: {}", path);
                 self.label_title.set_text("Download complete! Applying update...");
                 
-                // Apply update (this will exit DriveGuard)
+                // Apply update (exits DriveGuard unless restart_after_update defers it)
                 if let Err(e) = checker.apply_update(&info.version) {
                     log::error!("Failed to apply update: {}", e);
                     nwg::modal_error_message(&self.window, "Update Failed", 
                         &format!("Failed to apply update:\n\n{}", e));
                 } else {
-                    // This shouldn't be reached as apply_update exits the app
+                    // Deferred until a backup finishes, the computer is idle or DriveGuard is closed
                     nwg::stop_thread_dispatch();
                 }
             }
@@ -200,18 +230,26 @@ impl UpdateNotificationWindow {
         }
     }
     
+    /// Esc asks later and Enter updates now, unless the buttons are disabled during the download
+    fn on_key_press(&self, key: u32) {
+        match key {
+            nwg::keys::ESCAPE if self.btn_ask_later.enabled() => self.ask_later(),
+            nwg::keys::RETURN if self.btn_update_now.enabled() => self.start_update(),
+            _ => {}
+        }
+    }
+    
+    fn ask_later(&self) {
+        log::info!("Update postponed by user");
+        nwg::stop_thread_dispatch();
+    }
+    
     fn skip_version(&self) {
         let info = self.update_info.lock().unwrap();
         log::info!("User chose to skip version {}", info.version);
         
-        // Add version to skipped list
         if let Ok(mut config) = self.config.lock() {
-            if let Some(ref mut update_settings) = config.general.update_settings {
-                if !update_settings.skipped_versions.contains(&info.version) {
-                    update_settings.skipped_versions.push(info.version.clone());
-                    config.save();
-                }
-            }
+            config.skip_version(&info.version);
         }
         
         nwg::stop_thread_dispatch();
@@ -225,4 +263,26 @@ impl Drop for UpdateNotificationWindow {
             nwg::unbind_event_handler(h);
         }
     }
-}
\ No newline at end of file
+}
+/// Release details from the manifest: date, breaking status and where to read the changelog
+fn release_text(info: &UpdateInfo) -> String {
+    let mut lines = Vec::new();
+    if !info.release_date.is_empty() {
+        lines.push(format!("Released: {}", info.release_date));
+    }
+    lines.push(if info.breaking_changes {
+        "⚠ This update contains breaking changes. Please review the changelog.".to_string()
+    } else {
+        "This is a compatible update and can be installed safely.".to_string()
+    });
+    if !info.min_compatible_version.is_empty() {
+        lines.push(format!("Installs over v{} and newer", info.min_compatible_version));
+    }
+    if !info.changelog_url.is_empty() {
+        lines.push(format!("Changelog: {}", info.changelog_url));
+    }
+    
+    lines.push(String::new());
+    lines.push("To disable automatic updates, go to Settings > Updates".to_string());
+    lines.join("\n")
+}
@@ -0,0 +1,57 @@
+// Streaming checksum helpers
+// Files are hashed in chunks so large updates/backups don't need to fit in RAM
+
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+/// Compute the lowercase hex SHA-256 of everything readable from `reader`
+pub fn sha256_reader<R: Read>(reader: &mut R) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(reader, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Compute the lowercase hex SHA-256 of a file without loading it into memory
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    sha256_reader(&mut reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    
+    #[test]
+    fn test_sha256_file_large() {
+        let path = std::env::temp_dir().join(format!("driveguard_hash_test_{}.bin", std::process::id()));
+        
+        // 16 MB of non-uniform data, written in chunks
+        let chunk: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let mut expected = Sha256::new();
+        {
+            let mut file = File::create(&path).unwrap();
+            for _ in 0..16 {
+                file.write_all(&chunk).unwrap();
+                expected.update(&chunk);
+            }
+        }
+        
+        let checksum = sha256_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        
+        assert_eq!(checksum, format!("{:x}", expected.finalize()));
+        assert_eq!(checksum.len(), 64);
+    }
+    
+    #[test]
+    fn test_sha256_reader_known_value() {
+        let mut data: &[u8] = b"abc";
+        assert_eq!(
+            sha256_reader(&mut data).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
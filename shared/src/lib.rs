@@ -1,4 +1,8 @@
 // DriveGuard Shared Library
 // Common code shared between main app and updater
 
-pub mod manifest;
\ No newline at end of file
+pub mod checksum;
+pub mod manifest;
+pub mod pe;
+pub mod update_state;
+pub mod updater_output;
\ No newline at end of file
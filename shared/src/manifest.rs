@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Complete update manifest from server
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,8 +30,20 @@ pub struct VersionInfo {
     pub patch_checksum: Option<String>,
     #[serde(default)]
     pub patch_required_from: Vec<String>,
+    
+    // "x86", "x64" or "arm64"; unset means the build runs everywhere we ship
+    #[serde(default)]
+    pub architecture: Option<String>,
 }
 
+/// Environment variable used to hand a source's request headers to the updater
+/// (kept out of the command line so tokens don't show up in process listings)
+pub const UPDATE_HEADERS_ENV: &str = "DRIVEGUARD_UPDATE_HEADERS";
+
+/// Environment variable with the URL of the source those headers belong to; the updater only
+/// sends them to URLs with the same scheme, host and port
+pub const UPDATE_SOURCE_URL_ENV: &str = "DRIVEGUARD_UPDATE_SOURCE_URL";
+
 /// Update source configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateSource {
@@ -39,6 +51,12 @@ pub struct UpdateSource {
     pub url: String,
     pub enabled: bool,
     pub priority: u8, // 0 = highest
+    
+    // Credentials for private/self-hosted sources
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub auth_token: Option<String>, // Sent as "Authorization: Bearer <token>"
 }
 
 impl Default for UpdateSource {
@@ -48,7 +66,42 @@ impl Default for UpdateSource {
             url: "https://api.github.com/repos/ArsenijN/driveguard/releases".to_string(),
             enabled: true,
             priority: 0,
+            headers: HashMap::new(),
+            auth_token: None,
+        }
+    }
+}
+
+impl UpdateSource {
+    /// All extra headers to send to this source, including the bearer token
+    pub fn request_headers(&self) -> Vec<(String, String)> {
+        let mut headers: Vec<(String, String)> = self.headers
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        headers.sort();
+        
+        if let Some(ref token) = self.auth_token {
+            if !token.is_empty() {
+                headers.push(("Authorization".to_string(), format!("Bearer {}", token)));
+            }
         }
+        
+        headers
+    }
+}
+
+/// Hide credentials when a header value is written to the log
+pub fn redact_header_value(name: &str, value: &str) -> String {
+    let name = name.to_ascii_lowercase();
+    let sensitive = ["authorization", "token", "key", "secret", "cookie"]
+        .iter()
+        .any(|s| name.contains(s));
+    
+    if sensitive {
+        "<redacted>".to_string()
+    } else {
+        value.to_string()
     }
 }
 
@@ -64,6 +117,47 @@ pub struct UpdateSettings {
     pub skipped_versions: Vec<String>,
     pub allow_test_versions: bool, // Enable beta/RC versions
     pub sources: Vec<UpdateSource>,
+    
+    // Anonymous usage report (DriveGuard version, OS, architecture) sent to telemetry_url with
+    // each update check; None until the user has answered the consent prompt
+    #[serde(default)]
+    pub telemetry_consent: Option<bool>,
+    #[serde(default)]
+    pub telemetry_url: String,
+    
+    // Accept the system certificate store next to the embedded CA, for corporate proxies that
+    // re-sign TLS traffic (any CA trusted by Windows can then vouch for the update server)
+    #[serde(default)]
+    pub trust_system_certificates: bool,
+    
+    #[serde(default)]
+    pub restart_after_update: RestartPolicy,
+    
+    // Outcome of the last contact with each source, keyed by its URL; kept up to date by DriveGuard
+    #[serde(default)]
+    pub source_status: BTreeMap<String, SourceStatus>,
+}
+
+/// How the last update checks against one source went, to tell a source that can't be reached
+/// from one that simply has nothing new
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceStatus {
+    pub last_success: Option<String>, // ISO 8601; the source answered, with or without an update
+    pub last_failure: Option<String>, // ISO 8601
+    pub last_error: Option<String>,   // Why the last failed check failed
+}
+
+/// When DriveGuard switches to a downloaded update; never while a backup is running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartPolicy {
+    /// Install and restart right away
+    #[default]
+    Immediately,
+    /// Once the computer hasn't been used for wait_after_interaction_minutes
+    WhenIdle,
+    /// When DriveGuard is closed from the tray; the user starts the new version
+    Manual,
 }
 
 impl Default for UpdateSettings {
@@ -83,24 +177,107 @@ impl Default for UpdateSettings {
                     url: "https://github.com/ArsenijN/driveguard/releases".to_string(),
                     enabled: true,
                     priority: 0,
+                    headers: HashMap::new(),
+                    auth_token: None,
                 },
                 UpdateSource {
                     name: "Custom Server".to_string(),
                     url: "https://arseniusgen.uk.to/projects/driveguard/manifest.json".to_string(),
                     enabled: true,
                     priority: 1,
+                    headers: HashMap::new(),
+                    auth_token: None,
                 },
 				UpdateSource {
                     name: "Custom Server".to_string(),
                     url: "http://arseniusgen.uk.to/projects/driveguard/manifest.json".to_string(),
                     enabled: true,
                     priority: 2,
+                    headers: HashMap::new(),
+                    auth_token: None,
                 },
             ],
+            telemetry_consent: None,
+            telemetry_url: String::new(), // No endpoint: nothing is sent and nobody is asked
+            trust_system_certificates: false,
+            restart_after_update: RestartPolicy::Immediately,
+            source_status: BTreeMap::new(),
         }
     }
 }
 
+impl UpdateSettings {
+    /// Only report when the user agreed and there is somewhere to report to
+    pub fn telemetry_allowed(&self) -> bool {
+        self.telemetry_consent == Some(true) && !self.telemetry_url.is_empty()
+    }
+    
+    /// The consent prompt is shown once, and only if update checks could actually send a report
+    pub fn needs_telemetry_consent(&self) -> bool {
+        self.enabled && self.telemetry_consent.is_none() && !self.telemetry_url.is_empty()
+    }
+}
+
+/// Environment variable used to hand the update policy (test versions, skipped
+/// versions) to the updater so it can decide which update to offer
+pub const UPDATE_SETTINGS_ENV: &str = "DRIVEGUARD_UPDATE_SETTINGS";
+
+/// What to do with the latest version listed in a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateDecision {
+    /// Offer this version to the user
+    Offer(String),
+    /// The latest version is not newer than the running one
+    UpToDate,
+    /// Newer, but a test (RC) build and test versions are disabled
+    TestVersionNotAllowed(String),
+    /// Newer, but the user chose to skip it
+    Skipped(String),
+    /// Newer, but it can't be installed over the running version directly
+    Incompatible { version: String, min_compatible_version: String },
+}
+
+/// Decide which update, if any, to offer for the running version
+pub fn select_update(manifest: &UpdateManifest, current_version: &str, settings: &UpdateSettings) -> Result<UpdateDecision, String> {
+    let current = Version::parse(current_version)
+        .map_err(|e| format!("Failed to parse current version: {}", e))?;
+    let latest = Version::parse(&manifest.latest_version)
+        .map_err(|e| format!("Failed to parse latest version: {}", e))?;
+    let version = manifest.latest_version.clone();
+    
+    if latest <= current {
+        return Ok(UpdateDecision::UpToDate);
+    }
+    
+    // Without its entry there is nothing to download; say so instead of offering an empty release
+    let info = manifest.versions.get(&version).ok_or_else(|| {
+        format!("Malformed manifest: latest_version {} has no entry in versions", version)
+    })?;
+    if info.download_url.trim().is_empty() || info.checksum_sha256.trim().is_empty() {
+        return Err(format!("Malformed manifest: version {} has no download_url or checksum_sha256", version));
+    }
+    
+    if latest.is_test() && !settings.allow_test_versions {
+        return Ok(UpdateDecision::TestVersionNotAllowed(version));
+    }
+    
+    if settings.skipped_versions.iter().any(|v| v == &version) {
+        return Ok(UpdateDecision::Skipped(version));
+    }
+    
+    // An unparsable minimum is treated as a manifest error rather than ignored
+    let min = Version::parse(&info.min_compatible_version)
+        .map_err(|e| format!("Invalid min_compatible_version for {}: {}", version, e))?;
+    if current < min {
+        return Ok(UpdateDecision::Incompatible {
+            version,
+            min_compatible_version: info.min_compatible_version.clone(),
+        });
+    }
+    
+    Ok(UpdateDecision::Offer(version))
+}
+
 /// Parse semantic version string with optional release candidate suffix
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Version {
@@ -157,6 +334,11 @@ impl Version {
     pub fn is_compatible_with(&self, other: &Version) -> bool {
         // Same major version = compatible
         // Test versions are compatible with their stable counterparts
+        // Before 1.0 every minor release may break compatibility
+        if self.major == 0 || other.major == 0 {
+            return self.major == other.major && self.minor == other.minor;
+        }
+        
         self.major == other.major
     }
     
@@ -201,4 +383,85 @@ mod tests {
         // Test base version
         assert_eq!(v4.base_version(), Version::parse("0.1.3").unwrap());
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_source_headers_and_redaction() {
+        let mut source = UpdateSource::default();
+        assert!(source.request_headers().is_empty());
+        
+        source.headers.insert("X-Channel".to_string(), "stable".to_string());
+        source.auth_token = Some("ghp_secret".to_string());
+        
+        let headers = source.request_headers();
+        assert!(headers.contains(&("X-Channel".to_string(), "stable".to_string())));
+        assert!(headers.contains(&("Authorization".to_string(), "Bearer ghp_secret".to_string())));
+        
+        assert_eq!(redact_header_value("Authorization", "Bearer ghp_secret"), "<redacted>");
+        assert_eq!(redact_header_value("X-Api-Key", "abc"), "<redacted>");
+        assert_eq!(redact_header_value("X-Channel", "stable"), "stable");
+    }
+    
+    fn version_info(download_url: &str) -> VersionInfo {
+        VersionInfo {
+            release_date: "2025-11-19".to_string(),
+            breaking_changes: false,
+            min_compatible_version: "0.1.0".to_string(),
+            download_url: download_url.to_string(),
+            checksum_sha256: "ab".repeat(32),
+            changelog_url: String::new(),
+            file_size_bytes: 1024,
+            has_patch: false,
+            patch_url: None,
+            patch_checksum: None,
+            patch_required_from: Vec::new(),
+            architecture: None,
+        }
+    }
+    
+    #[test]
+    fn test_select_update_requires_latest_entry() {
+        let settings = UpdateSettings::default();
+        let mut manifest = UpdateManifest {
+            latest_version: "0.3.0".to_string(),
+            versions: HashMap::from([("0.2.0".to_string(), version_info("https://example.com/0.2.0.exe"))]),
+        };
+        
+        let error = select_update(&manifest, "0.1.0", &settings).unwrap_err();
+        assert!(error.contains("0.3.0") && error.contains("no entry"), "{}", error);
+        
+        // Nothing to download either way when already up to date
+        assert_eq!(select_update(&manifest, "0.3.0", &settings), Ok(UpdateDecision::UpToDate));
+        
+        manifest.versions.insert("0.3.0".to_string(), version_info(""));
+        assert!(select_update(&manifest, "0.1.0", &settings).unwrap_err().contains("download_url"));
+        
+        manifest.versions.insert("0.3.0".to_string(), version_info("https://example.com/0.3.0.exe"));
+        assert_eq!(select_update(&manifest, "0.1.0", &settings), Ok(UpdateDecision::Offer("0.3.0".to_string())));
+    }
+    
+    #[test]
+    fn test_telemetry_consent() {
+        // Off by default, and not even asked about without an endpoint
+        let mut settings = UpdateSettings::default();
+        assert!(!settings.telemetry_allowed());
+        assert!(!settings.needs_telemetry_consent());
+        
+        // Settings saved before telemetry existed load with it off
+        let mut json: serde_json::Value = serde_json::to_value(&settings).unwrap();
+        json.as_object_mut().unwrap().remove("telemetry_consent");
+        json.as_object_mut().unwrap().remove("telemetry_url");
+        let old: UpdateSettings = serde_json::from_value(json).unwrap();
+        assert_eq!(old.telemetry_consent, None);
+        
+        settings.telemetry_url = "https://example.com/driveguard/telemetry".to_string();
+        assert!(settings.needs_telemetry_consent());
+        assert!(!settings.telemetry_allowed());
+        
+        settings.telemetry_consent = Some(false);
+        assert!(!settings.needs_telemetry_consent());
+        assert!(!settings.telemetry_allowed());
+        
+        settings.telemetry_consent = Some(true);
+        assert!(settings.telemetry_allowed());
+    }
+}
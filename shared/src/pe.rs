@@ -0,0 +1,90 @@
+// Minimal PE (Windows executable) header reading
+// Used to make sure an update is a real executable built for the installed architecture
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+// IMAGE_FILE_HEADER.Machine values
+pub const MACHINE_X86: u16 = 0x014c;
+pub const MACHINE_X64: u16 = 0x8664;
+pub const MACHINE_ARM64: u16 = 0xaa64;
+
+/// Read the machine type from a PE file's COFF header
+pub fn read_machine(path: &Path) -> Result<u16, String> {
+    let mut file = File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    machine_from_reader(&mut file)
+        .map_err(|e| format!("{} is not a valid Windows executable: {}", path.display(), e))
+}
+
+pub fn machine_from_reader<R: Read + Seek>(reader: &mut R) -> Result<u16, String> {
+    let mut dos_header = [0u8; 64];
+    reader.read_exact(&mut dos_header).map_err(|_| "file too small".to_string())?;
+    
+    if &dos_header[0..2] != b"MZ" {
+        return Err("missing MZ signature".to_string());
+    }
+    
+    // e_lfanew: offset of the PE signature
+    let pe_offset = u32::from_le_bytes([dos_header[60], dos_header[61], dos_header[62], dos_header[63]]);
+    reader.seek(SeekFrom::Start(pe_offset as u64)).map_err(|e| e.to_string())?;
+    
+    let mut signature = [0u8; 6];
+    reader.read_exact(&mut signature).map_err(|_| "truncated PE header".to_string())?;
+    
+    if &signature[0..4] != b"PE\0\0" {
+        return Err("missing PE signature".to_string());
+    }
+    
+    Ok(u16::from_le_bytes([signature[4], signature[5]]))
+}
+
+/// Architecture name as used in the manifest's `architecture` field
+pub fn machine_name(machine: u16) -> &'static str {
+    match machine {
+        MACHINE_X86 => "x86",
+        MACHINE_X64 => "x64",
+        MACHINE_ARM64 => "arm64",
+        _ => "unknown",
+    }
+}
+
+/// Architecture this binary was built for, in manifest naming
+pub fn current_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86" => "x86",
+        "x86_64" => "x64",
+        "aarch64" => "arm64",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    
+    fn fake_pe(machine: u16) -> Vec<u8> {
+        let mut data = vec![0u8; 0x80];
+        data[0..2].copy_from_slice(b"MZ");
+        data[60..64].copy_from_slice(&0x40u32.to_le_bytes());
+        data[0x40..0x44].copy_from_slice(b"PE\0\0");
+        data[0x44..0x46].copy_from_slice(&machine.to_le_bytes());
+        data
+    }
+    
+    #[test]
+    fn test_read_machine() {
+        assert_eq!(machine_from_reader(&mut Cursor::new(fake_pe(MACHINE_X64))), Ok(MACHINE_X64));
+        assert_eq!(machine_name(machine_from_reader(&mut Cursor::new(fake_pe(MACHINE_X86))).unwrap()), "x86");
+        assert_eq!(machine_name(MACHINE_ARM64), "arm64");
+        
+        // Not an executable
+        assert!(machine_from_reader(&mut Cursor::new(b"<html>404 Not Found</html>".to_vec())).is_err());
+        
+        let mut broken = fake_pe(MACHINE_X64);
+        broken[0x40] = b'X';
+        assert!(machine_from_reader(&mut Cursor::new(broken)).is_err());
+    }
+}
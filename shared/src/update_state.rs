@@ -0,0 +1,113 @@
+// Update state markers shared between the updater and the main app
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+/// Downloads, backups of replaced versions and markers, next to driveguard.exe when that folder is writable
+pub const UPDATES_DIR: &str = "updates";
+
+/// Overrides the updates folder; set by the updater for its elevated replace step, which may run as another user
+pub const UPDATES_DIR_ENV: &str = "DRIVEGUARD_UPDATES_DIR";
+
+/// Written by the updater when an update was applied but DriveGuard couldn't be restarted
+pub const NO_RESTART_MARKER_FILE: &str = "update_applied_no_restart.txt";
+
+/// Written by the updater when an update couldn't be applied and the old version was restarted
+pub const APPLY_FAILED_MARKER_FILE: &str = "update_apply_failed.txt";
+
+/// Written by the updater after replacing the executable, removed by DriveGuard once the new version has run a while
+pub const PENDING_UPDATE_FILE: &str = "pending_update.json";
+
+/// Launches of a new version that may end before it counts as working; the next one rolls back
+pub const MAX_UPDATE_LAUNCHES: u32 = 3;
+
+/// An update that was applied but hasn't proven itself yet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingUpdate {
+    pub version: String,
+    pub previous_version: String, // Kept in `updates/v<previous_version>`
+    #[serde(default)]
+    pub launches: u32,
+    #[serde(default)]
+    pub rollback_started: bool, // Set before rolling back so a failed rollback isn't retried forever
+}
+
+/// Where updates are downloaded and old versions kept: `updates` in the install folder, or
+/// `%LOCALAPPDATA%\DriveGuard\updates` when the install folder is write-protected (e.g. Program Files)
+pub fn updates_dir() -> PathBuf {
+    if let Some(dir) = env::var_os(UPDATES_DIR_ENV).filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    
+    if is_dir_writable(Path::new(".")) {
+        return PathBuf::from(UPDATES_DIR);
+    }
+    
+    match env::var_os("LOCALAPPDATA") {
+        Some(local) => PathBuf::from(local).join("DriveGuard").join(UPDATES_DIR),
+        None => PathBuf::from(UPDATES_DIR),
+    }
+}
+
+/// Check whether files can be created in `dir` by actually creating (and removing) one
+pub fn is_dir_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".driveguard_write_test_{}", std::process::id()));
+    match fs::File::create(&probe) {
+        Ok(_) => {
+            fs::remove_file(&probe).ok();
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Record that `version` was applied without a restart
+pub fn write_no_restart_marker(version: &str) -> io::Result<()> {
+    write_marker(NO_RESTART_MARKER_FILE, version)
+}
+
+/// Read and remove the no-restart marker, returning the applied version if present
+pub fn take_no_restart_marker() -> Option<String> {
+    take_marker(NO_RESTART_MARKER_FILE).map(|version| version.trim().to_string())
+}
+
+/// Record why applying an update failed, for DriveGuard to show once it's running again
+pub fn write_apply_failed_marker(reason: &str) -> io::Result<()> {
+    write_marker(APPLY_FAILED_MARKER_FILE, reason)
+}
+
+/// Read and remove the apply-failed marker, returning the reason if present
+pub fn take_apply_failed_marker() -> Option<String> {
+    take_marker(APPLY_FAILED_MARKER_FILE).map(|reason| reason.trim().to_string())
+}
+
+pub fn write_pending_update(pending: &PendingUpdate) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(pending)?;
+    write_marker(PENDING_UPDATE_FILE, &json)
+}
+
+/// The pending update marker, if there is a readable one
+pub fn read_pending_update() -> Option<PendingUpdate> {
+    let contents = fs::read_to_string(updates_dir().join(PENDING_UPDATE_FILE)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+pub fn clear_pending_update() {
+    fs::remove_file(updates_dir().join(PENDING_UPDATE_FILE)).ok();
+}
+
+fn write_marker(name: &str, contents: &str) -> io::Result<()> {
+    let dir = updates_dir();
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(name), contents)
+}
+
+fn take_marker(name: &str) -> Option<String> {
+    let path = updates_dir().join(name);
+    let contents = fs::read_to_string(&path).ok()?;
+    fs::remove_file(&path).ok();
+    Some(contents)
+}
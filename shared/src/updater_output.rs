@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+
+/// Flag that makes the updater print a single JSON `UpdaterOutput` instead of `KEY:value` lines
+pub const JSON_FLAG: &str = "--json";
+
+/// Exit codes of the updater, the same for every command. The JSON or `KEY:value` output has the
+/// details; the code alone is enough for scripts and for callers that can't parse the output.
+pub mod exit_code {
+    /// Done: up to date, downloaded, applied or rolled back
+    pub const SUCCESS: i32 = 0;
+    /// Any failure without a more specific code
+    pub const FAILURE: i32 = 1;
+    /// The server couldn't be reached or answered with an error
+    pub const NETWORK: i32 = 2;
+    /// The download doesn't match the checksum from the manifest
+    pub const CHECKSUM: i32 = 3;
+    /// Nothing at the URL (HTTP 404), or no downloaded update or backup to apply or roll back
+    pub const NOT_FOUND: i32 = 4;
+    /// The manifest isn't valid or lacks the version it names as latest
+    pub const INVALID_MANIFEST: i32 = 5;
+    /// Reading or writing a local file failed
+    pub const FILE_SYSTEM: i32 = 6;
+    /// Missing arguments or an unknown command
+    pub const USAGE: i32 = 7;
+    /// `--check`: a newer version can be installed
+    pub const UPDATE_AVAILABLE: i32 = 10;
+    /// `--check`: a newer version exists, but the user skipped it
+    pub const UPDATE_SKIPPED: i32 = 11;
+    /// `--check`: a newer version exists, but can't be installed here (test version, too old, other CPU).
+    /// `--apply`: the downloaded version is built for another CPU; nothing was changed
+    pub const UPDATE_NOT_INSTALLABLE: i32 = 12;
+    
+    /// Every code with a short description, for usage text and logs
+    pub const ALL: &[(i32, &str)] = &[
+        (SUCCESS, "success or up to date"),
+        (FAILURE, "failed"),
+        (NETWORK, "network or server error"),
+        (CHECKSUM, "checksum mismatch"),
+        (NOT_FOUND, "not found"),
+        (INVALID_MANIFEST, "invalid manifest"),
+        (FILE_SYSTEM, "file error"),
+        (USAGE, "invalid arguments"),
+        (UPDATE_AVAILABLE, "update available"),
+        (UPDATE_SKIPPED, "update skipped"),
+        (UPDATE_NOT_INSTALLABLE, "update can't be installed"),
+    ];
+    
+    pub fn describe(code: i32) -> &'static str {
+        ALL.iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, description)| *description)
+            .unwrap_or("unknown exit code")
+    }
+}
+
+/// Release the updater found, as needed to download and present it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseDetails {
+    pub version: String,
+    pub url: String,
+    pub checksum: String,
+    pub size_bytes: u64,
+    pub breaking_changes: bool,
+    pub is_test: bool,
+    // From the manifest's version entry; empty when reported by an older updater
+    #[serde(default)]
+    pub release_date: String,
+    #[serde(default)]
+    pub changelog_url: String,
+    #[serde(default)]
+    pub min_compatible_version: String,
+}
+
+/// Result of an updater `--check` or `--download` run in JSON mode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum UpdaterOutput {
+    UpdateAvailable(ReleaseDetails),
+    /// Newer, but skipped by the user; still reported in full so a manual check can offer it
+    UpdateSkipped(ReleaseDetails),
+    TestVersionNotAllowed { version: String },
+    Incompatible { version: String, min_compatible_version: String },
+    /// Newer, but built for a different CPU architecture than the installed exe
+    ArchitectureMismatch { version: String, available: String, installed: String },
+    UpToDate,
+    DownloadComplete { path: String },
+    Error { message: String },
+}
+
+impl UpdaterOutput {
+    /// Exit code the updater ends with after printing this; errors use the more specific
+    /// code of their cause, `FAILURE` is only the fallback
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            UpdaterOutput::UpdateAvailable(_) => exit_code::UPDATE_AVAILABLE,
+            UpdaterOutput::UpdateSkipped(_) => exit_code::UPDATE_SKIPPED,
+            UpdaterOutput::TestVersionNotAllowed { .. }
+            | UpdaterOutput::Incompatible { .. }
+            | UpdaterOutput::ArchitectureMismatch { .. } => exit_code::UPDATE_NOT_INSTALLABLE,
+            UpdaterOutput::UpToDate | UpdaterOutput::DownloadComplete { .. } => exit_code::SUCCESS,
+            UpdaterOutput::Error { .. } => exit_code::FAILURE,
+        }
+    }
+    
+    /// Find the JSON result in the updater's stdout, falling back to the legacy
+    /// `KEY:value` lines printed by updaters that predate `--json`
+    pub fn parse(stdout: &str) -> Result<Self, String> {
+        let line = stdout
+            .lines()
+            .rev()
+            .map(str::trim)
+            .find(|l| l.starts_with('{'));
+        
+        match line {
+            Some(line) => serde_json::from_str(line).map_err(|e| format!("Invalid updater output: {}", e)),
+            None => Self::parse_legacy(stdout),
+        }
+    }
+    
+    /// Parse the legacy `KEY:value` lines
+    pub fn parse_legacy(stdout: &str) -> Result<Self, String> {
+        let mut status = None;
+        let mut release = ReleaseDetails::default();
+        
+        for line in stdout.lines() {
+            let line = line.trim_end_matches('\r');
+            if line == "UP_TO_DATE" {
+                return Ok(UpdaterOutput::UpToDate);
+            }
+            
+            let (key, value) = match split_legacy_line(line) {
+                Some(kv) => kv,
+                None => continue,
+            };
+            
+            match key {
+                "UPDATE_AVAILABLE" | "UPDATE_SKIPPED" => {
+                    status = Some(key);
+                    release.version = value;
+                }
+                "URL" => release.url = value,
+                "CHECKSUM" => release.checksum = value,
+                "SIZE" => release.size_bytes = value.parse().unwrap_or(0),
+                "BREAKING" => release.breaking_changes = value == "true",
+                "IS_TEST" => release.is_test = value == "true",
+                "RELEASE_DATE" => release.release_date = value,
+                "CHANGELOG_URL" => release.changelog_url = value,
+                "MIN_COMPATIBLE" => release.min_compatible_version = value,
+                "TEST_VERSION_NOT_ALLOWED" => return Ok(UpdaterOutput::TestVersionNotAllowed { version: value }),
+                "INCOMPATIBLE" => {
+                    let (version, min) = value.split_once(':').unwrap_or((&value, ""));
+                    return Ok(UpdaterOutput::Incompatible {
+                        version: version.to_string(),
+                        min_compatible_version: min.to_string(),
+                    });
+                }
+                "ARCHITECTURE_MISMATCH" => {
+                    let mut parts = value.splitn(3, ':');
+                    return Ok(UpdaterOutput::ArchitectureMismatch {
+                        version: parts.next().unwrap_or_default().to_string(),
+                        available: parts.next().unwrap_or_default().to_string(),
+                        installed: parts.next().unwrap_or_default().to_string(),
+                    });
+                }
+                "DOWNLOAD_COMPLETE" => return Ok(UpdaterOutput::DownloadComplete { path: value }),
+                _ => {}
+            }
+        }
+        
+        match status {
+            Some("UPDATE_AVAILABLE") => Ok(UpdaterOutput::UpdateAvailable(release)),
+            Some(_) => Ok(UpdaterOutput::UpdateSkipped(release)),
+            None => Err("Failed to parse updater output".to_string()),
+        }
+    }
+}
+
+/// Split a legacy `KEY:value` line on its first colon only, so values may contain
+/// colons (`https://...`, `C:\...`); the value is unescaped
+pub fn split_legacy_line(line: &str) -> Option<(&str, String)> {
+    let (key, value) = line.split_once(':')?;
+    Some((key, decode_legacy_value(value)))
+}
+
+/// Escape a legacy value so it always fits on one line
+pub fn encode_legacy_value(value: &str) -> String {
+    value.replace('%', "%25").replace('\n', "%0A").replace('\r', "%0D")
+}
+
+/// Reverse `encode_legacy_value`; only those three escapes are recognized
+pub fn decode_legacy_value(value: &str) -> String {
+    value.replace("%0A", "\n").replace("%0D", "\r").replace("%25", "%")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_round_trip_with_special_characters() {
+        let output = UpdaterOutput::UpdateAvailable(ReleaseDetails {
+            version: "0.2.0".to_string(),
+            url: "https://example.com:8443/dl?file=a:b&x=\"y\"".to_string(),
+            checksum: "abc123".to_string(),
+            size_bytes: 42,
+            breaking_changes: true,
+            is_test: false,
+            release_date: "2025-12-01".to_string(),
+            changelog_url: "https://example.com/changelog#v0.2.0".to_string(),
+            min_compatible_version: "0.1.0".to_string(),
+        });
+        
+        let json = serde_json::to_string(&output).unwrap();
+        assert!(json.contains("\"status\":\"update_available\""));
+        
+        let stdout = format!("some noise\n{}\n", json);
+        assert_eq!(UpdaterOutput::parse(&stdout).unwrap(), output);
+        
+        let path = UpdaterOutput::DownloadComplete { path: "C:\\Program Files\\DriveGuard\\x.exe".to_string() };
+        assert_eq!(UpdaterOutput::parse(&serde_json::to_string(&path).unwrap()).unwrap(), path);
+        
+        assert!(UpdaterOutput::parse("garbage").is_err());
+    }
+    
+    #[test]
+    fn test_legacy_lines_with_colons() {
+        let checksum = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let stdout = format!(
+            "UPDATE_AVAILABLE:0.2.0\nURL:https://github.com/ArsenijN/driveguard/releases/download/v0.2.0/driveguard.exe\n\
+             CHECKSUM:{}\nSIZE:1048576\nBREAKING:false\nIS_TEST:false\nRELEASE_DATE:2025-12-01\n\
+             CHANGELOG_URL:https://github.com/ArsenijN/driveguard/releases/tag/v0.2.0\n", checksum);
+        
+        let output = UpdaterOutput::parse(&stdout).unwrap();
+        assert_eq!(output, UpdaterOutput::UpdateAvailable(ReleaseDetails {
+            version: "0.2.0".to_string(),
+            url: "https://github.com/ArsenijN/driveguard/releases/download/v0.2.0/driveguard.exe".to_string(),
+            checksum: checksum.to_string(),
+            size_bytes: 1_048_576,
+            breaking_changes: false,
+            is_test: false,
+            release_date: "2025-12-01".to_string(),
+            changelog_url: "https://github.com/ArsenijN/driveguard/releases/tag/v0.2.0".to_string(),
+            min_compatible_version: String::new(),
+        }));
+        
+        // Older updaters' JSON lacks the manifest details
+        let old = r#"{"status":"update_available","version":"0.2.0","url":"u","checksum":"c","size_bytes":1,"breaking_changes":false,"is_test":false}"#;
+        match UpdaterOutput::parse(old).unwrap() {
+            UpdaterOutput::UpdateAvailable(release) => assert!(release.release_date.is_empty() && release.changelog_url.is_empty()),
+            other => panic!("unexpected {:?}", other),
+        }
+        
+        assert_eq!(UpdaterOutput::parse("UP_TO_DATE\n").unwrap(), UpdaterOutput::UpToDate);
+        assert_eq!(UpdaterOutput::parse("INCOMPATIBLE:0.3.0:0.2.5").unwrap(), UpdaterOutput::Incompatible {
+            version: "0.3.0".to_string(),
+            min_compatible_version: "0.2.5".to_string(),
+        });
+        
+        // Values with newlines and percent signs survive a round trip
+        let tricky = "C:\\Updates\\100%\nline two";
+        let line = format!("DOWNLOAD_COMPLETE:{}", encode_legacy_value(tricky));
+        assert_eq!(line.lines().count(), 1);
+        assert_eq!(UpdaterOutput::parse(&line).unwrap(), UpdaterOutput::DownloadComplete { path: tricky.to_string() });
+    }
+    
+    #[test]
+    fn test_exit_codes() {
+        let codes: Vec<i32> = exit_code::ALL.iter().map(|(code, _)| *code).collect();
+        assert!(codes.iter().enumerate().all(|(i, code)| !codes[i + 1..].contains(code)));
+        
+        assert_eq!(UpdaterOutput::UpToDate.exit_code(), exit_code::SUCCESS);
+        assert_eq!(UpdaterOutput::UpdateAvailable(ReleaseDetails::default()).exit_code(), exit_code::UPDATE_AVAILABLE);
+        assert_eq!(UpdaterOutput::TestVersionNotAllowed { version: "0.3.0-test".to_string() }.exit_code(),
+                   exit_code::UPDATE_NOT_INSTALLABLE);
+        assert_eq!(exit_code::describe(exit_code::CHECKSUM), "checksum mismatch");
+        assert_eq!(exit_code::describe(99), "unknown exit code");
+    }
+}
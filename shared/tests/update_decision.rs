@@ -0,0 +1,93 @@
+// Which update gets offered for a given manifest and update settings
+
+use std::collections::HashMap;
+use driveguard_shared::manifest::{select_update, UpdateDecision, UpdateManifest, UpdateSettings, VersionInfo};
+
+fn version_info(min_compatible_version: &str) -> VersionInfo {
+    VersionInfo {
+        release_date: "2025-01-01".to_string(),
+        breaking_changes: false,
+        min_compatible_version: min_compatible_version.to_string(),
+        download_url: "https://example.com/driveguard.zip".to_string(),
+        checksum_sha256: "00".repeat(32),
+        changelog_url: "https://example.com/changelog".to_string(),
+        file_size_bytes: 1024,
+        has_patch: false,
+        patch_url: None,
+        patch_checksum: None,
+        patch_required_from: Vec::new(),
+        architecture: None,
+    }
+}
+
+fn manifest(latest: &str, min_compatible_version: &str) -> UpdateManifest {
+    let mut versions = HashMap::new();
+    versions.insert(latest.to_string(), version_info(min_compatible_version));
+    
+    UpdateManifest {
+        latest_version: latest.to_string(),
+        versions,
+    }
+}
+
+fn settings(allow_test_versions: bool, skipped: &[&str]) -> UpdateSettings {
+    UpdateSettings {
+        allow_test_versions,
+        skipped_versions: skipped.iter().map(|v| v.to_string()).collect(),
+        ..UpdateSettings::default()
+    }
+}
+
+#[test]
+fn newer_stable_is_offered() {
+    let decision = select_update(&manifest("0.2.1", "0.1.0"), "0.2.0", &settings(false, &[])).unwrap();
+    assert_eq!(decision, UpdateDecision::Offer("0.2.1".to_string()));
+}
+
+#[test]
+fn same_or_older_version_is_up_to_date() {
+    let s = settings(true, &[]);
+    assert_eq!(select_update(&manifest("0.2.0", "0.1.0"), "0.2.0", &s).unwrap(), UpdateDecision::UpToDate);
+    assert_eq!(select_update(&manifest("0.1.9", "0.1.0"), "0.2.0", &s).unwrap(), UpdateDecision::UpToDate);
+}
+
+#[test]
+fn newer_rc_requires_test_versions() {
+    let m = manifest("0.2.1r3", "0.1.0");
+    
+    assert_eq!(select_update(&m, "0.2.0", &settings(false, &[])).unwrap(),
+               UpdateDecision::TestVersionNotAllowed("0.2.1r3".to_string()));
+    assert_eq!(select_update(&m, "0.2.0", &settings(true, &[])).unwrap(),
+               UpdateDecision::Offer("0.2.1r3".to_string()));
+}
+
+#[test]
+fn skipped_version_is_not_offered() {
+    let decision = select_update(&manifest("0.2.1", "0.1.0"), "0.2.0", &settings(false, &["0.2.1"])).unwrap();
+    assert_eq!(decision, UpdateDecision::Skipped("0.2.1".to_string()));
+    
+    // Skipping an older release doesn't hide the newest one
+    let decision = select_update(&manifest("0.2.2", "0.1.0"), "0.2.0", &settings(false, &["0.2.1"])).unwrap();
+    assert_eq!(decision, UpdateDecision::Offer("0.2.2".to_string()));
+}
+
+#[test]
+fn below_min_compatible_version_is_refused() {
+    let decision = select_update(&manifest("0.3.0", "0.2.5"), "0.2.0", &settings(false, &[])).unwrap();
+    assert_eq!(decision, UpdateDecision::Incompatible {
+        version: "0.3.0".to_string(),
+        min_compatible_version: "0.2.5".to_string(),
+    });
+    
+    // Exactly at the minimum is fine
+    let decision = select_update(&manifest("0.3.0", "0.2.5"), "0.2.5", &settings(false, &[])).unwrap();
+    assert_eq!(decision, UpdateDecision::Offer("0.3.0".to_string()));
+}
+
+#[test]
+fn invalid_versions_are_errors() {
+    let s = settings(false, &[]);
+    assert!(select_update(&manifest("latest", "0.1.0"), "0.2.0", &s).is_err());
+    assert!(select_update(&manifest("0.3.0", "0.1.0"), "dev", &s).is_err());
+    assert!(select_update(&manifest("0.3.0", "any"), "0.2.0", &s).is_err());
+}
@@ -0,0 +1,98 @@
+// Running the updater with administrator rights
+// Used for the replace step when driveguard.exe is installed in a write-protected folder
+
+use std::path::Path;
+
+/// Start this updater elevated (showing the UAC prompt) with `args`, wait for it and return its exit code
+#[cfg(windows)]
+pub fn run_elevated(args: &[String], working_dir: &Path) -> Result<u32, String> {
+    use windows::core::{w, HRESULT, HSTRING, PCWSTR};
+    use windows::Win32::Foundation::{CloseHandle, ERROR_CANCELLED};
+    use windows::Win32::System::Threading::{GetExitCodeProcess, WaitForSingleObject, INFINITE};
+    use windows::Win32::UI::Shell::{ShellExecuteExW, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW};
+    use windows::Win32::UI::WindowsAndMessaging::SW_HIDE;
+    
+    let exe = std::env::current_exe().map_err(|e| format!("Failed to locate updater.exe: {}", e))?;
+    let file = HSTRING::from(exe.as_path());
+    let parameters = HSTRING::from(args.iter().map(|a| quote_argument(a)).collect::<Vec<_>>().join(" "));
+    let directory = HSTRING::from(working_dir);
+    
+    let mut info = SHELLEXECUTEINFOW {
+        cbSize: std::mem::size_of::<SHELLEXECUTEINFOW>() as u32,
+        fMask: SEE_MASK_NOCLOSEPROCESS,
+        lpVerb: w!("runas"),
+        lpFile: PCWSTR(file.as_ptr()),
+        lpParameters: PCWSTR(parameters.as_ptr()),
+        lpDirectory: PCWSTR(directory.as_ptr()),
+        nShow: SW_HIDE.0,
+        ..Default::default()
+    };
+    
+    unsafe {
+        if let Err(e) = ShellExecuteExW(&mut info) {
+            if e.code() == HRESULT::from_win32(ERROR_CANCELLED.0) {
+                return Err(format!(
+                    "Administrator permission was declined. DriveGuard is installed in {}, which can't be changed \
+                     without it; run the update again and choose Yes, or move DriveGuard to a folder you can write to",
+                    working_dir.display()));
+            }
+            return Err(format!("Failed to start the updater as administrator: {}", e));
+        }
+        
+        WaitForSingleObject(info.hProcess, INFINITE);
+        let mut exit_code = 0u32;
+        let result = GetExitCodeProcess(info.hProcess, &mut exit_code);
+        CloseHandle(info.hProcess).ok();
+        result.map_err(|e| format!("Failed to read the elevated updater's result: {}", e))?;
+        
+        Ok(exit_code)
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run_elevated(_args: &[String], working_dir: &Path) -> Result<u32, String> {
+    Err(format!("{} is write-protected and elevation is only supported on Windows", working_dir.display()))
+}
+
+/// Quote one argument for a Windows command line (as parsed by CommandLineToArgvW / the Rust runtime)
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn quote_argument(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    
+    for c in arg.chars() {
+        if c == '\\' {
+            backslashes += 1;
+            continue;
+        }
+        
+        // Backslashes only need escaping when they end up in front of a quote
+        let count = if c == '"' { backslashes * 2 + 1 } else { backslashes };
+        quoted.push_str(&"\\".repeat(count));
+        quoted.push(c);
+        backslashes = 0;
+    }
+    
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_quote_argument() {
+        assert_eq!(quote_argument("0.2.0"), "0.2.0");
+        assert_eq!(quote_argument("C:\\Program Files\\DriveGuard"), "\"C:\\Program Files\\DriveGuard\"");
+        // A trailing backslash must not escape the closing quote
+        assert_eq!(quote_argument("C:\\Program Files\\"), "\"C:\\Program Files\\\\\"");
+        assert_eq!(quote_argument("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(quote_argument(""), "\"\"");
+    }
+}
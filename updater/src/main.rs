@@ -1,12 +1,18 @@
 // DriveGuard Updater
 // Handles downloading and applying updates
 
+mod elevation;
+
 use std::env;
 use std::fs;
-use std::path::{PathBuf};
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use sha2::{Sha256, Digest};
-use driveguard_shared::manifest::{UpdateManifest, Version};
+use driveguard_shared::checksum;
+use driveguard_shared::pe;
+use driveguard_shared::manifest::{self, UpdateDecision, UpdateManifest, UpdateSettings, Version};
+use driveguard_shared::update_state;
+use driveguard_shared::updater_output::{self, encode_legacy_value, exit_code, ReleaseDetails, UpdaterOutput};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 // Embedded CA certificate (self-signed, safe to include in source)
 const CUSTOM_CA_CERT: &[u8] = br#"-----BEGIN CERTIFICATE-----
@@ -36,18 +42,47 @@ ohrbTfrrmDUvKMzPvhLvfUOI7u8nFZL9BXsEhttvhwG1KxQSN3NYCoK2Oted00Xy
 -----END CERTIFICATE-----"#;
 
 
+// Set by --json: print one JSON object instead of KEY:value lines
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+// Also trust the system certificate store, not only the embedded CA (same as trust_system_certificates)
+const TRUST_SYSTEM_CERTS_FLAG: &str = "--trust-system-certs";
+static TRUST_SYSTEM_CERTS: AtomicBool = AtomicBool::new(false);
+
+/// An error message and the exit code (see `updater_output::exit_code`) it ends the updater with
+type Failure = (i32, String);
+
 fn main() {
+    // Answered before anything else starts
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("--version") => {
+            println!("DriveGuard Updater {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Some("--help") | Some("-h") => {
+            print_usage();
+            return;
+        }
+        _ => {}
+    }
+    
     env_logger::init();
     
-    let args: Vec<String> = env::args().collect();
+    // --json may appear anywhere; the remaining arguments keep their positions
+    if args.iter().any(|a| a == updater_output::JSON_FLAG) {
+        JSON_OUTPUT.store(true, Ordering::SeqCst);
+    }
+    if args.iter().any(|a| a == TRUST_SYSTEM_CERTS_FLAG) {
+        TRUST_SYSTEM_CERTS.store(true, Ordering::SeqCst);
+    }
+    let args: Vec<String> = args
+        .into_iter()
+        .filter(|a| a != updater_output::JSON_FLAG && a != TRUST_SYSTEM_CERTS_FLAG)
+        .collect();
     
     if args.len() < 2 {
-        println!("DriveGuard Updater");
-        println!("Usage:");
-        println!("  updater.exe --check <manifest_url> <current_version>");
-        println!("  updater.exe --download <version> <url> <checksum>");
-        println!("  updater.exe --apply <version> <current_version>");
-        println!("  updater.exe --rollback");
+        print_usage();
         return;
     }
     
@@ -55,34 +90,303 @@ fn main() {
         "--check" => {
             if args.len() < 4 {
                 eprintln!("Error: --check requires manifest URL and current version");
-                std::process::exit(1);
+                std::process::exit(exit_code::USAGE);
             }
             check_for_updates(&args[2], &args[3]);
         }
         "--download" => {
             if args.len() < 5 {
                 eprintln!("Error: --download requires version, URL, and checksum");
-                std::process::exit(1);
+                std::process::exit(exit_code::USAGE);
             }
             download_update(&args[2], &args[3], &args[4]);
         }
         "--apply" => {
             if args.len() < 4 {
                 eprintln!("Error: --apply requires version and current version");
-                std::process::exit(1);
+                std::process::exit(exit_code::USAGE);
+            }
+            apply_update(&args[2], &args[3], !args[4..].iter().any(|a| a == "--no-restart"));
+        }
+        "--replace" => {
+            if args.len() < 7 {
+                eprintln!("Error: --replace requires version, current version, install folder, updates folder and checksum");
+                std::process::exit(exit_code::USAGE);
+            }
+            run_replace_step(&args[2], &args[3], Path::new(&args[4]), &args[5], &args[6]);
+        }
+        "--restore" => {
+            if args.len() < 6 {
+                eprintln!("Error: --restore requires version, install folder, updates folder and checksum");
+                std::process::exit(exit_code::USAGE);
             }
-            apply_update(&args[2], &args[3]);
+            run_restore_step(&args[2], Path::new(&args[3]), &args[4], &args[5]);
         }
         "--rollback" => {
-            rollback_update();
+            match args.get(2) {
+                Some(version) => rollback_broken_update(version),
+                None => rollback_update(),
+            }
         }
         _ => {
             eprintln!("Error: Unknown command: {}", args[1]);
-            std::process::exit(1);
+            std::process::exit(exit_code::USAGE);
+        }
+    }
+}
+
+fn print_usage() {
+    println!("DriveGuard Updater");
+    println!("Usage:");
+    println!("  updater.exe --check <manifest_url> <current_version>");
+    println!("  updater.exe --download <version> <url> <checksum>");
+    println!("  updater.exe --apply <version> <current_version> [--no-restart]");
+    println!("  updater.exe --rollback [version]  (with a version: restore that one and restart DriveGuard)");
+    println!("  updater.exe --replace <version> <current_version> <install_dir> <updates_dir> <checksum>  (elevated step of --apply)");
+    println!("  updater.exe --restore <version> <install_dir> <updates_dir> <checksum>  (elevated step of --rollback <version>)");
+    println!("  updater.exe --version | --help");
+    println!("Add --json to --check/--download for a single machine-readable JSON result.");
+    println!("Add {} to --check/--download to also trust the system certificate store (TLS-inspecting proxies).",
+             TRUST_SYSTEM_CERTS_FLAG);
+    println!("Exit codes:");
+    for (code, description) in exit_code::ALL {
+        println!("  {:>2}  {}", code, description);
+    }
+}
+
+/// Identifies the updater to update servers (GitHub's API rejects requests without a User-Agent),
+/// e.g. "DriveGuard-Updater/0.1.0 (windows; x86_64)"
+fn user_agent() -> String {
+    format!("DriveGuard-Updater/{} ({}; {})", env!("CARGO_PKG_VERSION"), env::consts::OS, env::consts::ARCH)
+}
+
+/// Apply the extra headers DriveGuard passed for the current update source to a request for
+/// `url`, if it's on that source's scheme, host and port; a manifest can point anywhere
+fn with_source_headers(mut request: reqwest::blocking::RequestBuilder, url: &str) -> reqwest::blocking::RequestBuilder {
+    let headers: Vec<(String, String)> = match env::var(manifest::UPDATE_HEADERS_ENV) {
+        Ok(value) => match serde_json::from_str(&value) {
+            Ok(h) => h,
+            Err(e) => {
+                log::warn!("Ignoring malformed {}: {}", manifest::UPDATE_HEADERS_ENV, e);
+                Vec::new()
+            }
+        },
+        Err(_) => Vec::new(),
+    };
+    
+    let source_url = env::var(manifest::UPDATE_SOURCE_URL_ENV).unwrap_or_default();
+    if !headers.is_empty() && !same_origin(url, &source_url) {
+        log::debug!("Not sending the source headers to {}, it's not on {}", url, source_url);
+        return request;
+    }
+    
+    for (name, value) in headers {
+        log::debug!("Sending header {}: {}", name, manifest::redact_header_value(&name, &value));
+        request = request.header(name.as_str(), value.as_str());
+    }
+    
+    request
+}
+
+/// Whether both URLs have the same scheme, host and port (default ports included)
+fn same_origin(url: &str, other: &str) -> bool {
+    match (reqwest::Url::parse(url), reqwest::Url::parse(other)) {
+        (Ok(url), Ok(other)) => url.origin().is_tuple() && url.origin() == other.origin(),
+        _ => false,
+    }
+}
+
+/// Update policy DriveGuard passed for this check (defaults when run by hand)
+fn update_settings() -> UpdateSettings {
+    match env::var(manifest::UPDATE_SETTINGS_ENV) {
+        Ok(value) => serde_json::from_str(&value).unwrap_or_else(|e| {
+            log::warn!("Ignoring malformed {}: {}", manifest::UPDATE_SETTINGS_ENV, e);
+            UpdateSettings::default()
+        }),
+        Err(_) => UpdateSettings::default(),
+    }
+}
+
+/// With the user's consent, tell the telemetry endpoint which version runs on which OS. Nothing
+/// else is sent (no source headers or tokens), and failures are only logged so the check goes on.
+fn send_telemetry(client: &reqwest::blocking::Client, current_version: &str) {
+    let settings = update_settings();
+    if !settings.telemetry_allowed() {
+        return;
+    }
+    
+    let report = serde_json::json!({
+        "version": current_version,
+        "os": env::consts::OS,
+        "arch": env::consts::ARCH,
+    });
+    let result = client
+        .post(&settings.telemetry_url)
+        .json(&report)
+        .timeout(std::time::Duration::from_secs(5))
+        .send()
+        .and_then(|response| response.error_for_status());
+    
+    match result {
+        Ok(_) => log::debug!("Sent usage report to {}", settings.telemetry_url),
+        Err(e) => log::debug!("Failed to send usage report to {}: {}", settings.telemetry_url, e),
+    }
+}
+
+/// Whether certificates from the system store are accepted next to the embedded CA. Needed behind
+/// proxies that re-sign TLS traffic with a company certificate; it also means any CA Windows trusts
+/// can vouch for the update server, not just ours.
+fn trust_system_certificates() -> bool {
+    let trust = TRUST_SYSTEM_CERTS.load(Ordering::SeqCst) || update_settings().trust_system_certificates;
+    if trust {
+        log::info!("Trusting the system certificate store in addition to the embedded CA");
+    }
+    trust
+}
+
+/// Print the update decision for DriveGuard to parse
+fn report_update(manifest: &UpdateManifest, current_version: &str) {
+    let decision = match manifest::select_update(manifest, current_version, &update_settings()) {
+        Ok(d) => d,
+        Err(e) => fail(exit_code::INVALID_MANIFEST, &e),
+    };
+    
+    let details = |version: String| {
+        let info = manifest.versions.get(&version);
+        ReleaseDetails {
+            is_test: Version::parse(&version).map(|v| v.is_test()).unwrap_or(false),
+            url: info.map(|i| i.download_url.clone()).unwrap_or_default(),
+            checksum: info.map(|i| i.checksum_sha256.clone()).unwrap_or_default(),
+            size_bytes: info.map(|i| i.file_size_bytes).unwrap_or(0),
+            breaking_changes: info.map(|i| i.breaking_changes).unwrap_or(false),
+            release_date: info.map(|i| i.release_date.clone()).unwrap_or_default(),
+            changelog_url: info.map(|i| i.changelog_url.clone()).unwrap_or_default(),
+            min_compatible_version: info.map(|i| i.min_compatible_version.clone()).unwrap_or_default(),
+            version,
+        }
+    };
+    
+    // Catch a build for the wrong CPU before downloading it
+    let installed = installed_architecture();
+    let output = match decision {
+        UpdateDecision::Offer(version) | UpdateDecision::Skipped(version)
+            if manifest.versions.get(&version)
+                .and_then(|i| i.architecture.as_deref())
+                .is_some_and(|available| available != installed) =>
+        {
+            let available = manifest.versions[&version].architecture.clone().unwrap_or_default();
+            UpdaterOutput::ArchitectureMismatch { version, available, installed: installed.to_string() }
+        }
+        UpdateDecision::Offer(version) => UpdaterOutput::UpdateAvailable(details(version)),
+        UpdateDecision::Skipped(version) => UpdaterOutput::UpdateSkipped(details(version)),
+        UpdateDecision::TestVersionNotAllowed(version) => UpdaterOutput::TestVersionNotAllowed { version },
+        UpdateDecision::Incompatible { version, min_compatible_version } => {
+            UpdaterOutput::Incompatible { version, min_compatible_version }
+        }
+        UpdateDecision::UpToDate => UpdaterOutput::UpToDate,
+    };
+    
+    emit(&output);
+    std::process::exit(output.exit_code());
+}
+
+/// Print a result as JSON (--json) or as the legacy KEY:value lines
+fn emit(output: &UpdaterOutput) {
+    if JSON_OUTPUT.load(Ordering::SeqCst) {
+        match serde_json::to_string(output) {
+            Ok(json) => println!("{}", json),
+            Err(e) => log::error!("Failed to serialize output: {}", e),
         }
+        return;
+    }
+    
+    let release = match output {
+        UpdaterOutput::UpdateAvailable(release) => {
+            println!("UPDATE_AVAILABLE:{}", encode_legacy_value(&release.version));
+            release
+        }
+        UpdaterOutput::UpdateSkipped(release) => {
+            println!("UPDATE_SKIPPED:{}", encode_legacy_value(&release.version));
+            release
+        }
+        UpdaterOutput::TestVersionNotAllowed { version } => {
+            println!("TEST_VERSION_NOT_ALLOWED:{}", encode_legacy_value(version));
+            return;
+        }
+        UpdaterOutput::Incompatible { version, min_compatible_version } => {
+            println!("INCOMPATIBLE:{}:{}", encode_legacy_value(version), encode_legacy_value(min_compatible_version));
+            return;
+        }
+        UpdaterOutput::ArchitectureMismatch { version, available, installed } => {
+            println!("ARCHITECTURE_MISMATCH:{}:{}:{}", encode_legacy_value(version),
+                     encode_legacy_value(available), encode_legacy_value(installed));
+            return;
+        }
+        UpdaterOutput::UpToDate => {
+            println!("UP_TO_DATE");
+            return;
+        }
+        UpdaterOutput::DownloadComplete { path } => {
+            println!("DOWNLOAD_COMPLETE:{}", encode_legacy_value(path));
+            return;
+        }
+        UpdaterOutput::Error { message } => {
+            eprintln!("Error: {}", message);
+            return;
+        }
+    };
+    
+    // Values are escaped so they stay on one line; readers split on the first colon only
+    println!("URL:{}", encode_legacy_value(&release.url));
+    println!("CHECKSUM:{}", encode_legacy_value(&release.checksum));
+    println!("SIZE:{}", release.size_bytes);
+    println!("BREAKING:{}", release.breaking_changes);
+    println!("IS_TEST:{}", release.is_test);
+    println!("RELEASE_DATE:{}", encode_legacy_value(&release.release_date));
+    println!("CHANGELOG_URL:{}", encode_legacy_value(&release.changelog_url));
+    println!("MIN_COMPATIBLE:{}", encode_legacy_value(&release.min_compatible_version));
+}
+
+/// Architecture of the installed driveguard.exe, or of this updater if it can't be read
+fn installed_architecture() -> &'static str {
+    match pe::read_machine(Path::new("driveguard.exe")) {
+        Ok(machine) => pe::machine_name(machine),
+        Err(e) => {
+            log::debug!("Using updater architecture: {}", e);
+            pe::current_architecture()
+        }
+    }
+}
+
+/// Log the error, report it (in JSON mode) and exit with `code`
+fn fail(code: i32, message: &str) -> ! {
+    log::error!("{}", message);
+    if JSON_OUTPUT.load(Ordering::SeqCst) {
+        emit(&UpdaterOutput::Error { message: message.to_string() });
+    }
+    std::process::exit(code);
+}
+
+/// A 404 or 500 still completes the request; report it as such instead of as a broken manifest or file
+fn ensure_success(response: &reqwest::blocking::Response, what: &str) -> Result<(), Failure> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else if status == reqwest::StatusCode::NOT_FOUND {
+        Err((exit_code::NOT_FOUND, format!("{} server returned {}", what, status)))
+    } else {
+        Err((exit_code::NETWORK, format!("{} server returned {}", what, status)))
     }
 }
 
+fn fetch_manifest(client: &reqwest::blocking::Client, manifest_url: &str) -> Result<UpdateManifest, Failure> {
+    let response = with_source_headers(client.get(manifest_url), manifest_url)
+        .send()
+        .map_err(|e| (exit_code::NETWORK, format!("Failed to fetch manifest: {}", e)))?;
+    ensure_success(&response, "Manifest")?;
+    response.json().map_err(|e| (exit_code::INVALID_MANIFEST, format!("Failed to parse manifest: {}", e)))
+}
+
 fn check_for_updates(manifest_url: &str, current_version: &str) {
     log::info!("Checking for updates from: {}", manifest_url);
     log::info!("Current version: {}", current_version);
@@ -99,63 +403,26 @@ fn check_for_updates(manifest_url: &str, current_version: &str) {
     };
     
     let client = match reqwest::blocking::Client::builder()
+        .user_agent(user_agent())
         .add_root_certificate(ca_cert)
+        .tls_built_in_root_certs(trust_system_certificates())
         .build()
     {
         Ok(c) => c,
         Err(e) => {
-            log::error!("Failed to create HTTP client: {}", e);
-            std::process::exit(1);
+            fail(exit_code::FAILURE, &format!("Failed to create HTTP client: {}", e));
         }
     };
     
-    let response = match client.get(manifest_url).send() {
-        Ok(resp) => resp,
-        Err(e) => {
-            log::error!("Failed to fetch manifest: {}", e);
-            std::process::exit(1);
-        }
-    };
-    
-    let manifest: UpdateManifest = match response.json() {
+    let manifest = match fetch_manifest(&client, manifest_url) {
         Ok(m) => m,
-        Err(e) => {
-            log::error!("Failed to parse manifest: {}", e);
-            std::process::exit(1);
-        }
+        Err((code, e)) => fail(code, &e),
     };
     
     log::info!("Latest version: {}", manifest.latest_version);
     
-    let current = match Version::parse(current_version) {
-        Ok(v) => v,
-        Err(e) => {
-            log::error!("Failed to parse current version: {}", e);
-            std::process::exit(1);
-        }
-    };
-    
-    let latest = match Version::parse(&manifest.latest_version) {
-        Ok(v) => v,
-        Err(e) => {
-            log::error!("Failed to parse latest version: {}", e);
-            std::process::exit(1);
-        }
-    };
-    
-    if latest > current {
-        println!("UPDATE_AVAILABLE:{}", manifest.latest_version);
-        
-        if let Some(version_info) = manifest.versions.get(&manifest.latest_version) {
-            println!("URL:{}", version_info.download_url);
-            println!("CHECKSUM:{}", version_info.checksum_sha256);
-            println!("SIZE:{}", version_info.file_size_bytes);
-            println!("BREAKING:{}", version_info.breaking_changes);
-            println!("IS_TEST:{}", latest.is_test());
-        }
-    } else {
-        println!("UP_TO_DATE");
-    }
+    send_telemetry(&client, current_version);
+    report_update(&manifest, current_version);
 }
 
 fn check_for_updates_insecure(manifest_url: &str, current_version: &str) {
@@ -164,70 +431,32 @@ fn check_for_updates_insecure(manifest_url: &str, current_version: &str) {
     
     // Fallback: accept any certificate (development only)
     let client = match reqwest::blocking::Client::builder()
+        .user_agent(user_agent())
         .danger_accept_invalid_certs(true)
         .build()
     {
         Ok(c) => c,
         Err(e) => {
-            log::error!("Failed to create HTTP client: {}", e);
-            std::process::exit(1);
-        }
-    };
-    
-    let response = match client.get(manifest_url).send() {
-        Ok(resp) => resp,
-        Err(e) => {
-            log::error!("Failed to fetch manifest: {}", e);
-            std::process::exit(1);
+            fail(exit_code::FAILURE, &format!("Failed to create HTTP client: {}", e));
         }
     };
     
-    let manifest: UpdateManifest = match response.json() {
+    let manifest = match fetch_manifest(&client, manifest_url) {
         Ok(m) => m,
-        Err(e) => {
-            log::error!("Failed to parse manifest: {}", e);
-            std::process::exit(1);
-        }
+        Err((code, e)) => fail(code, &e),
     };
     
     log::info!("Latest version: {}", manifest.latest_version);
     
-    let current = match Version::parse(current_version) {
-        Ok(v) => v,
-        Err(e) => {
-            log::error!("Failed to parse current version: {}", e);
-            std::process::exit(1);
-        }
-    };
-    
-    let latest = match Version::parse(&manifest.latest_version) {
-        Ok(v) => v,
-        Err(e) => {
-            log::error!("Failed to parse latest version: {}", e);
-            std::process::exit(1);
-        }
-    };
-    
-    if latest > current {
-        println!("UPDATE_AVAILABLE:{}", manifest.latest_version);
-        
-        if let Some(version_info) = manifest.versions.get(&manifest.latest_version) {
-            println!("URL:{}", version_info.download_url);
-            println!("CHECKSUM:{}", version_info.checksum_sha256);
-            println!("SIZE:{}", version_info.file_size_bytes);
-            println!("BREAKING:{}", version_info.breaking_changes);
-            println!("IS_TEST:{}", latest.is_test());
-        }
-    } else {
-        println!("UP_TO_DATE");
-    }
+    send_telemetry(&client, current_version);
+    report_update(&manifest, current_version);
 }
 
 fn download_update(version: &str, url: &str, expected_checksum: &str) {
     log::info!("Downloading update {} from {}", version, url);
     
     let filename = format!("driveguard_v{}.exe", version);
-    let download_path = PathBuf::from("updates").join("downloads").join(&filename);
+    let download_path = update_state::updates_dir().join("downloads").join(&filename);
     
     // Create downloads directory
     fs::create_dir_all(download_path.parent().unwrap()).ok();
@@ -243,180 +472,368 @@ fn download_update(version: &str, url: &str, expected_checksum: &str) {
     };
     
     let client = match reqwest::blocking::Client::builder()
+        .user_agent(user_agent())
         .add_root_certificate(ca_cert)
+        .tls_built_in_root_certs(trust_system_certificates())
         .build()
     {
         Ok(c) => c,
         Err(e) => {
-            log::error!("Failed to create HTTP client: {}", e);
-            std::process::exit(1);
+            fail(exit_code::FAILURE, &format!("Failed to create HTTP client: {}", e));
         }
     };
     
     // Download file
-    let mut response = match client.get(url).send() {
+    let mut response = match with_source_headers(client.get(url), url).send() {
         Ok(resp) => resp,
         Err(e) => {
-            log::error!("Failed to download: {}", e);
-            std::process::exit(1);
+            fail(exit_code::NETWORK, &format!("Failed to download: {}", e));
         }
     };
+    if let Err((code, e)) = ensure_success(&response, "Download") {
+        fail(code, &e);
+    }
     
     let mut file = match fs::File::create(&download_path) {
         Ok(f) => f,
         Err(e) => {
-            log::error!("Failed to create file: {}", e);
-            std::process::exit(1);
+            fail(exit_code::FILE_SYSTEM, &format!("Failed to create file: {}", e));
         }
     };
     
     if let Err(e) = std::io::copy(&mut response, &mut file) {
-        log::error!("Failed to write file: {}", e);
-        std::process::exit(1);
+        fail(exit_code::FILE_SYSTEM, &format!("Failed to write file: {}", e));
     }
     
     log::info!("Downloaded to: {}", download_path.display());
     
     // Verify checksum
-    let contents = fs::read(&download_path).unwrap();
-    let mut hasher = Sha256::new();
-    hasher.update(&contents);
-    let checksum = format!("{:x}", hasher.finalize());
+    let checksum = match checksum::sha256_file(&download_path) {
+        Ok(c) => c,
+        Err(e) => {
+            fail(exit_code::FILE_SYSTEM, &format!("Failed to read downloaded file: {}", e));
+        }
+    };
     
     if checksum != expected_checksum {
-        log::error!("Checksum mismatch! Expected: {}, Got: {}", expected_checksum, checksum);
         fs::remove_file(&download_path).ok();
-        std::process::exit(1);
+        fail(exit_code::CHECKSUM, &format!("Checksum mismatch! Expected: {}, Got: {}", expected_checksum, checksum));
     }
     
     log::info!("Checksum verified successfully");
-    println!("DOWNLOAD_COMPLETE:{}", download_path.display());
+    emit(&UpdaterOutput::DownloadComplete { path: download_path.display().to_string() });
 }
 
 fn download_update_insecure(version: &str, url: &str, expected_checksum: &str) {
     log::info!("Downloading update {} from {} (insecure)", version, url);
     
     let filename = format!("driveguard_v{}.exe", version);
-    let download_path = PathBuf::from("updates").join("downloads").join(&filename);
+    let download_path = update_state::updates_dir().join("downloads").join(&filename);
     
     // Create downloads directory
     fs::create_dir_all(download_path.parent().unwrap()).ok();
     
     let client = match reqwest::blocking::Client::builder()
+        .user_agent(user_agent())
         .danger_accept_invalid_certs(true)
         .build()
     {
         Ok(c) => c,
         Err(e) => {
-            log::error!("Failed to create HTTP client: {}", e);
-            std::process::exit(1);
+            fail(exit_code::FAILURE, &format!("Failed to create HTTP client: {}", e));
         }
     };
     
     // Download file
-    let mut response = match client.get(url).send() {
+    let mut response = match with_source_headers(client.get(url), url).send() {
         Ok(resp) => resp,
         Err(e) => {
-            log::error!("Failed to download: {}", e);
-            std::process::exit(1);
+            fail(exit_code::NETWORK, &format!("Failed to download: {}", e));
         }
     };
+    if let Err((code, e)) = ensure_success(&response, "Download") {
+        fail(code, &e);
+    }
     
     let mut file = match fs::File::create(&download_path) {
         Ok(f) => f,
         Err(e) => {
-            log::error!("Failed to create file: {}", e);
-            std::process::exit(1);
+            fail(exit_code::FILE_SYSTEM, &format!("Failed to create file: {}", e));
         }
     };
     
     if let Err(e) = std::io::copy(&mut response, &mut file) {
-        log::error!("Failed to write file: {}", e);
-        std::process::exit(1);
+        fail(exit_code::FILE_SYSTEM, &format!("Failed to write file: {}", e));
     }
     
     log::info!("Downloaded to: {}", download_path.display());
     
     // Verify checksum
-    let contents = fs::read(&download_path).unwrap();
-    let mut hasher = Sha256::new();
-    hasher.update(&contents);
-    let checksum = format!("{:x}", hasher.finalize());
+    let checksum = match checksum::sha256_file(&download_path) {
+        Ok(c) => c,
+        Err(e) => {
+            fail(exit_code::FILE_SYSTEM, &format!("Failed to read downloaded file: {}", e));
+        }
+    };
     
     if checksum != expected_checksum {
-        log::error!("Checksum mismatch! Expected: {}, Got: {}", expected_checksum, checksum);
         fs::remove_file(&download_path).ok();
-        std::process::exit(1);
+        fail(exit_code::CHECKSUM, &format!("Checksum mismatch! Expected: {}, Got: {}", expected_checksum, checksum));
     }
     
     log::info!("Checksum verified successfully");
-    println!("DOWNLOAD_COMPLETE:{}", download_path.display());
+    emit(&UpdaterOutput::DownloadComplete { path: download_path.display().to_string() });
 }
 
-fn apply_update(version: &str, current_version: &str) {
+/// Replace DriveGuard with the downloaded `version` and, if `restart`, start it again
+fn apply_update(version: &str, current_version: &str, restart: bool) {
     log::info!("Applying update from {} to version {}", current_version, version);
     
-    let new_exe = PathBuf::from("updates")
-        .join("downloads")
-        .join(format!("driveguard_v{}.exe", version));
+    let install_dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let result = file_checksum(&downloaded_exe(version)).and_then(|checksum| {
+        if update_state::is_dir_writable(&install_dir) {
+            replace_executable(version, current_version, &checksum)
+        } else {
+            // e.g. Program Files: only the replace runs elevated, DriveGuard is restarted unelevated below
+            log::info!("{} is write-protected, asking for administrator permission", install_dir.display());
+            replace_elevated(version, current_version, &checksum, &install_dir)
+        }
+    });
     
-    if !new_exe.exists() {
-        log::error!("Update file not found: {}", new_exe.display());
-        std::process::exit(1);
+    let current_exe = PathBuf::from("driveguard.exe");
+    
+    if let Err((code, e)) = result {
+        // Bring the old version back up and let it tell the user why nothing changed
+        if let Err(e) = update_state::write_apply_failed_marker(&e) {
+            log::warn!("Failed to write update marker: {}", e);
+        }
+        Command::new(&current_exe).spawn().ok();
+        fail(code, &e);
     }
     
-    let current_exe = PathBuf::from("driveguard.exe");
+    // DriveGuard counts its launches against this and rolls back if the new version keeps failing
+    let pending = update_state::PendingUpdate {
+        version: version.to_string(),
+        previous_version: current_version.to_string(),
+        launches: 0,
+        rollback_started: false,
+    };
+    if let Err(e) = update_state::write_pending_update(&pending) {
+        log::warn!("Failed to write update marker: {}", e);
+    }
     
-    // Create backup
-    let backup_dir = PathBuf::from("updates").join(format!("v{}", current_version));
-    fs::create_dir_all(&backup_dir).ok();
-    let backup_path = backup_dir.join("driveguard.exe");
+    if !restart {
+        // The user closed DriveGuard to finish the update and starts it again themselves
+        log::info!("Update applied, not restarting DriveGuard as requested");
+        if let Err(e) = update_state::write_no_restart_marker(version) {
+            log::warn!("Failed to write update marker: {}", e);
+        }
+        println!("UPDATE_APPLIED_NO_RESTART:{}", version);
+        return;
+    }
     
-    log::info!("Backing up current version to: {}", backup_path.display());
-    if let Err(e) = fs::copy(&current_exe, &backup_path) {
-        log::error!("Failed to create backup: {}", e);
-        std::process::exit(1);
+    // Restart DriveGuard
+    log::info!("Restarting DriveGuard...");
+    match Command::new(&current_exe).spawn() {
+        Ok(_) => {
+            println!("UPDATE_APPLIED:{}", version);
+        }
+        Err(e) => {
+            // The update itself succeeded, only the restart failed
+            log::error!("Update applied but failed to restart DriveGuard: {}", e);
+            if let Err(e) = update_state::write_no_restart_marker(version) {
+                log::warn!("Failed to write update marker: {}", e);
+            }
+            println!("UPDATE_APPLIED_NO_RESTART:{}", version);
+        }
+    }
+}
+
+/// Run `--replace` in an elevated copy of this updater and wait for it. The updates folder is
+/// writable without elevation, so the step gets the checksum of the file checked here and only
+/// installs a copy that still matches it.
+fn replace_elevated(version: &str, current_version: &str, checksum: &str, install_dir: &Path) -> Result<(), Failure> {
+    let args = vec![
+        "--replace".to_string(),
+        version.to_string(),
+        current_version.to_string(),
+        install_dir.display().to_string(),
+        absolute_updates_dir().display().to_string(),
+        checksum.to_string(),
+    ];
+    run_elevated_step(&args, install_dir)
+}
+
+/// Run `--restore` in an elevated copy of this updater and wait for it, checked like `--replace`
+fn restore_elevated(version: &str, checksum: &str, install_dir: &Path) -> Result<(), Failure> {
+    let args = vec![
+        "--restore".to_string(),
+        version.to_string(),
+        install_dir.display().to_string(),
+        absolute_updates_dir().display().to_string(),
+        checksum.to_string(),
+    ];
+    run_elevated_step(&args, install_dir)
+}
+
+/// The updates folder as an absolute path, for handing to an elevated step: that process may
+/// start elsewhere and run as a different user
+fn absolute_updates_dir() -> PathBuf {
+    env::current_dir()
+        .map(|dir| dir.join(update_state::updates_dir()))
+        .unwrap_or_else(|_| update_state::updates_dir())
+}
+
+fn run_elevated_step(args: &[String], install_dir: &Path) -> Result<(), Failure> {
+    // The elevated step's own exit code tells what went wrong
+    match elevation::run_elevated(args, install_dir).map_err(|e| (exit_code::FAILURE, e))? {
+        0 => Ok(()),
+        code => Err((code as i32, format!("The elevated update step failed ({}, exit code {}); DriveGuard was not changed",
+                                          exit_code::describe(code as i32), code))),
     }
+}
+
+/// `--replace`: the elevated half of `--apply`, which only swaps the executable
+fn run_replace_step(version: &str, current_version: &str, install_dir: &Path, updates_dir: &str, checksum: &str) {
+    if let Err(e) = env::set_current_dir(install_dir) {
+        fail(exit_code::FILE_SYSTEM, &format!("Failed to open {}: {}", install_dir.display(), e));
+    }
+    env::set_var(update_state::UPDATES_DIR_ENV, updates_dir);
     
-    // Replace executable
-    log::info!("Replacing executable...");
-    if let Err(e) = fs::remove_file(&current_exe) {
-        log::error!("Failed to remove old executable: {}", e);
-        std::process::exit(1);
+    if let Err((code, e)) = replace_executable(version, current_version, checksum) {
+        fail(code, &e);
     }
+}
+
+/// `--restore`: the elevated half of `--rollback <version>`, which only copies the backup back
+fn run_restore_step(version: &str, install_dir: &Path, updates_dir: &str, checksum: &str) {
+    if let Err(e) = env::set_current_dir(install_dir) {
+        fail(exit_code::FILE_SYSTEM, &format!("Failed to open {}: {}", install_dir.display(), e));
+    }
+    env::set_var(update_state::UPDATES_DIR_ENV, updates_dir);
     
-    if let Err(e) = fs::copy(&new_exe, &current_exe) {
-        log::error!("Failed to copy new executable: {}", e);
-        // Try to restore backup
-        fs::copy(&backup_path, &current_exe).ok();
-        std::process::exit(1);
+    if let Err((code, e)) = restore_executable(version, checksum) {
+        fail(code, &e);
+    }
+}
+
+/// The downloaded driveguard.exe of `version`
+fn downloaded_exe(version: &str) -> PathBuf {
+    update_state::updates_dir().join("downloads").join(format!("driveguard_v{}.exe", version))
+}
+
+/// SHA-256 of the update file (or backup) at `path`, taken before the install folder is touched
+fn file_checksum(path: &Path) -> Result<String, Failure> {
+    if !path.exists() {
+        return Err((exit_code::NOT_FOUND, format!("Update file not found: {}", path.display())));
+    }
+    checksum::sha256_file(path).map_err(|e| (exit_code::FILE_SYSTEM, format!("Failed to read {}: {}", path.display(), e)))
+}
+
+/// Copy `source` to `staged` next to driveguard.exe and check the SHA-256 of that copy. Only the
+/// copy in the install folder is used afterwards, so `source` changing after the check doesn't matter.
+fn stage_verified(source: &Path, staged: &Path, expected_checksum: &str) -> Result<(), Failure> {
+    fs::copy(source, staged)
+        .map_err(|e| (exit_code::FILE_SYSTEM, format!("Failed to copy {}: {}", source.display(), e)))?;
+    
+    match checksum::sha256_file(staged) {
+        Ok(actual) if actual.eq_ignore_ascii_case(expected_checksum) => Ok(()),
+        Ok(actual) => {
+            fs::remove_file(staged).ok();
+            Err((exit_code::CHECKSUM, format!("{} changed after it was checked! Expected: {}, Got: {}",
+                                              source.display(), expected_checksum, actual)))
+        }
+        Err(e) => {
+            fs::remove_file(staged).ok();
+            Err((exit_code::FILE_SYSTEM, format!("Failed to read {}: {}", staged.display(), e)))
+        }
+    }
+}
+
+/// Back up the installed driveguard.exe and put the downloaded version, if it still has
+/// `expected_checksum`, in its place
+fn replace_executable(version: &str, current_version: &str, expected_checksum: &str) -> Result<(), Failure> {
+    let new_exe = downloaded_exe(version);
+    
+    if !new_exe.exists() {
+        return Err((exit_code::NOT_FOUND, format!("Update file not found: {}", new_exe.display())));
+    }
+    
+    let current_exe = PathBuf::from("driveguard.exe");
+    let staged_exe = PathBuf::from("driveguard.exe.new");
+    stage_verified(&new_exe, &staged_exe, expected_checksum)?;
+    
+    let result = swap_in_staged(&staged_exe, &current_exe, version, current_version);
+    if result.is_err() {
+        fs::remove_file(&staged_exe).ok();
+        return result;
     }
     
     log::info!("Update applied successfully!");
     
     // Clean up download
     fs::remove_file(&new_exe).ok();
+    Ok(())
+}
+
+/// Check the verified `staged_exe`, back up `current_exe` and move the staged one over it
+fn swap_in_staged(staged_exe: &Path, current_exe: &Path, version: &str, current_version: &str) -> Result<(), Failure> {
+    // Never swap in something that isn't an executable for this machine
+    let new_machine = pe::read_machine(staged_exe).map_err(|e| (exit_code::FILE_SYSTEM, e))?;
+    let installed = match pe::read_machine(current_exe) {
+        Ok(m) => pe::machine_name(m),
+        Err(e) => {
+            log::warn!("Can't read installed executable ({}), comparing with updater architecture", e);
+            pe::current_architecture()
+        }
+    };
+    if pe::machine_name(new_machine) != installed {
+        return Err((exit_code::UPDATE_NOT_INSTALLABLE, format!(
+            "Update v{} is built for {} but the installed DriveGuard is {}; download the {} build instead",
+            version, pe::machine_name(new_machine), installed, installed)));
+    }
     
-    // Restart DriveGuard
-    log::info!("Restarting DriveGuard...");
-    Command::new(&current_exe)
-        .spawn()
-        .expect("Failed to restart DriveGuard");
+    // Create backup
+    let backup_path = backup_exe(current_version);
+    if let Some(backup_dir) = backup_path.parent() {
+        fs::create_dir_all(backup_dir).ok();
+    }
+    
+    log::info!("Backing up current version to: {}", backup_path.display());
+    fs::copy(current_exe, &backup_path)
+        .map_err(|e| (exit_code::FILE_SYSTEM, format!("Failed to create backup: {}", e)))?;
     
-    println!("UPDATE_APPLIED:{}", version);
+    // Replace executable; a rename within the folder either fully happens or leaves the old one
+    log::info!("Replacing executable...");
+    fs::rename(staged_exe, current_exe)
+        .map_err(|e| (exit_code::FILE_SYSTEM, format!("Failed to replace the old executable: {}", e)))
 }
 
 fn rollback_update() {
     log::info!("Rolling back to previous version");
     
     // Find most recent backup
-    let updates_dir = PathBuf::from("updates");
+    let updates_dir = update_state::updates_dir();
+    
+    // Never updated (fresh install) means there is simply nothing to roll back to
+    let entries = match fs::read_dir(&updates_dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::info!("Can't read {}: {}", updates_dir.display(), e);
+            fail(exit_code::NOT_FOUND, "No backup found to rollback to");
+        }
+    };
     
-    let mut versions: Vec<PathBuf> = fs::read_dir(&updates_dir)
-        .unwrap()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().is_dir())
-        .map(|e| e.path())
+    let mut versions: Vec<PathBuf> = entries
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry.path()),
+            Err(e) => {
+                log::warn!("Skipping unreadable entry in {}: {}", updates_dir.display(), e);
+                None
+            }
+        })
+        // Only folders holding a replaced version (not downloads/)
+        .filter(|path| path.is_dir() && path.join("driveguard.exe").exists())
         .collect();
     
     versions.sort();
@@ -424,17 +841,158 @@ fn rollback_update() {
     
     if let Some(backup_dir) = versions.first() {
         let backup_exe = backup_dir.join("driveguard.exe");
+        let current_exe = PathBuf::from("driveguard.exe");
+        if let Err(e) = fs::copy(&backup_exe, &current_exe) {
+            fail(exit_code::FILE_SYSTEM, &format!("Failed to restore {}: {}", backup_exe.display(), e));
+        }
         
-        if backup_exe.exists() {
-            let current_exe = PathBuf::from("driveguard.exe");
-            fs::copy(&backup_exe, &current_exe).expect("Failed to restore backup");
-            
-            log::info!("Rolled back to: {}", backup_dir.display());
-            println!("ROLLBACK_COMPLETE");
-            return;
+        log::info!("Rolled back to: {}", backup_dir.display());
+        println!("ROLLBACK_COMPLETE");
+        return;
+    }
+    
+    fail(exit_code::NOT_FOUND, "No backup found to rollback to");
+}
+
+/// `--rollback <version>`: started by DriveGuard when a freshly applied update kept failing to start.
+/// Puts `updates/v<version>` back, leaves the reason for the restored version to show and restarts it.
+fn rollback_broken_update(version: &str) {
+    log::info!("Rolling back a failed update to v{}", version);
+    
+    let install_dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let result = file_checksum(&backup_exe(version)).and_then(|checksum| {
+        if update_state::is_dir_writable(&install_dir) {
+            restore_executable(version, &checksum)
+        } else {
+            // Same as --apply: a Program Files install can only be changed elevated
+            log::info!("{} is write-protected, asking for administrator permission", install_dir.display());
+            restore_elevated(version, &checksum, &install_dir)
         }
+    });
+    if let Err((code, e)) = result {
+        fail(code, &e);
     }
     
-    log::error!("No backup found to rollback to");
-    std::process::exit(1);
-}
\ No newline at end of file
+    let current_exe = PathBuf::from("driveguard.exe");
+    let reason = format!("The update didn't start properly {} times in a row, so DriveGuard v{} was restored",
+                         update_state::MAX_UPDATE_LAUNCHES, version);
+    if let Err(e) = update_state::write_apply_failed_marker(&reason) {
+        log::warn!("Failed to write update marker: {}", e);
+    }
+    
+    log::info!("Rolled back to v{}, restarting DriveGuard", version);
+    Command::new(&current_exe).spawn().ok();
+    println!("ROLLBACK_COMPLETE");
+}
+
+/// The driveguard.exe of `version` that an update replaced
+fn backup_exe(version: &str) -> PathBuf {
+    update_state::updates_dir().join(format!("v{}", version)).join("driveguard.exe")
+}
+
+/// Copy the backup of `version`, if it still has `expected_checksum`, over the installed driveguard.exe
+fn restore_executable(version: &str, expected_checksum: &str) -> Result<(), Failure> {
+    let backup_exe = backup_exe(version);
+    if !backup_exe.exists() {
+        return Err((exit_code::NOT_FOUND, format!("No backup of v{} found to rollback to", version)));
+    }
+    
+    let current_exe = PathBuf::from("driveguard.exe");
+    let staged_exe = PathBuf::from("driveguard.exe.new");
+    stage_verified(&backup_exe, &staged_exe, expected_checksum)?;
+    
+    // DriveGuard exits right after starting us; give Windows a moment to release the executable
+    let mut attempts = 0;
+    while let Err(e) = fs::rename(&staged_exe, &current_exe) {
+        attempts += 1;
+        if attempts >= 10 {
+            fs::remove_file(&staged_exe).ok();
+            return Err((exit_code::FILE_SYSTEM, format!("Failed to restore {}: {}", backup_exe.display(), e)));
+        }
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    
+    /// Serve one request on a local port with `status_line` and a small HTML body, like a web server error page
+    fn serve_once(status_line: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/manifest.json", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request);
+            let body = "<html><body>Internal Server Error</body></html>";
+            write!(stream, "{}\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                   status_line, body.len(), body).ok();
+        });
+        url
+    }
+    
+    #[test]
+    fn test_stage_verified_checks_the_copy() {
+        let dir = env::temp_dir().join(format!("driveguard_stage_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (download, staged) = (dir.join("driveguard_v2.0.0.exe"), dir.join("driveguard.exe.new"));
+        fs::write(&download, "the checked update").unwrap();
+        let checksum = checksum::sha256_file(&download).unwrap();
+        
+        stage_verified(&download, &staged, &checksum).unwrap();
+        assert_eq!(fs::read_to_string(&staged).unwrap(), "the checked update");
+        
+        // Swapped after the unelevated check: refused, and nothing is left next to the executable
+        fs::write(&download, "something else").unwrap();
+        assert_eq!(stage_verified(&download, &staged, &checksum).unwrap_err().0, exit_code::CHECKSUM);
+        assert!(!staged.exists());
+        
+        fs::remove_dir_all(&dir).ok();
+    }
+    
+    #[test]
+    fn test_source_headers_stay_on_the_source() {
+        env::set_var(manifest::UPDATE_HEADERS_ENV, r#"[["Authorization", "Bearer secret"]]"#);
+        env::set_var(manifest::UPDATE_SOURCE_URL_ENV, "https://updates.example.com/driveguard/manifest.json");
+        let client = reqwest::blocking::Client::new();
+        let authorization = |url: &str| {
+            with_source_headers(client.get(url), url).build().unwrap().headers().get("Authorization").cloned()
+        };
+        
+        assert!(authorization("https://updates.example.com/driveguard/DriveGuard-2.0.0.exe").is_some());
+        assert!(authorization("https://updates.example.com:443/other/file.exe").is_some());
+        
+        // A manifest pointing its download at another host, scheme or port doesn't get the token
+        assert!(authorization("https://downloads.example.net/DriveGuard-2.0.0.exe").is_none());
+        assert!(authorization("https://example.com/DriveGuard-2.0.0.exe").is_none());
+        assert!(authorization("http://updates.example.com/driveguard/DriveGuard-2.0.0.exe").is_none());
+        assert!(authorization("https://updates.example.com:8443/DriveGuard-2.0.0.exe").is_none());
+        
+        env::remove_var(manifest::UPDATE_SOURCE_URL_ENV);
+        assert!(authorization("https://updates.example.com/driveguard/DriveGuard-2.0.0.exe").is_none());
+        env::remove_var(manifest::UPDATE_HEADERS_ENV);
+    }
+    
+    #[test]
+    fn test_fetch_manifest_reports_http_errors() {
+        let client = reqwest::blocking::Client::new();
+        
+        let url = serve_once("HTTP/1.1 500 Internal Server Error");
+        assert_eq!(fetch_manifest(&client, &url).unwrap_err(),
+                   (exit_code::NETWORK, "Manifest server returned 500 Internal Server Error".to_string()));
+        
+        let url = serve_once("HTTP/1.1 404 Not Found");
+        assert_eq!(fetch_manifest(&client, &url).unwrap_err(),
+                   (exit_code::NOT_FOUND, "Manifest server returned 404 Not Found".to_string()));
+        
+        // A 200 with something other than a manifest is still a parse error
+        let url = serve_once("HTTP/1.1 200 OK");
+        let (code, message) = fetch_manifest(&client, &url).unwrap_err();
+        assert_eq!(code, exit_code::INVALID_MANIFEST);
+        assert!(message.starts_with("Failed to parse manifest"));
+    }
+}